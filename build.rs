@@ -0,0 +1,117 @@
+//! find the resolved versions of the engines behind dollgen's integrations in `Cargo.lock`, for `features()`
+
+use std::{
+	env,
+	fs,
+	path::{Path, PathBuf},
+};
+
+/// the engines whose versions are reported, and the variable each is provided as
+const ENGINES: &[(&str, &str)] = &[
+	("liquid", "DOLLGEN_LIQUID_VERSION"),
+	("minijinja", "DOLLGEN_MINIJINJA_VERSION"),
+	("grass", "DOLLGEN_GRASS_VERSION"),
+	("wasm-bindgen-cli-support", "DOLLGEN_WASM_BINDGEN_VERSION"),
+	("markdoll", "DOLLGEN_MARKDOLL_VERSION"),
+	("notify", "DOLLGEN_NOTIFY_VERSION"),
+];
+
+/// a `[[package]]` of a lock file
+struct Package {
+	name: String,
+	version: String,
+	dependencies: Vec<String>,
+}
+
+fn main() {
+	println!("cargo::rerun-if-changed=build.rs");
+
+	let Some(lock) = find_lock() else {
+		println!("cargo::warning=Cargo.lock not found, engine versions won't be reported");
+		return;
+	};
+	println!("cargo::rerun-if-changed={}", lock.display());
+
+	let Ok(lock) = fs::read_to_string(&lock) else {
+		return;
+	};
+	let packages = parse_lock(&lock);
+
+	let version = env::var("CARGO_PKG_VERSION").unwrap();
+	let Some(dollgen) = packages
+		.iter()
+		.find(|package| package.name == "dollgen" && package.version == version)
+	else {
+		return;
+	};
+
+	for (engine, var) in ENGINES {
+		// a dependency is qualified with its version (ex: `liquid 0.26.11`) if several versions are locked, and older lock files add its source after that
+		let version =
+			dollgen
+				.dependencies
+				.iter()
+				.find_map(|dependency| match dependency.split_once(' ') {
+					Some((name, rest)) if name == *engine => {
+						rest.split(' ').next().map(ToString::to_string)
+					}
+					None if dependency == engine => packages
+						.iter()
+						.find(|package| package.name == *engine)
+						.map(|package| package.version.clone()),
+					_ => None,
+				});
+
+		if let Some(version) = version {
+			println!("cargo::rustc-env={var}={version}");
+		}
+	}
+}
+
+/// the lock file of the build, which is next to the target directory that `OUT_DIR` is in for dependents, or next to the manifest when building dollgen itself
+fn find_lock() -> Option<PathBuf> {
+	let out_dir = PathBuf::from(env::var_os("OUT_DIR")?);
+	let manifest_dir = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR")?);
+
+	out_dir
+		.ancestors()
+		.map(|dir| dir.join("Cargo.lock"))
+		.chain([manifest_dir.join("Cargo.lock")])
+		.find(|lock| Path::is_file(lock))
+}
+
+/// the packages of a lock file, which is simple enough to read without a toml parser
+fn parse_lock(lock: &str) -> Vec<Package> {
+	let mut packages = Vec::new();
+
+	for block in lock.split("[[package]]").skip(1) {
+		let mut package = Package {
+			name: String::new(),
+			version: String::new(),
+			dependencies: Vec::new(),
+		};
+		let mut in_dependencies = false;
+
+		for line in block.lines().map(str::trim) {
+			if in_dependencies {
+				if line == "]" {
+					in_dependencies = false;
+				} else {
+					package
+						.dependencies
+						.push(line.trim_end_matches(',').trim_matches('"').to_string());
+				}
+			} else if let Some(name) = line.strip_prefix("name = ") {
+				package.name = name.trim_matches('"').to_string();
+			} else if let Some(version) = line.strip_prefix("version = ") {
+				package.version = version.trim_matches('"').to_string();
+			} else if line == "dependencies = [" {
+				in_dependencies = true;
+			}
+		}
+
+		packages.push(package);
+	}
+
+	packages
+}
@@ -1,15 +1,15 @@
 use {
 	::anyhow::bail,
-	::core::cell::RefCell,
 	::dollgen::{
 		lang::markdoll::markdoll::{emit::html::HtmlEmit, MarkDoll},
 		liquid::{liquid::ParserBuilder, Liquid},
+		minijinja::Minijinja,
 		scss,
+		BuildOptions,
 		Pattern,
 		Rule,
 	},
-	::minijinja::Environment,
-	::std::{env, fs, path::Path, rc::Rc},
+	::std::{env, fs, path::Path},
 };
 
 fn main() -> Result<(), anyhow::Error> {
@@ -23,6 +23,10 @@ fn main() -> Result<(), anyhow::Error> {
 
 	env::set_current_dir("examples")?;
 
+	#[allow(
+		clippy::result_large_err,
+		reason = "the emitter and context factories must return `ErrorKind`"
+	)]
 	let doll_lang = {
 		let mut doll = MarkDoll::new();
 		doll.add_tags(::markdoll::ext::all_tags());
@@ -30,86 +34,131 @@ fn main() -> Result<(), anyhow::Error> {
 
 		::dollgen::lang::shared_lang(::dollgen::lang::markdoll::create(
 			doll,
-			|_| HtmlEmit::default(),
-			|_| (),
+			|_| Ok(HtmlEmit::default()),
+			|_| Ok(()),
 		))
 	};
 
 	let liquid = Liquid::new(ParserBuilder::new().stdlib().build().unwrap());
+	liquid.lock().unwrap().output_root = Some("deploy".into());
 
-	let minijinja = Rc::new(RefCell::new({
-		let mut env = Environment::new();
-		env.set_loader(|name| Ok(fs::read_to_string(name).ok()));
-		env
-	}));
+	let minijinja = Minijinja::new();
+	minijinja.write().unwrap().output_root = Some("deploy".into());
 
-	if let Err(err) = ::dollgen::run(&mut [
-		// liquid
-		Rule {
-			include: &[Pattern::new("src/(**)/(*).useliquid.doll")?],
-			exclude: &[Pattern::new("**/*.draft.*")?],
-			dst: "deploy/{0}/{1}.html",
-			plan: &mut ::dollgen::liquid::create_templated(
-				Path::new("templates/page.liquid").to_path_buf(),
-				liquid.clone(),
-				::dollgen::liquid::default_globals,
-				doll_lang.clone(),
-			),
-		},
-		Rule {
-			include: &[Pattern::new("src/(**)/(*).page.liquid")?],
-			exclude: &[Pattern::new("**/*.draft.*")?],
-			dst: "deploy/{0}/{1}.html",
-			plan: &mut ::dollgen::liquid::create_standalone(liquid.clone(), |_| Default::default()),
-		},
-		// jinja
-		Rule {
-			include: &[Pattern::new("src/(**)/(*).usejinja.doll")?],
-			exclude: &[Pattern::new("**/*.draft.*")?],
-			dst: "deploy/{0}/{1}.html",
-			plan: &mut ::dollgen::minijinja::create_templated(
-				Path::new("templates/awa.jinja").to_path_buf(),
-				minijinja.clone(),
-				::dollgen::minijinja::default_globals,
-				doll_lang.clone(),
-			),
-		},
-		Rule {
-			include: &[Pattern::new("src/(**)/(*).page.jinja")?],
-			exclude: &[Pattern::new("**/*.draft.*")?],
-			dst: "deploy/{0}/{1}.html",
-			plan: &mut ::dollgen::minijinja::create_standalone(minijinja.clone(), |_| {
-				Default::default()
-			}),
-		},
-		// other
-		Rule {
-			include: &[Pattern::new("src/(**)/(*).html")?],
+	// rules may be selected by tag (ex: `cargo run --example example -- scss`)
+	let select = env::args().skip(1).collect::<Vec<_>>();
+	let select = select.iter().map(String::as_str).collect::<Vec<_>>();
+
+	// the wasm format strings are checked against these captures when the rule is created
+	let wasm_include = [Pattern::new("src/(**)/.build-wasm")?];
+
+	if let Err(err) = ::dollgen::run_with(
+		&mut [
+			// liquid
+			Rule {
+				tags: &["liquid"],
+				..Rule::new(
+					&[Pattern::new("src/(**)/(*).useliquid.doll")?],
+					&[],
+					"deploy/{0}/{1}.html",
+					&mut ::dollgen::liquid::create_templated(
+						Path::new("templates/page.liquid").to_path_buf(),
+						liquid.clone(),
+						::dollgen::liquid::default_globals,
+						doll_lang.clone(),
+					),
+				)
+			},
+			Rule {
+				tags: &["liquid"],
+				..Rule::new(
+					&[Pattern::new("src/(**)/(*).page.liquid")?],
+					&[],
+					"deploy/{0}/{1}.html",
+					&mut ::dollgen::liquid::create_standalone(liquid.clone(), |_, _, _| {
+						Default::default()
+					}),
+				)
+			},
+			// jinja
+			Rule {
+				tags: &["jinja"],
+				..Rule::new(
+					&[Pattern::new("src/(**)/(*).usejinja.doll")?],
+					&[],
+					"deploy/{0}/{1}.html",
+					&mut ::dollgen::minijinja::create_templated(
+						Path::new("templates/awa.jinja").to_path_buf(),
+						minijinja.clone(),
+						::dollgen::minijinja::default_globals,
+						doll_lang.clone(),
+					),
+				)
+			},
+			Rule {
+				tags: &["jinja"],
+				..Rule::new(
+					&[Pattern::new("src/(**)/(*).page.jinja")?],
+					&[],
+					"deploy/{0}/{1}.html",
+					&mut ::dollgen::minijinja::create_standalone(minijinja.clone(), |_, _, _| {
+						Default::default()
+					}),
+				)
+			},
+			// other
+			Rule {
+				tags: &["assets"],
+				..Rule::new(
+					&[Pattern::new("src/(**)/(*).html")?],
+					&[],
+					"deploy/{0}/{1}.html",
+					&mut ::dollgen::copy,
+				)
+			},
+			Rule {
+				tags: &["wasm"],
+				..Rule::new(
+					&wasm_include,
+					&[],
+					"deploy/{0}.wasm",
+					&mut ::dollgen::wasm::create_both(
+						::dollgen::wasm::Profile::from_env(
+							"DOLLGEN_WASM_PROFILE",
+							::dollgen::wasm::Profile::Release,
+						),
+						&wasm_include,
+						"deploy/{0}.js",
+						"gen_types/{0}.d.ts",
+					)?,
+				)
+			},
+			Rule {
+				tags: &["scss"],
+				..Rule::new(
+					&[Pattern::new("src/(**)/(*).scss")?],
+					&[],
+					"deploy/{0}/{1}.css",
+					&mut scss::create(&scss::options().style(scss::grass::OutputStyle::Compressed)),
+				)
+			},
+			Rule {
+				tags: &["assets"],
+				..Rule::new(
+					&[Pattern::new("src/(**)/(*).asset.(*)")?],
+					&[],
+					"deploy/{0}/{1}.{2}",
+					&mut ::dollgen::copy,
+				)
+			},
+		],
+		&BuildOptions {
+			select: &select,
 			exclude: &[Pattern::new("**/*.draft.*")?],
-			dst: "deploy/{0}/{1}.html",
-			plan: &mut ::dollgen::copy,
-		},
-		Rule {
-			include: &[Pattern::new("src/(**)/.build-wasm")?],
-			exclude: &[],
-			dst: "deploy/{0}.wasm",
-			plan: &mut ::dollgen::wasm::create_both(true, "deploy/{0}.js", "gen_types/{0}.d.ts"),
-		},
-		Rule {
-			include: &[Pattern::new("src/(**)/(*).scss")?],
-			exclude: &[],
-			dst: "deploy/{0}/{1}.css",
-			plan: &mut scss::create(
-				&scss::grass::Options::default().style(scss::grass::OutputStyle::Compressed),
-			),
-		},
-		Rule {
-			include: &[Pattern::new("src/(**)/(*).asset.(*)")?],
-			exclude: &[],
-			dst: "deploy/{0}/{1}.{2}",
-			plan: &mut ::dollgen::copy,
+			output_root: Some(Path::new("deploy")),
+			..Default::default()
 		},
-	]) {
+	) {
 		println!("{err:#?}\n{err}");
 	}
 
@@ -0,0 +1,318 @@
+//! rewrite asset urls in html, so that pages may reference assets by their logical url (ex: `/style.css`) while the assets are written elsewhere (ex: fingerprinted, as `/style.3f2a9c.css`)
+//!
+//! the map from logical urls to real ones is built by whatever renamed the assets, and is shared with the rules that plan pages through the [`Context`] as [`AssetUrls`]:
+//!
+//! - wrap the plan closure of those rules with [`rewrite_urls`]
+//! - or wrap a single transformation with [`RewriteUrls`]
+//!
+//! only `src` and `href` attributes are rewritten, and only when the url has one of the given extensions, urls in text, scripts, styles, and comments are left alone
+//!
+//! requires `assets` feature
+
+use {
+	crate::{util::hash_bytes, Context, ErrorKind, PlannedTransformation, Sink},
+	::core::hash::Hasher,
+	::std::{
+		collections::BTreeMap,
+		path::{Path, PathBuf},
+		sync::{Arc, Mutex, PoisonError},
+	},
+	::tracing::{instrument, Level},
+};
+
+/// a map from logical asset urls to the urls they were actually written to (ex: `/style.css` to `/style.3f2a9c.css`)
+///
+/// the map is shared, so this is cheap to clone into every plan
+#[derive(Clone, Default, Debug)]
+pub struct AssetUrls(pub Arc<BTreeMap<String, String>>);
+
+impl AssetUrls {
+	/// the url to use instead of `url`, keeping its query and fragment, if `url` has one of `extensions` and is in the map
+	#[must_use]
+	pub fn rewrite(&self, url: &str, extensions: &[String]) -> Option<String> {
+		let (path, suffix) = url.split_at(url.find(['?', '#']).unwrap_or(url.len()));
+
+		let extension = Path::new(path).extension()?.to_str()?;
+		if !extensions.iter().any(|ext| ext == extension) {
+			return None;
+		}
+
+		self.0.get(path).map(|real| format!("{real}{suffix}"))
+	}
+
+	/// rewrite the `src` and `href` attributes of every tag in `html` (see [`AssetUrls::rewrite`])
+	#[must_use]
+	pub fn rewrite_html(&self, html: &str, extensions: &[String]) -> String {
+		let mut out = String::with_capacity(html.len());
+		let mut rest = html;
+
+		while let Some(start) = rest.find('<') {
+			out.push_str(&rest[..start]);
+			rest = &rest[start..];
+
+			if rest.starts_with("<!--") {
+				let end = rest.find("-->").map_or(rest.len(), |end| end + 3);
+				out.push_str(&rest[..end]);
+				rest = &rest[end..];
+				continue;
+			}
+
+			let end = tag_end(rest);
+			let tag = &rest[..end];
+			self.rewrite_tag(tag, extensions, &mut out);
+			rest = &rest[end..];
+
+			// the contents of raw text elements are not html
+			let name = tag[1..]
+				.split(|c: char| c.is_ascii_whitespace() || c == '>' || c == '/')
+				.next()
+				.unwrap_or_default();
+			if name.eq_ignore_ascii_case("script") || name.eq_ignore_ascii_case("style") {
+				let end = rest
+					.to_ascii_lowercase()
+					.find(&format!("</{}", name.to_ascii_lowercase()))
+					.unwrap_or(rest.len());
+				out.push_str(&rest[..end]);
+				rest = &rest[end..];
+			}
+		}
+
+		out.push_str(rest);
+		out
+	}
+
+	/// rewrite the `src` and `href` attributes of a single tag, from `<` to `>`
+	fn rewrite_tag(&self, tag: &str, extensions: &[String], out: &mut String) {
+		let bytes = tag.as_bytes();
+		let is_space = |index: usize| bytes[index].is_ascii_whitespace();
+		let mut copied = 0;
+
+		// skip the `<` and the tag name
+		let mut index = 0;
+		while index < bytes.len() && !is_space(index) {
+			index += 1;
+		}
+
+		while index < bytes.len() {
+			let name_start = index;
+			while index < bytes.len() && !is_space(index) && !b"=>/".contains(&bytes[index]) {
+				index += 1;
+			}
+			if index == name_start {
+				index += 1;
+				continue;
+			}
+			let name = &tag[name_start..index];
+
+			while index < bytes.len() && is_space(index) {
+				index += 1;
+			}
+			if bytes.get(index) != Some(&b'=') {
+				continue;
+			}
+			index += 1;
+			while index < bytes.len() && is_space(index) {
+				index += 1;
+			}
+
+			let (value_start, value_end) = if let Some(&quote @ (b'"' | b'\'')) = bytes.get(index) {
+				let end = tag[index + 1..]
+					.find(char::from(quote))
+					.map_or(bytes.len(), |end| index + 1 + end);
+				(index + 1, end)
+			} else {
+				let end = tag[index..]
+					.find(|c: char| c.is_ascii_whitespace() || c == '>')
+					.map_or(bytes.len(), |end| index + end);
+				(index, end)
+			};
+			index = value_end + 1;
+
+			if name.eq_ignore_ascii_case("src") || name.eq_ignore_ascii_case("href") {
+				if let Some(url) = self.rewrite(&tag[value_start..value_end], extensions) {
+					out.push_str(&tag[copied..value_start]);
+					out.push_str(&url);
+					copied = value_end;
+				}
+			}
+		}
+
+		out.push_str(&tag[copied..]);
+	}
+}
+
+/// the length of the tag at the start of `html`, up to and including its `>`, ignoring any in quoted attribute values
+fn tag_end(html: &str) -> usize {
+	let mut quote = None;
+
+	for (index, c) in html.char_indices() {
+		match (quote, c) {
+			(None, '"' | '\'') => quote = Some(c),
+			(Some(open), _) if open == c => quote = None,
+			(None, '>') => return index + 1,
+			_ => {}
+		}
+	}
+
+	html.len()
+}
+
+/// a plan to rewrite the asset urls in the html output of another transformation (see [`AssetUrls::rewrite_html`])
+///
+/// the inner transformation must support [`Sink::Buffer`] (ex: templates), as its output is rewritten before it is written
+#[derive(Debug)]
+pub struct RewriteUrls {
+	/// the transformation whose output is rewritten
+	pub inner: Box<dyn PlannedTransformation>,
+	/// the urls to rewrite
+	pub urls: AssetUrls,
+	/// which extensions to rewrite urls with (ex: `["css", "js"]`)
+	pub extensions: Vec<String>,
+}
+
+impl PlannedTransformation for RewriteUrls {
+	fn execute(self: Box<Self>, dst: PathBuf) -> Result<(), ErrorKind> {
+		self.execute_to(Sink::File(dst))
+	}
+
+	#[instrument(name = "rewrite asset urls", level = Level::DEBUG)]
+	fn execute_to(self: Box<Self>, sink: Sink) -> Result<(), ErrorKind> {
+		if self.inner.is_noop() {
+			return self.inner.execute_to(sink);
+		}
+
+		let buffer = Arc::new(Mutex::new(Vec::new()));
+		self.inner.execute_to(Sink::Buffer(buffer.clone()))?;
+
+		let html = String::from_utf8(::core::mem::take(
+			&mut *buffer.lock().unwrap_or_else(PoisonError::into_inner),
+		))
+		.map_err(|_| ErrorKind::NonUTF8Characters)?;
+
+		sink.write_all(self.urls.rewrite_html(&html, &self.extensions).as_bytes())
+	}
+
+	fn is_noop(&self) -> bool {
+		self.inner.is_noop()
+	}
+
+	fn outputs(&self, dst: &Path) -> Vec<PathBuf> {
+		self.inner.outputs(dst)
+	}
+
+	fn inputs(&self) -> Vec<PathBuf> {
+		self.inner.inputs()
+	}
+
+	fn force(&mut self) {
+		self.inner.force();
+	}
+
+	fn fingerprint(&self, state: &mut dyn Hasher) {
+		self.inner.fingerprint(state);
+
+		state.write_usize(self.extensions.len());
+		for extension in &self.extensions {
+			hash_bytes(state, extension.as_bytes());
+		}
+
+		state.write_usize(self.urls.0.len());
+		for (logical, real) in &*self.urls.0 {
+			hash_bytes(state, logical.as_bytes());
+			hash_bytes(state, real.as_bytes());
+		}
+	}
+}
+
+/// wrap a plan closure, so that the urls in its output are rewritten by the [`AssetUrls`] in the [`Context`] (see [`RewriteUrls`])
+///
+/// - `extensions` - which extensions to rewrite urls with (ex: `&["css", "js"]`)
+/// - `plan` - the plan closure to wrap (ex: `liquid::create_templated`)
+///
+/// if the context has no [`AssetUrls`], the output is left alone
+pub fn rewrite_urls(
+	extensions: &[&str],
+	mut plan: impl FnMut(
+		PathBuf,
+		Vec<String>,
+		&Path,
+		&Context,
+	) -> Result<Box<dyn PlannedTransformation>, ErrorKind>,
+) -> impl FnMut(PathBuf, Vec<String>, &Path, &Context) -> Result<Box<dyn PlannedTransformation>, ErrorKind>
+{
+	let extensions = extensions
+		.iter()
+		.map(ToString::to_string)
+		.collect::<Vec<_>>();
+
+	move |src, captures, dst: &Path, context: &Context| {
+		let inner = plan(src, captures, dst, context)?;
+
+		Ok(match context.get::<AssetUrls>() {
+			Some(urls) => Box::new(RewriteUrls {
+				inner,
+				urls: urls.clone(),
+				extensions: extensions.clone(),
+			}),
+			None => inner,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn urls() -> AssetUrls {
+		AssetUrls(Arc::new(BTreeMap::from([
+			("/style.css".to_string(), "/style.3f2a9c.css".to_string()),
+			("/app.js".to_string(), "/app.9b1e07.js".to_string()),
+		])))
+	}
+
+	fn rewrite(html: &str) -> String {
+		urls().rewrite_html(html, &["css".to_string(), "js".to_string()])
+	}
+
+	#[test]
+	fn rewrites_src_and_href() {
+		assert_eq!(
+			rewrite(r#"<link rel="stylesheet" href="/style.css"><script src='/app.js'></script>"#),
+			r#"<link rel="stylesheet" href="/style.3f2a9c.css"><script src='/app.9b1e07.js'></script>"#
+		);
+		assert_eq!(
+			rewrite("<link href=/style.css?v=1#top >"),
+			"<link href=/style.3f2a9c.css?v=1#top >"
+		);
+		assert_eq!(
+			rewrite(r#"<LINK HREF = "/style.css">"#),
+			r#"<LINK HREF = "/style.3f2a9c.css">"#
+		);
+	}
+
+	#[test]
+	fn leaves_other_urls_alone() {
+		for html in [
+			// not in the map, or without a rewritten extension
+			r#"<a href="/other.css">"#,
+			r#"<a href="/style.css.map">"#,
+			// not an attribute
+			r#"<a title="/style.css">/style.css</a>"#,
+			r#"<div data-src="/app.js">"#,
+			// not html
+			r#"<!-- <link href="/style.css"> -->"#,
+			r#"<script>load("<img src='/app.js'>")</script>"#,
+			r#"<style>a::after { content: '<img src="/style.css">' }</style>"#,
+		] {
+			assert_eq!(rewrite(html), html);
+		}
+	}
+
+	#[test]
+	fn quoted_angle_brackets_stay_in_the_tag() {
+		assert_eq!(
+			rewrite(r#"<img alt="a > b" src="/app.js">"#),
+			r#"<img alt="a > b" src="/app.9b1e07.js">"#
+		);
+	}
+}
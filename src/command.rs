@@ -0,0 +1,214 @@
+//! run an external program as a transformation, for tools dollgen doesn't integrate (ex: `tailwindcss`, `esbuild`, or `magick`)
+//!
+//! arguments are format-strings (see [`format`](crate::format)), where `{src}` and `{dst}` are the source and output paths, and `{0}`, `{1}`, etc are the captures
+//!
+//! ex: `command("esbuild", &["{src}", "--bundle", "--minify", "--outfile={dst}"])`
+//!
+//! requires `command` feature
+
+use {
+	crate::{
+		format_named,
+		util::hash_bytes,
+		Context,
+		ErrorKind,
+		Jobs,
+		PlannedTransformation,
+		Sink,
+	},
+	::core::hash::Hasher,
+	::std::{
+		path::{Path, PathBuf},
+		process::Command,
+	},
+	::tracing::{debug, instrument, trace, Level},
+};
+
+/// a planned run of an external program
+///
+/// if `stdout` is set, what the program writes to standard output is the output (so this supports every [`Sink`]), otherwise the program is expected to write to the output path itself
+#[derive(Clone, Debug)]
+pub struct RunCommand {
+	/// the program to run, found on the `PATH` if it has no separators
+	pub program: String,
+	/// the arguments to run it with, already formatted
+	pub args: Vec<String>,
+	/// whether standard output is the output
+	pub stdout: bool,
+	/// the source file, which the program reads
+	pub src: PathBuf,
+	/// the build's limit on programs running at once, which a slot is held in while the program runs (see [`Jobs`])
+	///
+	/// if `None`, the program runs whenever it is executed
+	pub jobs: Option<Jobs>,
+}
+
+impl RunCommand {
+	/// run the program, returning its standard output
+	fn run(&self) -> Result<Vec<u8>, CommandErrorKind> {
+		let _slot = self.jobs.as_ref().map(Jobs::acquire);
+		let out = Command::new(&self.program)
+			.args(&self.args)
+			.output()
+			.map_err(|err| CommandErrorKind::ProcessFailed {
+				program: self.program.clone(),
+				err,
+			})?;
+
+		let stderr = String::from_utf8_lossy(&out.stderr).into_owned();
+
+		if !out.status.success() {
+			return Err(CommandErrorKind::Failed {
+				program: self.program.clone(),
+				status: out.status.to_string(),
+				span: (0, stderr.len()),
+				stderr,
+			});
+		}
+
+		if !stderr.is_empty() {
+			debug!(
+				program = self.program,
+				stderr, "command succeeded with output on stderr"
+			);
+		}
+
+		Ok(out.stdout)
+	}
+}
+
+impl PlannedTransformation for RunCommand {
+	#[instrument(name = "run command", level = Level::DEBUG)]
+	fn execute(self: Box<Self>, dst: PathBuf) -> Result<(), ErrorKind> {
+		self.execute_to(Sink::File(dst))
+	}
+
+	fn execute_to(self: Box<Self>, sink: Sink) -> Result<(), ErrorKind> {
+		if self.stdout {
+			return sink.write_all(&self.run()?);
+		}
+
+		if sink.path().is_none() {
+			return Err(ErrorKind::UnsupportedSink("command"));
+		}
+
+		let stdout = self.run()?;
+		if !stdout.is_empty() {
+			trace!(stdout = %String::from_utf8_lossy(&stdout), "command output");
+		}
+
+		Ok(())
+	}
+
+	fn inputs(&self) -> Vec<PathBuf> {
+		vec![self.src.clone()]
+	}
+
+	fn fingerprint(&self, state: &mut dyn Hasher) {
+		hash_bytes(state, self.program.as_bytes());
+		state.write_usize(self.args.len());
+		for arg in &self.args {
+			hash_bytes(state, arg.as_bytes());
+		}
+		state.write_u8(self.stdout.into());
+	}
+}
+
+/// plan running `program` with `args` for each source file, where the program writes to the output path itself (ex: `{dst}` is an argument)
+///
+/// - `program` - the program to run (ex: `tailwindcss`)
+/// - `args` - format-strings for each argument (ex: `&["-i", "{src}", "-o", "{dst}", "--minify"]`)
+///
+/// a non-zero exit status fails the build, with the program's standard error
+pub fn command(
+	program: &str,
+	args: &[&str],
+) -> impl FnMut(PathBuf, Vec<String>, &Path, &Context) -> Result<Box<dyn PlannedTransformation>, ErrorKind>
+{
+	plan(program, args, false)
+}
+
+/// plan running `program` with `args` for each source file, where what the program writes to standard output is the output
+///
+/// - `program` - the program to run (ex: `magick`)
+/// - `args` - format-strings for each argument (ex: `&["{src}", "-resize", "50%", "webp:-"]`)
+///
+/// a non-zero exit status fails the build, with the program's standard error
+pub fn command_stdout(
+	program: &str,
+	args: &[&str],
+) -> impl FnMut(PathBuf, Vec<String>, &Path, &Context) -> Result<Box<dyn PlannedTransformation>, ErrorKind>
+{
+	plan(program, args, true)
+}
+
+fn plan(
+	program: &str,
+	args: &[&str],
+	stdout: bool,
+) -> impl FnMut(PathBuf, Vec<String>, &Path, &Context) -> Result<Box<dyn PlannedTransformation>, ErrorKind>
+{
+	let program = program.to_string();
+	let args = args.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+	move |src, mut captures, dst: &Path, context: &Context| {
+		// `{src}` and `{dst}` are named captures after the real ones
+		let mut names = vec![None; captures.len()];
+		names.extend([Some("src"), Some("dst")]);
+		captures.push(
+			src.to_str()
+				.ok_or(ErrorKind::NonUTF8PathCharacters)?
+				.to_string(),
+		);
+		captures.push(
+			dst.to_str()
+				.ok_or(ErrorKind::NonUTF8PathCharacters)?
+				.to_string(),
+		);
+
+		Ok(Box::new(RunCommand {
+			program: program.clone(),
+			args: args
+				.iter()
+				.map(|arg| format_named(arg, &captures, &names))
+				.collect::<Result<_, _>>()?,
+			stdout,
+			src,
+			jobs: context.get::<Jobs>().cloned(),
+		}))
+	}
+}
+
+/// an error while running an external program
+#[derive(::thiserror::Error, ::miette::Diagnostic, Debug)]
+pub enum CommandErrorKind {
+	/// the program couldn't be started
+	#[error("failed to run `{program}`")]
+	#[diagnostic(
+		code(dollgen::command::process_fail),
+		help("is `{program}` installed and on the PATH?")
+	)]
+	ProcessFailed {
+		/// the program
+		program: String,
+		/// why it couldn't be started
+		#[source]
+		err: ::std::io::Error,
+	},
+
+	/// the program exited unsuccessfully
+	#[error("`{program}` failed ({status})")]
+	#[diagnostic(code(dollgen::command::fail), help("stderr provided"))]
+	Failed {
+		/// the program
+		program: String,
+		/// how it exited (ex: `exit status: 1`)
+		status: String,
+		/// the standard error output of the program
+		#[source_code]
+		stderr: String,
+		/// spans from the start to the end of `stderr`, used for miette diagnostics
+		#[label]
+		span: (usize, usize),
+	},
+}
@@ -0,0 +1,275 @@
+//! precompress outputs, writing compressed copies next to them (ex: `app.wasm.br` and `app.wasm.gz` next to `app.wasm`) for hosts that serve precompressed variants
+//!
+//! - wrap a transformation with [`Precompress`], or the plan closure of a rule with [`precompress`]
+//! - compress wasm while compiling it with `wasm::CompileOptions::compress`
+//!
+//! requires `compress` feature
+
+use {
+	crate::{
+		filesystem::{Fs, StdFs},
+		util::hash_bytes,
+		Context,
+		ErrorKind,
+		PlannedTransformation,
+	},
+	::std::{
+		ffi::OsString,
+		hash::Hasher,
+		io::{self, Write},
+		path::{Path, PathBuf},
+	},
+	::tracing::{instrument, trace, Level},
+};
+
+/// a compression format to write a copy of an output in
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Encoding {
+	/// gzip, written with a `.gz` suffix, which every browser supports
+	Gzip,
+	/// brotli, written with a `.br` suffix, which compresses better than gzip (especially wasm) but is only served over https
+	Brotli,
+}
+
+impl Encoding {
+	/// both gzip and brotli
+	pub const ALL: &[Self] = &[Self::Gzip, Self::Brotli];
+
+	/// the suffix added to compressed copies (ex: `gz`)
+	#[must_use]
+	pub const fn extension(self) -> &'static str {
+		match self {
+			Self::Gzip => "gz",
+			Self::Brotli => "br",
+		}
+	}
+
+	/// compress some content at the highest level
+	pub fn compress(self, content: &[u8]) -> Result<Vec<u8>, ErrorKind> {
+		match self {
+			Self::Gzip => {
+				let mut encoder =
+					::flate2::write::GzEncoder::new(Vec::new(), ::flate2::Compression::best());
+				encoder.write_all(content)?;
+				Ok(encoder.finish()?)
+			}
+			Self::Brotli => {
+				let mut out = Vec::new();
+				::brotli::BrotliCompress(
+					&mut io::Cursor::new(content),
+					&mut out,
+					&::brotli::enc::BrotliEncoderParams::default(),
+				)?;
+				Ok(out)
+			}
+		}
+	}
+
+	/// where the compressed copy of `path` is written (ex: `app.wasm` -> `app.wasm.br`)
+	#[must_use]
+	pub fn path(self, path: &Path) -> PathBuf {
+		let mut name = OsString::from(path.as_os_str());
+		name.push(".");
+		name.push(self.extension());
+		PathBuf::from(name)
+	}
+}
+
+/// write a compressed copy of `path` in each of `encodings` next to it
+pub fn compress_file(path: &Path, encodings: &[Encoding]) -> Result<(), ErrorKind> {
+	compress_file_in(&StdFs, path, encodings)
+}
+
+/// write a compressed copy of `path` in each of `encodings` next to it, on a filesystem other than the real one (see [`execute_in`](crate::execute_in))
+pub fn compress_file_in(fs: &dyn Fs, path: &Path, encodings: &[Encoding]) -> Result<(), ErrorKind> {
+	if encodings.is_empty() {
+		return Ok(());
+	}
+
+	let content = fs.read(path)?;
+
+	for encoding in encodings {
+		let compressed = encoding.compress(&content)?;
+		trace!(
+			?path,
+			?encoding,
+			from = content.len(),
+			to = compressed.len(),
+			"compressed"
+		);
+		fs.write(&encoding.path(path), &compressed)?;
+	}
+
+	Ok(())
+}
+
+/// whether `path` has one of `extensions`, or `extensions` is empty
+fn has_extension(path: &Path, extensions: &[String]) -> bool {
+	extensions.is_empty()
+		|| path
+			.extension()
+			.and_then(|ext| ext.to_str())
+			.is_some_and(|ext| extensions.iter().any(|extension| extension == ext))
+}
+
+/// a plan to write compressed copies of the outputs of another transformation, after executing it
+#[derive(Debug)]
+pub struct Precompress {
+	/// the transformation whose outputs are compressed
+	pub inner: Box<dyn PlannedTransformation>,
+	/// which encodings to write copies in
+	pub encodings: Vec<Encoding>,
+	/// only compress outputs with these extensions (ex: `["wasm", "js"]`), or every output if empty
+	pub extensions: Vec<String>,
+}
+
+impl PlannedTransformation for Precompress {
+	fn execute(self: Box<Self>, dst: PathBuf) -> Result<(), ErrorKind> {
+		self.execute_in(dst, &StdFs)
+	}
+
+	#[instrument(name = "precompress", level = Level::DEBUG, skip(fs))]
+	fn execute_in(self: Box<Self>, dst: PathBuf, fs: &dyn Fs) -> Result<(), ErrorKind> {
+		let outputs = self.inner.outputs(&dst);
+		self.inner.execute_in(dst, fs)?;
+
+		for output in outputs {
+			if has_extension(&output, &self.extensions) {
+				compress_file_in(fs, &output, &self.encodings)?;
+			}
+		}
+
+		Ok(())
+	}
+
+	fn is_noop(&self) -> bool {
+		self.inner.is_noop()
+	}
+
+	fn outputs(&self, dst: &Path) -> Vec<PathBuf> {
+		let mut outputs = self.inner.outputs(dst);
+		let compressed = outputs
+			.iter()
+			.filter(|output| has_extension(output, &self.extensions))
+			.flat_map(|output| self.encodings.iter().map(|encoding| encoding.path(output)))
+			.collect::<Vec<_>>();
+		outputs.extend(compressed);
+		outputs
+	}
+
+	fn inputs(&self) -> Vec<PathBuf> {
+		self.inner.inputs()
+	}
+
+	fn force(&mut self) {
+		self.inner.force();
+	}
+
+	fn fingerprint(&self, state: &mut dyn Hasher) {
+		self.inner.fingerprint(state);
+
+		state.write_usize(self.encodings.len());
+		for encoding in &self.encodings {
+			state.write_u8(*encoding as u8);
+		}
+
+		state.write_usize(self.extensions.len());
+		for extension in &self.extensions {
+			hash_bytes(state, extension.as_bytes());
+		}
+	}
+}
+
+/// wrap a plan closure, so that compressed copies of its outputs are written too (see [`Precompress`])
+///
+/// - `encodings` - which encodings to write copies in (ex: [`Encoding::ALL`])
+/// - `extensions` - only compress outputs with these extensions (ex: `&["wasm", "js"]`), or every output if empty
+/// - `plan` - the plan closure to wrap (ex: `copy`)
+pub fn precompress(
+	encodings: &[Encoding],
+	extensions: &[&str],
+	mut plan: impl FnMut(
+		PathBuf,
+		Vec<String>,
+		&Path,
+		&Context,
+	) -> Result<Box<dyn PlannedTransformation>, ErrorKind>,
+) -> impl FnMut(PathBuf, Vec<String>, &Path, &Context) -> Result<Box<dyn PlannedTransformation>, ErrorKind>
+{
+	let encodings = encodings.to_vec();
+	let extensions = extensions
+		.iter()
+		.map(ToString::to_string)
+		.collect::<Vec<_>>();
+
+	move |src, captures, dst: &Path, context: &Context| {
+		Ok(Box::new(Precompress {
+			inner: plan(src, captures, dst, context)?,
+			encodings: encodings.clone(),
+			extensions: extensions.clone(),
+		}))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use {
+		super::*,
+		crate::{execute_in, filesystem::MemoryFs, Plan},
+		::std::io::Read,
+	};
+
+	fn precompress(encodings: &[Encoding], extensions: &[&str]) -> Precompress {
+		Precompress {
+			inner: Box::new(String::from("hello hello hello")),
+			encodings: encodings.to_vec(),
+			extensions: extensions.iter().map(ToString::to_string).collect(),
+		}
+	}
+
+	#[test]
+	fn compresses_within_memory() {
+		let fs = MemoryFs::new();
+
+		execute_in(
+			vec![
+				Plan::new("out/a.txt", precompress(Encoding::ALL, &["txt"])),
+				Plan::new("out/b.css", precompress(Encoding::ALL, &["txt"])),
+			],
+			&fs,
+		)
+		.unwrap();
+
+		let files = fs.files();
+		assert_eq!(files.len(), 4);
+		assert!(files.contains_key(Path::new("out/b.css")));
+
+		let mut gzip = String::new();
+		::flate2::read::GzDecoder::new(&files[Path::new("out/a.txt.gz")][..])
+			.read_to_string(&mut gzip)
+			.unwrap();
+		assert_eq!(gzip, "hello hello hello");
+
+		let mut brotli = Vec::new();
+		::brotli::BrotliDecompress(
+			&mut io::Cursor::new(&files[Path::new("out/a.txt.br")]),
+			&mut brotli,
+		)
+		.unwrap();
+		assert_eq!(brotli, b"hello hello hello");
+	}
+
+	#[test]
+	fn options_change_identity() {
+		let identity = |encodings: &[Encoding], extensions: &[&str]| {
+			Plan::new("out/a.txt", precompress(encodings, extensions))
+				.identity()
+				.unwrap()
+		};
+
+		let all = identity(Encoding::ALL, &["txt"]);
+		assert_eq!(all, identity(Encoding::ALL, &["txt"]));
+		assert_ne!(all, identity(&[Encoding::Gzip], &["txt"]));
+		assert_ne!(all, identity(Encoding::ALL, &["html"]));
+	}
+}
@@ -0,0 +1,90 @@
+//! write data computed in rust (ex: a search index) as json, toml, or yaml
+//!
+//! the data is only serialized when the plan is executed
+//!
+//! requires `data` feature
+
+use {
+	crate::{util::hash_serialized, ErrorKind, PlannedTransformation, Sink},
+	::core::{fmt::Debug, hash::Hasher},
+	::serde::Serialize,
+	::std::{fs, path::PathBuf},
+	::tracing::{instrument, Level},
+};
+
+pub extern crate serde;
+
+/// a format to serialize data as
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DataFormat {
+	/// compact json
+	Json,
+	/// indented json
+	PrettyJson,
+	/// toml, which requires the data to be a table at the top level
+	Toml,
+	/// yaml
+	Yaml,
+}
+
+impl DataFormat {
+	/// serialize a value in this format
+	pub fn serialize<T: Serialize + ?Sized>(self, value: &T) -> Result<String, ErrorKind> {
+		Ok(match self {
+			Self::Json => ::serde_json::to_string(value).map_err(DataErrorKind::Json)?,
+			Self::PrettyJson => {
+				::serde_json::to_string_pretty(value).map_err(DataErrorKind::Json)?
+			}
+			Self::Toml => ::toml::to_string(value).map_err(DataErrorKind::Toml)?,
+			Self::Yaml => ::serde_yaml::to_string(value).map_err(DataErrorKind::Yaml)?,
+		})
+	}
+}
+
+/// a plan to serialize a value and write it to the destination file
+#[derive(Debug)]
+pub struct Serialized<T> {
+	/// the value to serialize
+	pub value: T,
+	/// the format to serialize it as
+	pub format: DataFormat,
+}
+
+impl<T: Serialize + Debug + 'static> PlannedTransformation for Serialized<T> {
+	#[instrument(skip(self), name = "write serialized data", level = Level::DEBUG)]
+	fn execute(self: Box<Self>, dst: PathBuf) -> Result<(), ErrorKind> {
+		fs::write(dst, self.format.serialize(&self.value)?)?;
+		Ok(())
+	}
+
+	fn execute_to(self: Box<Self>, sink: Sink) -> Result<(), ErrorKind> {
+		sink.write_all(self.format.serialize(&self.value)?.as_bytes())
+	}
+
+	fn fingerprint(&self, state: &mut dyn Hasher) {
+		state.write_u8(self.format as u8);
+		hash_serialized(state, &self.value);
+	}
+}
+
+/// an error while serializing data
+#[derive(::thiserror::Error, ::miette::Diagnostic, Debug)]
+pub enum DataErrorKind {
+	/// json serialization failed
+	#[error("json serialization failed")]
+	#[diagnostic(code(dollgen::data::json))]
+	Json(#[source] ::serde_json::Error),
+
+	/// toml serialization failed
+	#[error("toml serialization failed")]
+	#[diagnostic(
+		code(dollgen::data::toml),
+		help("toml requires the data to be a table (ex: a struct or map) at the top level")
+	)]
+	Toml(#[source] ::toml::ser::Error),
+
+	/// yaml serialization failed
+	#[error("yaml serialization failed")]
+	#[diagnostic(code(dollgen::data::yaml))]
+	Yaml(#[source] ::serde_yaml::Error),
+}
@@ -0,0 +1,248 @@
+//! the filesystem that plans are executed against (see [`execute_in`](crate::execute_in))
+//!
+//! by default, plans are executed against the real filesystem ([`StdFs`]), but they may be executed against an in-memory one ([`MemoryFs`]) to test transformations without temporary directories, or against a custom [`Fs`] (ex: to upload outputs somewhere)
+//!
+//! transformations go through the filesystem for the files they read and write themselves (ex: `copy` reads its source from it, see [`PlannedTransformation::execute_in`](crate::PlannedTransformation::execute_in)),
+//! except for what only exists on the real filesystem: source files read while planning, build artifacts (ex: wasm-bindgen's output), files read by external programs (ex: `command`), and outputs hashed by `integrity`
+
+use {
+	crate::Sink,
+	::std::{
+		collections::BTreeMap,
+		fs,
+		io::{self, Read},
+		path::{Path, PathBuf},
+		sync::{Arc, Mutex, PoisonError},
+	},
+};
+
+/// a filesystem that plans are executed against, which outputs are written to and copied files are read from
+pub trait Fs {
+	/// read an entire file
+	fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+	/// open a file for reading, so large files may be compared without reading them entirely
+	fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+		Ok(Box::new(io::Cursor::new(self.read(path)?)))
+	}
+
+	/// write an entire file, replacing it if it exists
+	fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+
+	/// copy a file, replacing the destination if it exists
+	fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+		self.write(to, &self.read(from)?)
+	}
+
+	/// make `to` refer to the same file as `from`, replacing it if it exists
+	///
+	/// by default, this copies, as not every filesystem has links
+	fn hard_link(&self, from: &Path, to: &Path) -> io::Result<()> {
+		self.copy(from, to)
+	}
+
+	/// create a directory and all of its parents, if they don't exist
+	fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+
+	/// information about a file or directory
+	fn metadata(&self, path: &Path) -> io::Result<Metadata>;
+
+	/// where a transformation writes its output to produce the file at `path`
+	///
+	/// transformations that can only write to a [`Sink::File`] (ex: those that write several files) fail with [`ErrorKind::UnsupportedSink`](crate::ErrorKind::UnsupportedSink) when given any other sink
+	fn sink(&self, path: &Path) -> Sink;
+}
+
+/// information about a file or directory, see [`Fs::metadata`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Metadata {
+	/// the size in bytes
+	pub len: u64,
+	/// whether it is a directory
+	pub is_dir: bool,
+}
+
+/// the real filesystem, via [`std::fs`]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct StdFs;
+
+impl Fs for StdFs {
+	fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+		fs::read(path)
+	}
+
+	fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+		Ok(Box::new(fs::File::open(path)?))
+	}
+
+	fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+		fs::write(path, contents)
+	}
+
+	fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+		fs::copy(from, to).map(|_| ())
+	}
+
+	/// falls back to copying if the paths are on different filesystems
+	fn hard_link(&self, from: &Path, to: &Path) -> io::Result<()> {
+		match fs::remove_file(to) {
+			Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+			_ => {}
+		}
+
+		match fs::hard_link(from, to) {
+			Err(err)
+				if matches!(
+					err.kind(),
+					io::ErrorKind::CrossesDevices | io::ErrorKind::Unsupported
+				) =>
+			{
+				self.copy(from, to)
+			}
+			result => result,
+		}
+	}
+
+	fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+		fs::create_dir_all(path)
+	}
+
+	fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+		let metadata = fs::metadata(path)?;
+
+		Ok(Metadata {
+			len: metadata.len(),
+			is_dir: metadata.is_dir(),
+		})
+	}
+
+	fn sink(&self, path: &Path) -> Sink {
+		Sink::File(path.to_path_buf())
+	}
+}
+
+/// an in-memory filesystem, where every file is a buffer
+///
+/// directories are not tracked, so [`Fs::create_dir_all`] always succeeds and [`Fs::metadata`] only finds files
+#[derive(Default, Debug)]
+pub struct MemoryFs {
+	files: Mutex<BTreeMap<PathBuf, Arc<Mutex<Vec<u8>>>>>,
+}
+
+impl MemoryFs {
+	/// create an empty filesystem
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// every file and its contents, sorted by path
+	#[must_use]
+	pub fn files(&self) -> BTreeMap<PathBuf, Vec<u8>> {
+		self.files
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.iter()
+			.map(|(path, contents)| {
+				(
+					path.clone(),
+					contents
+						.lock()
+						.unwrap_or_else(PoisonError::into_inner)
+						.clone(),
+				)
+			})
+			.collect()
+	}
+
+	/// replace the file at `path` with an empty one, returning its buffer
+	fn create(&self, path: &Path) -> Arc<Mutex<Vec<u8>>> {
+		let buffer = Arc::new(Mutex::new(Vec::new()));
+
+		self.files
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.insert(path.to_path_buf(), buffer.clone());
+
+		buffer
+	}
+
+	fn get(&self, path: &Path) -> io::Result<Arc<Mutex<Vec<u8>>>> {
+		self.files
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.get(path)
+			.cloned()
+			.ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+	}
+}
+
+impl Fs for MemoryFs {
+	fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+		Ok(self
+			.get(path)?
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.clone())
+	}
+
+	fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+		*self
+			.create(path)
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner) = contents.to_vec();
+		Ok(())
+	}
+
+	fn create_dir_all(&self, _: &Path) -> io::Result<()> {
+		Ok(())
+	}
+
+	fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+		Ok(Metadata {
+			len: self
+				.get(path)?
+				.lock()
+				.unwrap_or_else(PoisonError::into_inner)
+				.len() as u64,
+			is_dir: false,
+		})
+	}
+
+	fn sink(&self, path: &Path) -> Sink {
+		Sink::Buffer(self.create(path))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use {
+		super::*,
+		crate::{execute_in, CopyIfChanged, HardLink, Plan},
+	};
+
+	#[test]
+	fn copies_within_memory() {
+		let fs = MemoryFs::new();
+		fs.write(Path::new("src/a.txt"), b"a").unwrap();
+		fs.write(Path::new("out/unchanged.txt"), b"a").unwrap();
+
+		execute_in(
+			vec![
+				Plan::new("out/copy.txt", PathBuf::from("src/a.txt")),
+				Plan::new("out/link.txt", HardLink("src/a.txt".into())),
+				Plan::new("out/unchanged.txt", CopyIfChanged::new("src/a.txt")),
+				Plan::new("out/string.txt", String::from("b")),
+			],
+			&fs,
+		)
+		.unwrap();
+
+		let files = fs.files();
+		assert_eq!(files.len(), 5);
+		for path in ["out/copy.txt", "out/link.txt", "out/unchanged.txt"] {
+			assert_eq!(files[Path::new(path)], b"a");
+		}
+		assert_eq!(files[Path::new("out/string.txt")], b"b");
+	}
+}
@@ -0,0 +1,231 @@
+//! parsing format-strings (see [`format`](crate::format))
+
+use {
+	::std::path::MAIN_SEPARATOR,
+	::strfmt::{DisplayStr, FmtError, Formatter},
+};
+
+/// a `{...}` in a format-string (ex: `{0|basename:>8:-index}`)
+pub struct Placeholder<'a> {
+	/// the index or name of the capture
+	pub key: &'a str,
+	/// operations applied to the capture, in order
+	pub ops: Vec<PathOp>,
+	/// the format spec, after the `:` (ex: `>8`)
+	pub spec: &'a str,
+	/// what to use if the capture is missing or empty, after a `:-` that doesn't start a spec (ex: `index`)
+	pub default: Option<&'a str>,
+}
+
+impl Placeholder<'_> {
+	/// apply the operations to a capture (or the default, if it is empty), then write it to `out` according to the spec
+	pub fn write(&self, capture: &str, out: &mut String) -> Result<(), FmtError> {
+		let capture = match self.default {
+			Some(default) if capture.is_empty() => default,
+			_ => capture,
+		};
+		let value = self
+			.ops
+			.iter()
+			.fold(capture.to_string(), |value, op| op.apply(&value));
+
+		let pattern = format!("_:{}", self.spec);
+		value
+			.as_str()
+			.display_str(&mut Formatter::from_str(&pattern, out)?)
+	}
+}
+
+/// an operation on a captured path, separated from the key by `|` (ex: `{0|drop_prefix:1}`)
+#[derive(Clone, Copy, Debug)]
+pub enum PathOp {
+	/// everything before the last segment (ex: `blog/2024/post` -> `blog/2024`)
+	Dirname,
+	/// only the last segment (ex: `blog/2024/post` -> `post`)
+	Basename,
+	/// everything after the first `n` segments (ex: `drop_prefix:1` makes `blog/2024/post` -> `2024/post`)
+	DropPrefix(usize),
+}
+
+impl PathOp {
+	fn apply(self, path: &str) -> String {
+		let is_separator = |c: char| c == '/' || c == MAIN_SEPARATOR;
+
+		match self {
+			Self::Dirname => path
+				.rfind(is_separator)
+				.map_or("", |end| &path[..end])
+				.to_string(),
+			Self::Basename => path
+				.rfind(is_separator)
+				.map_or(path, |end| &path[end + 1..])
+				.to_string(),
+			Self::DropPrefix(n) => path
+				.splitn(n + 1, is_separator)
+				.nth(n)
+				.unwrap_or_default()
+				.to_string(),
+		}
+	}
+}
+
+/// format a format-string, calling `placeholder` to write each placeholder
+///
+/// `{{` and `}}` are literal braces
+pub fn format_with(
+	fmt: &str,
+	mut placeholder: impl FnMut(Placeholder<'_>, &mut String) -> Result<(), FmtError>,
+) -> Result<String, FmtError> {
+	let mut out = String::with_capacity(fmt.len() * 2);
+	let mut rest = fmt;
+
+	while let Some(index) = rest.find(['{', '}']) {
+		out.push_str(&rest[..index]);
+
+		if rest[index..].starts_with("{{") || rest[index..].starts_with("}}") {
+			out.push_str(&rest[index..=index]);
+			rest = &rest[index + 2..];
+		} else if rest[index..].starts_with('}') {
+			return Err(FmtError::Invalid(
+				"single '}' encountered in format string".to_string(),
+			));
+		} else {
+			let body = &rest[index + 1..];
+			let end = body
+				.find(['{', '}'])
+				.filter(|&end| body[end..].starts_with('}'))
+				.ok_or_else(|| FmtError::Invalid("unclosed '{' in format string".to_string()))?;
+
+			placeholder(parse_placeholder(&body[..end])?, &mut out)?;
+			rest = &body[end + 1..];
+		}
+	}
+
+	out.push_str(rest);
+	Ok(out)
+}
+
+/// parse the inside of a placeholder (ex: `0|drop_prefix:1:>8:-index`)
+fn parse_placeholder(body: &str) -> Result<Placeholder<'_>, FmtError> {
+	// a spec only contains `:-` if `-` is its fill, which must be followed by an alignment (strings have no sign), so the default is everything after the first other `:-`
+	let (body, default) = match body
+		.match_indices(":-")
+		.map(|(index, _)| index)
+		.find(|&index| !body[index + 2..].starts_with(['<', '^', '>']))
+	{
+		Some(index) => (&body[..index], Some(&body[index + 2..])),
+		None => (body, None),
+	};
+
+	let mut parts = body.split('|');
+	let first = parts.next().unwrap_or_default();
+	let ops = parts.collect::<Vec<_>>();
+
+	// without operations, the spec follows the key
+	let Some((last, init)) = ops.split_last() else {
+		let (key, spec) = first.split_once(':').unwrap_or((first, ""));
+		return Ok(Placeholder {
+			key: non_empty(key)?,
+			ops: Vec::new(),
+			spec,
+			default,
+		});
+	};
+
+	let mut parsed = init
+		.iter()
+		.map(|op| parse_op(op).map(|(op, _)| op))
+		.collect::<Result<Vec<_>, _>>()?;
+	let (op, spec) = parse_op(last)?;
+	parsed.push(op);
+
+	Ok(Placeholder {
+		key: non_empty(first)?,
+		ops: parsed,
+		spec,
+		default,
+	})
+}
+
+/// parse an operation and its argument, returning what comes after them (the spec, if it is the last operation)
+fn parse_op(op: &str) -> Result<(PathOp, &str), FmtError> {
+	let (name, rest) = op.split_once(':').unwrap_or((op, ""));
+
+	match name {
+		"dirname" => Ok((PathOp::Dirname, rest)),
+		"basename" => Ok((PathOp::Basename, rest)),
+		"drop_prefix" => {
+			let (n, rest) = rest.split_once(':').unwrap_or((rest, ""));
+			let n = n.parse().map_err(|_| {
+				FmtError::Invalid(format!(
+					"`drop_prefix` takes a number of segments (ex: `drop_prefix:1`), not \"{n}\""
+				))
+			})?;
+			Ok((PathOp::DropPrefix(n), rest))
+		}
+		name => Err(FmtError::Invalid(format!(
+			"unknown operation \"{name}\", expected `dirname`, `basename`, or `drop_prefix:n`"
+		))),
+	}
+}
+
+fn non_empty(key: &str) -> Result<&str, FmtError> {
+	if key.is_empty() {
+		Err(FmtError::Invalid("must specify identifier".to_string()))
+	} else {
+		Ok(key)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::format;
+
+	#[test]
+	fn captures() {
+		assert_eq!(
+			format("dist/{0}/{1}.html", &["a", "b"]).unwrap(),
+			"dist/a/b.html"
+		);
+	}
+
+	#[test]
+	fn escapes() {
+		assert_eq!(format("{{{0}}}", &["a"]).unwrap(), "{a}");
+		assert!(format("}", &["a"]).is_err());
+		assert!(format("{0", &["a"]).is_err());
+	}
+
+	#[test]
+	fn ops() {
+		let path = ["blog/2024/post"];
+		assert_eq!(format("{0|dirname}", &path).unwrap(), "blog/2024");
+		assert_eq!(format("{0|basename}", &path).unwrap(), "post");
+		assert_eq!(format("{0|drop_prefix:1}", &path).unwrap(), "2024/post");
+		assert_eq!(format("{0|dirname|basename}", &path).unwrap(), "2024");
+		assert!(format("{0|nope}", &path).is_err());
+		assert!(format("{0|drop_prefix:x}", &path).is_err());
+	}
+
+	#[test]
+	fn specs() {
+		assert_eq!(format("{0:>4}", &["a"]).unwrap(), "   a");
+		assert_eq!(format("{0:->8}", &["a"]).unwrap(), "-------a");
+		assert_eq!(format("{0:-^5}", &["a"]).unwrap(), "--a--");
+		assert_eq!(format("{1|basename:-<4:-x}", &["a"]).unwrap(), "x---");
+		assert_eq!(format("{0|basename:<4}|", &["x/a"]).unwrap(), "a   |");
+	}
+
+	#[test]
+	fn defaults() {
+		assert_eq!(format("{1:-index}", &["a"]).unwrap(), "index");
+		assert_eq!(format("{0:-index}", &[""]).unwrap(), "index");
+		assert_eq!(format("{0:-index}", &["a"]).unwrap(), "a");
+		// these would be valid specs, but `:-` is a default unless an alignment follows it
+		assert_eq!(format("{1:-2024}", &["a"]).unwrap(), "2024");
+		assert_eq!(format("{1:-e}", &["a"]).unwrap(), "e");
+		assert_eq!(format("{1:>6:-x:y|z}", &["a"]).unwrap(), " x:y|z");
+		assert_eq!(format("{1|basename:-a/b}", &["a"]).unwrap(), "b");
+		assert!(format("{1}", &["a"]).is_err());
+	}
+}
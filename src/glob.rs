@@ -0,0 +1,318 @@
+//! helpers for building `include`/`exclude` patterns at runtime
+
+use {
+	crate::{ErrorKind, Pattern, PatternError},
+	::std::env,
+};
+
+/// escape a literal string so that it only matches itself when used in a pattern
+///
+/// every character that would otherwise be glob syntax (`?`, `*`, `[`, `]`, `(`, `)`, and `{`, `}` for [`expand_braces`]) is wrapped in a character class
+///
+/// ex: `drafts (old)` becomes `drafts [(]old[)]`
+#[must_use]
+pub fn escape_glob(literal: &str) -> String {
+	let mut escaped = String::with_capacity(literal.len());
+
+	for c in literal.chars() {
+		match c {
+			'?' | '*' | '[' | ']' | '(' | ')' | '{' | '}' => {
+				escaped.push('[');
+				escaped.push(c);
+				escaped.push(']');
+			}
+			c => escaped.push(c),
+		}
+	}
+
+	escaped
+}
+
+/// builds a [`Pattern`] out of literal text (which is escaped) and glob syntax (which is not)
+///
+/// this makes it safe to build patterns out of runtime strings, such as directory names from configuration
+///
+/// ex: `PatternBuilder::new().literal(content_dir).glob("/(**)/(*).doll").build()?`
+#[derive(Clone, Default, Debug)]
+pub struct PatternBuilder {
+	pattern: String,
+}
+
+impl PatternBuilder {
+	/// create an empty pattern
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// append literal text, which only ever matches itself
+	#[must_use]
+	pub fn literal(mut self, literal: &str) -> Self {
+		self.pattern.push_str(&escape_glob(literal));
+		self
+	}
+
+	/// append glob syntax, which is used as-is
+	#[must_use]
+	pub fn glob(mut self, glob: &str) -> Self {
+		self.pattern.push_str(glob);
+		self
+	}
+
+	/// the pattern string built so far
+	#[must_use]
+	pub fn as_str(&self) -> &str {
+		&self.pattern
+	}
+
+	/// compile the pattern
+	pub fn build(&self) -> Result<Pattern, PatternError> {
+		Pattern::new(&self.pattern)
+	}
+}
+
+/// expand brace groups in a pattern into every alternative (ex: `(*).{png,jpg}` becomes `(*).png` and `(*).jpg`)
+///
+/// groups may be nested (ex: `{a,b{c,d}}`), braces inside of character classes (ex: `[{]`) are literal, and groups without a `,` are left as-is
+///
+/// captures inside of alternatives are kept, so alternatives should have the same number of captures for `dst` to refer to the same ones
+#[must_use]
+pub fn expand_braces(pattern: &str) -> Vec<String> {
+	let Some((open, close, commas)) = find_group(pattern) else {
+		return vec![pattern.to_string()];
+	};
+
+	let (prefix, suffix) = (&pattern[..open], &pattern[close + 1..]);
+
+	let mut starts = vec![open + 1];
+	starts.extend(commas.iter().map(|comma| comma + 1));
+	let mut ends = commas;
+	ends.push(close);
+
+	starts
+		.into_iter()
+		.zip(ends)
+		.flat_map(|(start, end)| {
+			expand_braces(&format!("{prefix}{}{suffix}", &pattern[start..end]))
+		})
+		.collect()
+}
+
+/// compile a pattern after expanding its brace groups (see [`expand_braces`]), for use as several `include` or `exclude` patterns
+///
+/// ex: `include: &glob::patterns("src/(**)/(*).{png,jpg,gif}")?`
+pub fn patterns(pattern: &str) -> Result<Vec<Pattern>, PatternError> {
+	expand_braces(pattern)
+		.iter()
+		.map(|pattern| Pattern::new(pattern))
+		.collect()
+}
+
+/// expand `~` and environment variables in a pattern or `dst` read from configuration (ex: a config file or command line arguments)
+///
+/// - a leading `~` (alone, or followed by `/`) becomes the home directory (`HOME`, or `USERPROFILE` on windows)
+/// - `$VAR` and `${VAR}` become the value of `VAR`, where `$VAR` is as long as possible (letters, digits, and `_`)
+/// - `$$` becomes a literal `$`
+///
+/// variables that aren't set are an [`ErrorKind::UnsetVariable`], rather than silently expanding to nothing
+///
+/// only use this for strings from configuration, patterns written in code shouldn't need it (and `$` or `~` in them would be surprising to expand)
+///
+/// ex: `~/notes/(**).doll` becomes `/home/doll/notes/(**).doll`
+pub fn expand_vars(input: &str) -> Result<String, ErrorKind> {
+	expand_vars_with(input, ToString::to_string)
+}
+
+/// [`expand_vars`], escaping every expanded value with `escape` (ex: [`escape_glob`] for `include`/`exclude` patterns, so that a `[` in a directory name doesn't start a character class)
+pub fn expand_vars_with(input: &str, escape: impl Fn(&str) -> String) -> Result<String, ErrorKind> {
+	let unset = |name: &str| ErrorKind::UnsetVariable {
+		name: name.to_string(),
+		input: input.to_string(),
+	};
+	let var = |name: &str| env::var(name).map_err(|_| unset(name));
+
+	let mut out = String::with_capacity(input.len());
+	let mut rest = input;
+
+	if let Some(after) = rest.strip_prefix('~') {
+		if after.is_empty() || after.starts_with('/') {
+			let home = var("HOME").or_else(|err| env::var("USERPROFILE").map_err(|_| err))?;
+			out.push_str(&escape(&home));
+			rest = after;
+		}
+	}
+
+	while let Some(dollar) = rest.find('$') {
+		out.push_str(&rest[..dollar]);
+		let after = &rest[dollar + 1..];
+
+		if let Some(after) = after.strip_prefix('$') {
+			out.push('$');
+			rest = after;
+		} else if let Some(braced) = after.strip_prefix('{') {
+			if let Some(close) = braced.find('}') {
+				out.push_str(&escape(&var(&braced[..close])?));
+				rest = &braced[close + 1..];
+			} else {
+				// an unclosed `${` is literal
+				out.push_str("${");
+				rest = braced;
+			}
+		} else {
+			let len = after
+				.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+				.unwrap_or(after.len());
+			if len == 0 {
+				// a `$` that isn't followed by a name is literal
+				out.push('$');
+			} else {
+				out.push_str(&escape(&var(&after[..len])?));
+			}
+			rest = &after[len..];
+		}
+	}
+
+	out.push_str(rest);
+	Ok(out)
+}
+
+/// the first brace group with a top-level `,`, as (index of `{`, index of `}`, indices of top-level `,`)
+fn find_group(pattern: &str) -> Option<(usize, usize, Vec<usize>)> {
+	let bytes = pattern.as_bytes();
+	let mut index = 0;
+
+	while index < bytes.len() {
+		match bytes[index] {
+			b'[' => index = class_end(bytes, index),
+			b'{' => {
+				if let Some((close, commas)) = group_end(bytes, index) {
+					if !commas.is_empty() {
+						return Some((index, close, commas));
+					}
+				}
+			}
+			_ => {}
+		}
+
+		index += 1;
+	}
+
+	None
+}
+
+/// the index of the `}` that closes the `{` at `open`, and the indices of the top-level `,` between them
+fn group_end(bytes: &[u8], open: usize) -> Option<(usize, Vec<usize>)> {
+	let mut depth = 0;
+	let mut commas = Vec::new();
+	let mut index = open + 1;
+
+	while index < bytes.len() {
+		match bytes[index] {
+			b'[' => index = class_end(bytes, index),
+			b'{' => depth += 1,
+			b'}' if depth == 0 => return Some((index, commas)),
+			b'}' => depth -= 1,
+			b',' if depth == 0 => commas.push(index),
+			_ => {}
+		}
+
+		index += 1;
+	}
+
+	None
+}
+
+/// the index of the `]` that closes the character class at `open`, or `open` if it isn't closed
+fn class_end(bytes: &[u8], open: usize) -> usize {
+	// a `]` right after the `[` (or `[!`) is part of the class
+	let mut index = open + 1;
+	if bytes.get(index) == Some(&b'!') {
+		index += 1;
+	}
+	if bytes.get(index) == Some(&b']') {
+		index += 1;
+	}
+
+	bytes[index.min(bytes.len())..]
+		.iter()
+		.position(|&byte| byte == b']')
+		.map_or(open, |end| index + end)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn expand_braces_alternatives() {
+		assert_eq!(expand_braces("(*).{png,jpg}"), ["(*).png", "(*).jpg"]);
+		assert_eq!(expand_braces("src/(*).doll"), ["src/(*).doll"]);
+	}
+
+	#[test]
+	fn expand_braces_nested() {
+		assert_eq!(expand_braces("{a,b{c,d}}"), ["a", "bc", "bd"]);
+		assert_eq!(expand_braces("{a,b}/{c,d}"), ["a/c", "a/d", "b/c", "b/d"]);
+	}
+
+	#[test]
+	fn expand_braces_literal() {
+		// without a `,`, or inside of a character class
+		assert_eq!(expand_braces("{a}.txt"), ["{a}.txt"]);
+		assert_eq!(expand_braces("[{]a,b[}]"), ["[{]a,b[}]"]);
+	}
+
+	#[test]
+	fn expand_vars_forms() {
+		env::set_var("DOLLGEN_TEST_NOTES", "notes");
+
+		assert_eq!(
+			expand_vars("$DOLLGEN_TEST_NOTES/(**)").unwrap(),
+			"notes/(**)"
+		);
+		assert_eq!(
+			expand_vars("${DOLLGEN_TEST_NOTES}_old").unwrap(),
+			"notes_old"
+		);
+		// `$name` is as long as possible
+		assert!(expand_vars("$DOLLGEN_TEST_NOTES_old").is_err());
+	}
+
+	#[test]
+	fn expand_vars_literals() {
+		assert_eq!(expand_vars("$$5").unwrap(), "$5");
+		assert_eq!(expand_vars("a$/b").unwrap(), "a$/b");
+		assert_eq!(expand_vars("${unclosed").unwrap(), "${unclosed");
+		assert_eq!(expand_vars("a~/b").unwrap(), "a~/b");
+		assert_eq!(expand_vars("~b").unwrap(), "~b");
+	}
+
+	#[test]
+	fn expand_vars_home() {
+		if let Ok(home) = env::var("HOME") {
+			assert_eq!(expand_vars("~/notes").unwrap(), format!("{home}/notes"));
+			assert_eq!(expand_vars("~").unwrap(), home);
+		}
+	}
+
+	#[test]
+	fn expand_vars_unset() {
+		match expand_vars("src/$DOLLGEN_TEST_UNSET/(*)") {
+			Err(ErrorKind::UnsetVariable { name, input }) => {
+				assert_eq!(name, "DOLLGEN_TEST_UNSET");
+				assert_eq!(input, "src/$DOLLGEN_TEST_UNSET/(*)");
+			}
+			result => panic!("expected an unset variable, got {result:?}"),
+		}
+	}
+
+	#[test]
+	fn expand_vars_with_escape() {
+		env::set_var("DOLLGEN_TEST_BRACKETS", "a[b]");
+
+		assert_eq!(
+			expand_vars_with("$DOLLGEN_TEST_BRACKETS/(*)", escape_glob).unwrap(),
+			"a[[]b[]]/(*)"
+		);
+	}
+}
@@ -0,0 +1,112 @@
+//! a graph of which paths each plan reads and writes, for debugging builds (ex: why changing a template rebuilds a page)
+//!
+//! see [`PlannedTransformation::inputs`](crate::PlannedTransformation::inputs) and [`PlannedTransformation::outputs`](crate::PlannedTransformation::outputs)
+
+use {
+	crate::Plan,
+	::std::{
+		collections::BTreeSet,
+		fmt::Write,
+		path::{Path, PathBuf},
+	},
+};
+
+/// the dependencies between paths, produced by [`dependency_graph`]
+#[derive(Clone, Default, Debug)]
+pub struct Graph {
+	/// every path that is read or written, sorted
+	pub nodes: BTreeSet<PathBuf>,
+	/// `(input, output)` pairs, where the output depends on the input, sorted
+	pub edges: BTreeSet<(PathBuf, PathBuf)>,
+}
+
+/// how to [`render`](Graph::render) a graph
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum GraphFormat {
+	/// graphviz dot
+	#[default]
+	Dot,
+	/// json, as `{ "nodes": [path], "edges": [{ "from": path, "to": path }] }`
+	Json,
+}
+
+/// build the graph of which paths each plan reads and writes
+///
+/// an output that another plan reads (ex: a generated stylesheet that a page inlines) links them together
+#[must_use]
+pub fn dependency_graph(plans: &[Plan]) -> Graph {
+	let mut graph = Graph::default();
+
+	for plan in plans {
+		let inputs = plan.inputs();
+		let outputs = plan.outputs();
+
+		graph.nodes.extend(inputs.iter().cloned());
+		graph.nodes.extend(outputs.iter().cloned());
+
+		for input in &inputs {
+			for output in &outputs {
+				graph.edges.insert((input.clone(), output.clone()));
+			}
+		}
+	}
+
+	graph
+}
+
+impl Graph {
+	/// render the graph as text
+	#[must_use]
+	pub fn render(&self, format: GraphFormat) -> String {
+		let mut out = String::new();
+
+		match format {
+			GraphFormat::Dot => {
+				out.push_str("digraph dollgen {\n");
+				for node in &self.nodes {
+					writeln!(out, "\t{};", quote(node)).unwrap();
+				}
+				for (from, to) in &self.edges {
+					writeln!(out, "\t{} -> {};", quote(from), quote(to)).unwrap();
+				}
+				out.push_str("}\n");
+			}
+			GraphFormat::Json => {
+				out.push_str("{\"nodes\":[");
+				for (i, node) in self.nodes.iter().enumerate() {
+					if i > 0 {
+						out.push(',');
+					}
+					out.push_str(&quote(node));
+				}
+				out.push_str("],\"edges\":[");
+				for (i, (from, to)) in self.edges.iter().enumerate() {
+					if i > 0 {
+						out.push(',');
+					}
+					write!(out, "{{\"from\":{},\"to\":{}}}", quote(from), quote(to)).unwrap();
+				}
+				out.push_str("]}");
+			}
+		}
+
+		out
+	}
+}
+
+/// quote a path as a string, which is valid in both dot and json
+fn quote(path: &Path) -> String {
+	let mut quoted = String::from("\"");
+
+	for c in path.to_string_lossy().chars() {
+		match c {
+			'"' => quoted.push_str("\\\""),
+			'\\' => quoted.push_str("\\\\"),
+			c if c.is_control() => write!(quoted, "\\u{:04x}", u32::from(c)).unwrap(),
+			c => quoted.push(c),
+		}
+	}
+
+	quoted.push('"');
+	quoted
+}
@@ -0,0 +1,100 @@
+//! compute [subresource integrity](https://developer.mozilla.org/en-US/docs/Web/Security/Subresource_Integrity) hashes of generated assets, for `<script integrity="...">` and content security policies
+//!
+//! hashes need the written content, so they can only be computed after the assets are executed:
+//!
+//! - to use them in templates, build the assets first, [`collect`] the hashes, and provide them as a global when building the pages
+//! - to write them to a file (ex: `integrity.json`), add an [`IntegrityManifest`] plan after the plans of the assets
+//!
+//! requires `integrity` feature
+
+use {
+	crate::{url, util::walk, ErrorKind, PlannedTransformation, Sink},
+	::base64::{engine::general_purpose::STANDARD, Engine},
+	::sha2::{Digest, Sha384},
+	::std::{
+		collections::BTreeMap,
+		fs,
+		path::{Path, PathBuf},
+	},
+	::tracing::{instrument, Level},
+};
+
+/// the integrity string of some content (ex: `sha384-...`)
+#[must_use]
+pub fn hash(content: &[u8]) -> String {
+	format!("sha384-{}", STANDARD.encode(Sha384::digest(content)))
+}
+
+/// the integrity string of a file's content
+pub fn hash_file(path: &Path) -> Result<String, ErrorKind> {
+	Ok(hash(&fs::read(path)?))
+}
+
+/// hash every file under `output_root` with one of `extensions` (ex: `["js", "css", "wasm"]`)
+///
+/// returns a map from the url of each file (see [`url`](crate::url)) to its integrity string
+pub fn collect(
+	output_root: &Path,
+	extensions: &[&str],
+) -> Result<BTreeMap<String, String>, ErrorKind> {
+	let mut paths = Vec::new();
+	walk(output_root, &mut paths)?;
+
+	let mut hashes = BTreeMap::new();
+	for path in paths {
+		if !path.is_file()
+			|| !path
+				.extension()
+				.and_then(|ext| ext.to_str())
+				.is_some_and(|ext| extensions.contains(&ext))
+		{
+			continue;
+		}
+
+		hashes.insert(
+			url(&path, output_root).ok_or(ErrorKind::NonUTF8PathCharacters)?,
+			hash_file(&path)?,
+		);
+	}
+
+	Ok(hashes)
+}
+
+/// a plan to write the integrity strings of every file under `output_root` with one of `extensions` as a json object, from url to integrity string (see [`collect`])
+///
+/// as the files are hashed when this is executed, it should come after the plans that write them
+/// (ex: `plans.push(Plan::new("deploy/integrity.json", IntegrityManifest { ... }))`)
+#[derive(Debug)]
+pub struct IntegrityManifest {
+	/// the directory to hash files in
+	pub output_root: PathBuf,
+	/// which extensions to hash (ex: `["js", "css", "wasm"]`)
+	pub extensions: Vec<String>,
+}
+
+impl PlannedTransformation for IntegrityManifest {
+	fn execute(self: Box<Self>, dst: PathBuf) -> Result<(), ErrorKind> {
+		self.execute_to(Sink::File(dst))
+	}
+
+	#[instrument(name = "write integrity manifest", level = Level::DEBUG)]
+	fn execute_to(self: Box<Self>, sink: Sink) -> Result<(), ErrorKind> {
+		let hashes = collect(
+			&self.output_root,
+			&self
+				.extensions
+				.iter()
+				.map(String::as_str)
+				.collect::<Vec<_>>(),
+		)?;
+
+		let json = ::serde_json::Value::Object(
+			hashes
+				.into_iter()
+				.map(|(url, hash)| (url, ::serde_json::Value::String(hash)))
+				.collect(),
+		);
+
+		sink.write_all(format!("{json:#}").as_bytes())
+	}
+}
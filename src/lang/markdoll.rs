@@ -1,38 +1,96 @@
 //! [markdoll](https://codeberg.org/0x57e11a/markdoll) support
 //!
+//! markdoll emits to any target with emitters registered for it, ready-made languages are:
+//!
+//! - html, with [`create`] and `HtmlEmit` (see [`markdoll::emit::html`])
+//! - plain text (ex: excerpts for search), with [`create_text`] and [`TextEmit`]
+//! - a dump of the syntax tree, for debugging tags, with [`create_ast`]
+//!
 //! requires `lang-markdoll` feature
 
 use {
-	crate::{lang::LangErrorKind, ErrorKind},
-	::core::fmt::Debug,
-	::markdoll::{diagnostics::DiagnosticKind, spanner::Spanner, MarkDoll, MarkDollSrc},
-	::miette::{Diagnostic, Report, Severity},
-	::std::{path::Path, sync::Arc},
+	crate::{
+		lang::{FrontmatterFormat, LangErrorKind},
+		ErrorKind,
+	},
+	::core::fmt::{Debug, Write},
+	::markdoll::{
+		diagnostics::DiagnosticKind,
+		emit::BuiltInEmitters,
+		spanner::{Spanned, Spanner},
+		tree::InlineItem,
+		MarkDoll,
+		MarkDollSrc,
+	},
+	::miette::{Diagnostic, NarratableReportHandler, Report, Severity},
+	::std::{
+		env,
+		io::{self, IsTerminal},
+		path::Path,
+		sync::Arc,
+	},
 	::tracing::trace_span,
 };
 
 pub extern crate hashbrown;
 pub extern crate markdoll;
 
-/// language support for markdoll
-pub fn create<Ctx, To: Debug + Into<String> + 'static>(
-	mut doll: MarkDoll<Ctx>,
-	to: impl Fn(&Path) -> To,
-	ctx: impl Fn(&Path) -> Ctx,
-) -> impl for<'a> FnMut(&'a str, &'a Path) -> Result<(String, String), ErrorKind> {
-	fn diag_beh(diagnostics: Vec<DiagnosticKind>, spanner: &Arc<Spanner<MarkDollSrc>>) -> usize {
-		let mut n = 0;
-		for diagnostic in diagnostics {
-			if let Some(Severity::Error) | None = diagnostic.severity() {
-				n += 1;
+/// how markdoll diagnostics are rendered to stderr
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum DiagnosticTheme {
+	/// [`Plain`](DiagnosticTheme::Plain) if the `NO_COLOR` environment variable is set (and not empty) or stderr is not a terminal, otherwise [`Graphical`](DiagnosticTheme::Graphical)
+	#[default]
+	Auto,
+	/// miette's installed report handler (graphical with colors, by default)
+	Graphical,
+	/// miette's narratable report handler, which never emits escape codes
+	Plain,
+}
+
+impl DiagnosticTheme {
+	fn is_plain(self) -> bool {
+		match self {
+			Self::Auto => {
+				env::var_os("NO_COLOR").is_some_and(|no_color| !no_color.is_empty())
+					|| !io::stderr().is_terminal()
 			}
-			eprintln!(
-				"{:?}",
-				Report::from(diagnostic).with_source_code(spanner.clone())
-			);
+			Self::Graphical => false,
+			Self::Plain => true,
 		}
-		n
 	}
+}
+
+/// language support for markdoll
+///
+/// - `doll` - the markdoll instance
+/// - `to` - creates the emit target for a source file
+/// - `ctx` - creates the emit context for a source file
+///
+/// if `to` or `ctx` fail, the diagnostics collected so far are still reported and the error is returned
+///
+/// diagnostics are rendered with [`DiagnosticTheme::Auto`], see [`create_with_theme`] to change this
+pub fn create<Ctx, To: Debug + Into<String> + 'static>(
+	doll: MarkDoll<Ctx>,
+	to: impl Fn(&Path) -> Result<To, ErrorKind>,
+	ctx: impl Fn(&Path) -> Result<Ctx, ErrorKind>,
+) -> impl for<'a> FnMut(&'a str, &'a Path) -> Result<(FrontmatterFormat, String, String), ErrorKind>
+{
+	create_with_theme(doll, to, ctx, DiagnosticTheme::Auto)
+}
+
+/// language support for markdoll, rendering diagnostics with the given theme
+///
+/// arguments are the same as [`create`]
+pub fn create_with_theme<Ctx, To: Debug + Into<String> + 'static>(
+	mut doll: MarkDoll<Ctx>,
+	to: impl Fn(&Path) -> Result<To, ErrorKind>,
+	ctx: impl Fn(&Path) -> Result<Ctx, ErrorKind>,
+	theme: DiagnosticTheme,
+) -> impl for<'a> FnMut(&'a str, &'a Path) -> Result<(FrontmatterFormat, String, String), ErrorKind>
+{
+	let plain = theme.is_plain();
+	let diag_beh = move |diagnostics, spanner: &_| report_diagnostics(diagnostics, spanner, plain);
+
 	move |src, path| {
 		let _span = trace_span!("compile markdoll").entered();
 
@@ -45,8 +103,13 @@ pub fn create<Ctx, To: Debug + Into<String> + 'static>(
 		);
 
 		if ok {
-			let mut to = to(path);
-			let mut ctx = ctx(path);
+			let (mut to, mut ctx) = match to(path).and_then(|to| Ok((to, ctx(path)?))) {
+				Ok(setup) => setup,
+				Err(err) => {
+					diag_beh(diagnostics, &doll.finish());
+					return Err(err);
+				}
+			};
 
 			let (emit_ok, mut emit_diagnostics) = doll.emit(&mut ast, &mut to, &mut ctx);
 
@@ -54,7 +117,11 @@ pub fn create<Ctx, To: Debug + Into<String> + 'static>(
 
 			let n = diag_beh(diagnostics, &doll.finish());
 			if emit_ok && n == 0 {
-				Ok((frontmatter.unwrap_or_default(), to.into()))
+				Ok((
+					FrontmatterFormat::Toml,
+					frontmatter.unwrap_or_default(),
+					to.into(),
+				))
 			} else {
 				Err(ErrorKind::Lang(LangErrorKind::Markdoll(n)))
 			}
@@ -65,3 +132,200 @@ pub fn create<Ctx, To: Debug + Into<String> + 'static>(
 		}
 	}
 }
+
+/// render diagnostics to stderr, returning how many are errors
+fn report_diagnostics(
+	diagnostics: Vec<DiagnosticKind>,
+	spanner: &Arc<Spanner<MarkDollSrc>>,
+	plain: bool,
+) -> usize {
+	let mut n = 0;
+	for diagnostic in diagnostics {
+		if let Some(Severity::Error) | None = diagnostic.severity() {
+			n += 1;
+		}
+
+		let report = Report::from(diagnostic).with_source_code(spanner.clone());
+
+		if plain {
+			let mut out = String::new();
+			match NarratableReportHandler::new().render_report(&mut out, &*report) {
+				Ok(()) => eprintln!("{out}"),
+				Err(_) => eprintln!("{report}"),
+			}
+		} else {
+			eprintln!("{report:?}");
+		}
+	}
+	n
+}
+
+/// emit to plain text, without any markup (ex: for search indexes, excerpts, or feeds)
+///
+/// paragraphs and sections are separated by blank lines, and list items start with `- ` (or `1. `, etc, if ordered)
+#[derive(Debug, Default)]
+pub struct TextEmit {
+	/// text buffer
+	pub write: String,
+}
+
+impl TextEmit {
+	/// start a new block, separated from the previous one by a blank line
+	fn block(&mut self) {
+		let len = self.write.trim_end().len();
+		self.write.truncate(len);
+
+		if !self.write.is_empty() {
+			self.write.push_str("\n\n");
+		}
+	}
+
+	/// the default emitters for plain text
+	#[must_use]
+	pub const fn default_emitters<Ctx>() -> BuiltInEmitters<Ctx, Self> {
+		BuiltInEmitters {
+			inline: |doll, to, ctx, segments, inline_block| {
+				if inline_block {
+					to.block();
+				}
+
+				for Spanned(_, segment) in segments {
+					match segment {
+						InlineItem::Split => to.write.push(' '),
+						InlineItem::Break => to.write.push('\n'),
+						InlineItem::Text(text) => to.write.push_str(text),
+						InlineItem::Tag(tag) => tag.emit(doll, to, ctx),
+					}
+				}
+			},
+			section: |doll, to, ctx, header, children| {
+				to.block();
+
+				if let Some(emitters) = doll.builtin_emitters.get::<Self>() {
+					(emitters.inline)(doll, to, ctx, header, false);
+				}
+
+				for Spanned(_, child) in children {
+					child.emit(doll, to, ctx, true);
+				}
+			},
+			list: |doll, to, ctx, ordered, items| {
+				to.block();
+
+				for (index, item) in items.iter_mut().enumerate() {
+					if index > 0 {
+						to.write.push('\n');
+					}
+
+					if ordered {
+						write!(to.write, "{}. ", index + 1).unwrap();
+					} else {
+						to.write.push_str("- ");
+					}
+
+					for Spanned(_, child) in item {
+						child.emit(doll, to, ctx, false);
+					}
+				}
+			},
+		}
+	}
+}
+
+impl From<TextEmit> for String {
+	fn from(text: TextEmit) -> Self {
+		text.write
+	}
+}
+
+/// language support for markdoll, emitting plain text (see [`TextEmit`])
+///
+/// - `doll` - the markdoll instance, with its tags already added
+/// - `ctx` - creates the emit context for a source file
+///
+/// tags without a [`TextEmit`] emitter are left out of the text, as their content can't be read without knowing its type,
+/// add one to a tag's `emitters` before calling this to include it (ex: `doll.tags.get_mut("em").unwrap().emitters.put::<TextEmit>(...)`)
+///
+/// diagnostics are rendered with [`DiagnosticTheme::Auto`], see [`create_text_with_theme`] to change this
+pub fn create_text<Ctx>(
+	doll: MarkDoll<Ctx>,
+	ctx: impl Fn(&Path) -> Result<Ctx, ErrorKind>,
+) -> impl for<'a> FnMut(&'a str, &'a Path) -> Result<(FrontmatterFormat, String, String), ErrorKind>
+{
+	create_text_with_theme(doll, ctx, DiagnosticTheme::Auto)
+}
+
+/// language support for markdoll, emitting plain text and rendering diagnostics with the given theme
+///
+/// arguments are the same as [`create_text`]
+pub fn create_text_with_theme<Ctx>(
+	mut doll: MarkDoll<Ctx>,
+	ctx: impl Fn(&Path) -> Result<Ctx, ErrorKind>,
+	theme: DiagnosticTheme,
+) -> impl for<'a> FnMut(&'a str, &'a Path) -> Result<(FrontmatterFormat, String, String), ErrorKind>
+{
+	doll.builtin_emitters.put(TextEmit::default_emitters());
+
+	for tag in doll.tags.values_mut() {
+		if tag.emitters.get::<TextEmit>().is_none() {
+			tag.emitters.put::<TextEmit>(|_, _, _, _, _| {});
+		}
+	}
+
+	create_with_theme(doll, |_| Ok(TextEmit::default()), ctx, theme)
+}
+
+/// language support for markdoll, where the content is a dump of the syntax tree rather than anything emitted
+///
+/// useful for debugging tags (ex: to see what a tag parsed its content into), rendering diagnostics with the given theme
+pub fn create_ast<Ctx>(
+	mut doll: MarkDoll<Ctx>,
+	theme: DiagnosticTheme,
+) -> impl for<'a> FnMut(&'a str, &'a Path) -> Result<(FrontmatterFormat, String, String), ErrorKind>
+{
+	let plain = theme.is_plain();
+
+	move |src, path| {
+		let _span = trace_span!("parse markdoll").entered();
+
+		let (ok, diagnostics, frontmatter, ast) = doll.parse_document(
+			path.to_str()
+				.ok_or(ErrorKind::NonUTF8PathCharacters)?
+				.to_string(),
+			src.to_string(),
+			None,
+		);
+
+		let n = report_diagnostics(diagnostics, &doll.finish(), plain);
+		if ok && n == 0 {
+			Ok((
+				FrontmatterFormat::Toml,
+				frontmatter.unwrap_or_default(),
+				format!("{ast:#?}"),
+			))
+		} else {
+			Err(ErrorKind::Lang(LangErrorKind::Markdoll(n)))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn text_separates_blocks_and_lists() {
+		let mut lang = create_text_with_theme(MarkDoll::new(), |_| Ok(()), DiagnosticTheme::Plain);
+
+		let (_, _, text) = lang(
+			"some text\nacross lines\n\n&heading\n\tinside\n\n-\tone\n-\ttwo\n\n=\tfirst\n=\tsecond\n",
+			Path::new("page.doll"),
+		)
+		.unwrap();
+
+		assert_eq!(
+			text,
+			"some text across lines\n\nheading\n\ninside\n\n- one\n- two\n\n1. first\n2. second"
+		);
+	}
+}
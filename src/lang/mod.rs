@@ -1,23 +1,211 @@
 //! source languages for templating rules
 
 use {
-	crate::ErrorKind,
+	crate::{util::with_added_extension_but_stable, ErrorKind},
 	::core::cell::RefCell,
-	::std::{path::Path, rc::Rc},
+	::std::{
+		fs,
+		io,
+		path::{Path, PathBuf},
+		rc::Rc,
+	},
 };
 
 #[cfg(feature = "lang-markdoll")]
 pub mod markdoll;
 
+/// the format of a frontmatter string returned by a language, so that it is parsed correctly
+///
+/// languages return it alongside their frontmatter (ex: `Ok((FrontmatterFormat::Yaml, frontmatter, body))` for markdown with `---` frontmatter)
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum FrontmatterFormat {
+	/// TOML (ex: `+++` frontmatter)
+	#[default]
+	Toml,
+	/// YAML (ex: `---` frontmatter)
+	Yaml,
+	/// JSON
+	Json,
+}
+
+impl FrontmatterFormat {
+	/// the format of files with an extension (ex: `yml`), if it is known
+	#[must_use]
+	pub fn from_extension(extension: &str) -> Option<Self> {
+		match extension {
+			"toml" => Some(Self::Toml),
+			"yaml" | "yml" => Some(Self::Yaml),
+			"json" => Some(Self::Json),
+			_ => None,
+		}
+	}
+}
+
 /// wraps a language parser so it can be easily shared between multiple rules
 pub fn shared_lang(
-	lang: impl for<'a> FnMut(&'a str, &'a Path) -> Result<(String, String), ErrorKind>,
-) -> impl for<'a> FnMut(&'a str, &'a Path) -> Result<(String, String), ErrorKind> + Clone {
+	lang: impl for<'a> FnMut(
+		&'a str,
+		&'a Path,
+	) -> Result<(FrontmatterFormat, String, String), ErrorKind>,
+) -> impl for<'a> FnMut(&'a str, &'a Path) -> Result<(FrontmatterFormat, String, String), ErrorKind>
+       + Clone {
 	let lang = Rc::new(RefCell::new(lang));
 
 	move |src, path| lang.borrow_mut()(src, path)
 }
 
+/// a boxed language parser, used where different languages need to be stored together (ex: [`by_extension`])
+pub type BoxedLang<'a> = Box<
+	dyn for<'b> FnMut(&'b str, &'b Path) -> Result<(FrontmatterFormat, String, String), ErrorKind>
+		+ 'a,
+>;
+
+/// dispatches to a different language depending on the extension of the source file
+///
+/// - `langs` - pairs of (extension, language)
+///   - extensions are matched against the end of the file name, so they may include multiple parts (ex: `.page.doll`)
+///   - the first matching extension is used
+///
+/// ex: `by_extension([(".md", Box::new(md_lang) as BoxedLang), (".doll", Box::new(doll_lang))])`
+pub fn by_extension<'a>(
+	langs: impl IntoIterator<Item = (&'a str, BoxedLang<'a>)>,
+) -> impl for<'b> FnMut(&'b str, &'b Path) -> Result<(FrontmatterFormat, String, String), ErrorKind> + 'a
+{
+	let mut langs = langs.into_iter().collect::<Vec<_>>();
+
+	move |src, path| {
+		let name = path
+			.file_name()
+			.unwrap_or_default()
+			.to_str()
+			.ok_or(ErrorKind::NonUTF8PathCharacters)?;
+
+		match langs.iter_mut().find(|(ext, _)| name.ends_with(ext)) {
+			Some((_, lang)) => lang(src, path),
+			None => Err(LangErrorKind::UnknownExtension(path.to_path_buf()).into()),
+		}
+	}
+}
+
+/// which frontmatter [`sidecar`] prefers when both inline frontmatter and a sidecar file are present
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum SidecarPrecedence {
+	/// the sidecar file is only read when the inline frontmatter is absent (empty)
+	#[default]
+	Inline,
+	/// the sidecar file is always used when it exists, replacing the inline frontmatter
+	Sidecar,
+}
+
+/// reads frontmatter from a sidecar file next to the source file (ex: `post.md` + `post.md.toml`), for content that can't or shouldn't have inline frontmatter
+///
+/// - `lang` - the language to wrap
+/// - `extension` - the extension added to the source file's path to find its sidecar (ex: `toml`), which also determines how it is parsed if known (see [`FrontmatterFormat::from_extension`])
+/// - `precedence` - which frontmatter wins when both are present
+///
+/// source files without a sidecar file keep their inline frontmatter
+pub fn sidecar<'a>(
+	mut lang: impl for<'b> FnMut(&'b str, &'b Path) -> Result<(FrontmatterFormat, String, String), ErrorKind>
+		+ 'a,
+	extension: &'a str,
+	precedence: SidecarPrecedence,
+) -> impl for<'b> FnMut(&'b str, &'b Path) -> Result<(FrontmatterFormat, String, String), ErrorKind> + 'a
+{
+	move |src, path| {
+		let (format, frontmatter, body) = lang(src, path)?;
+
+		if precedence == SidecarPrecedence::Inline && !frontmatter.trim().is_empty() {
+			return Ok((format, frontmatter, body));
+		}
+
+		match fs::read_to_string(with_added_extension_but_stable(path, extension)) {
+			Ok(sidecar) => Ok((
+				FrontmatterFormat::from_extension(extension).unwrap_or(format),
+				sidecar,
+				body,
+			)),
+			Err(err) if err.kind() == io::ErrorKind::NotFound => Ok((format, frontmatter, body)),
+			Err(err) => Err(err.into()),
+		}
+	}
+}
+
+/// transforms the content from a language before it is given to the `globals` closure of a templating rule, whatever the templating engine (ex: to add heading anchors, lazy-load images, or rewrite links)
+///
+/// - `lang` - the language to wrap
+/// - `transform` - takes the content and the path of the source file, and returns the new content
+///
+/// the frontmatter is left alone
+pub fn transform_body<'a>(
+	mut lang: impl for<'b> FnMut(&'b str, &'b Path) -> Result<(FrontmatterFormat, String, String), ErrorKind>
+		+ 'a,
+	mut transform: impl for<'b> FnMut(String, &'b Path) -> Result<String, ErrorKind> + 'a,
+) -> impl for<'b> FnMut(&'b str, &'b Path) -> Result<(FrontmatterFormat, String, String), ErrorKind> + 'a
+{
+	move |src, path| {
+		let (format, frontmatter, body) = lang(src, path)?;
+
+		Ok((format, frontmatter, transform(body, path)?))
+	}
+}
+
+/// which frontmatter [`pipe`] prefers when both stages return frontmatter
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum PipePrecedence {
+	/// the first stage's frontmatter is used, unless it is absent (empty)
+	#[default]
+	First,
+	/// the second stage's frontmatter is used, unless it is absent (empty)
+	Second,
+}
+
+/// runs a language, then feeds its body through another language (ex: a templating preprocessor, then markdoll)
+///
+/// - `first` - the language given the source file
+/// - `second` - the language given the body returned by `first`
+/// - `precedence` - which frontmatter wins when both stages return some
+///
+/// semantics:
+/// - `second` is given the same source path as `first`, so errors and diagnostics point at the source file even though its input is `first`'s body
+/// - the body returned is `second`'s body, `first`'s body is only ever seen by `second`
+/// - frontmatter is never merged (the stages may use different formats), only one stage's frontmatter is returned along with that stage's format
+///   - the preferred stage's frontmatter is returned if it is not empty (ignoring whitespace)
+///   - otherwise the other stage's frontmatter is returned, even if it is empty too
+/// - `second` always runs, even if its frontmatter will be discarded, since its body is needed
+/// - an error in either stage is returned as-is, and `second` doesn't run if `first` fails
+pub fn pipe<'a>(
+	mut first: impl for<'b> FnMut(&'b str, &'b Path) -> Result<(FrontmatterFormat, String, String), ErrorKind>
+		+ 'a,
+	mut second: impl for<'b> FnMut(&'b str, &'b Path) -> Result<(FrontmatterFormat, String, String), ErrorKind>
+		+ 'a,
+	precedence: PipePrecedence,
+) -> impl for<'b> FnMut(&'b str, &'b Path) -> Result<(FrontmatterFormat, String, String), ErrorKind> + 'a
+{
+	move |src, path| {
+		let (first_format, first_frontmatter, body) = first(src, path)?;
+		let (second_format, second_frontmatter, body) = second(&body, path)?;
+
+		let (preferred, other) = match precedence {
+			PipePrecedence::First => (
+				(first_format, first_frontmatter),
+				(second_format, second_frontmatter),
+			),
+			PipePrecedence::Second => (
+				(second_format, second_frontmatter),
+				(first_format, first_frontmatter),
+			),
+		};
+
+		let (format, frontmatter) = if preferred.1.trim().is_empty() {
+			other
+		} else {
+			preferred
+		};
+
+		Ok((format, frontmatter, body))
+	}
+}
+
 /// errors parsing template source languages
 #[derive(::thiserror::Error, ::miette::Diagnostic, Debug)]
 pub enum LangErrorKind {
@@ -28,4 +216,12 @@ pub enum LangErrorKind {
 	#[error("markdoll failed ({} errors)", .0)]
 	#[diagnostic(code(dollgen::lang::markdoll))]
 	Markdoll(usize),
+
+	/// no language was registered for the source file's extension
+	#[error("no language for the extension of {}", .0.display())]
+	#[diagnostic(
+		code(dollgen::lang::unknown_extension),
+		help("add a language for this extension, or exclude the file from the rule")
+	)]
+	UnknownExtension(PathBuf),
 }
@@ -0,0 +1,205 @@
+//! compile less stylesheets to css, by running the `lessc` compiler (install it with `npm install -g less`)
+//!
+//! requires `less` feature
+
+use {
+	crate::{command::CommandErrorKind, Context, ErrorKind, Jobs, PlannedTransformation},
+	::miette::LabeledSpan,
+	::std::{
+		fs,
+		path::{Path, PathBuf},
+		process::Command,
+	},
+	::tracing::{debug, debug_span},
+};
+
+/// how to compile less
+#[derive(Clone, Debug)]
+pub struct Options {
+	/// the compiler to run, found on the `PATH` if it has no separators
+	pub program: String,
+	/// directories `@import`s are resolved against, in addition to the directory of the stylesheet
+	pub include_paths: Vec<PathBuf>,
+	/// whether units must be compatible in math (ex: `1px + 1em` is an error, rather than `2px`)
+	pub strict_units: bool,
+	/// extra arguments given to the compiler, before the stylesheet (ex: `--rewrite-urls=all`)
+	pub args: Vec<String>,
+}
+
+impl Default for Options {
+	fn default() -> Self {
+		Self {
+			program: "lessc".to_string(),
+			include_paths: Vec::new(),
+			strict_units: false,
+			args: Vec::new(),
+		}
+	}
+}
+
+/// compiles less
+///
+/// - `options` - the options to compile with
+pub fn create(
+	options: &Options,
+) -> impl FnMut(
+	PathBuf,
+	Vec<String>,
+	&Path,
+	&Context,
+) -> Result<Box<dyn PlannedTransformation>, ErrorKind>
+       + '_ {
+	move |src, _, _: &Path, context: &Context| {
+		let _span = debug_span!("compile less", ?options).entered();
+
+		let mut command = Command::new(&options.program);
+		command.arg("--no-color");
+		if !options.include_paths.is_empty() {
+			let paths = ::std::env::join_paths(&options.include_paths)
+				.map_err(|_| ErrorKind::NonUTF8PathCharacters)?;
+			let mut arg = ::std::ffi::OsString::from("--include-path=");
+			arg.push(paths);
+			command.arg(arg);
+		}
+		if options.strict_units {
+			command.arg("--strict-units=on");
+		}
+		command.args(&options.args).arg(&src);
+
+		let _slot = context.get::<Jobs>().map(Jobs::acquire);
+		let out = command
+			.output()
+			.map_err(|err| CommandErrorKind::ProcessFailed {
+				program: options.program.clone(),
+				err,
+			})?;
+		let stderr = String::from_utf8_lossy(&out.stderr).into_owned();
+
+		if !out.status.success() {
+			return Err(parse_error(&src, &stderr).unwrap_or_else(|| {
+				CommandErrorKind::Failed {
+					program: options.program.clone(),
+					status: out.status.to_string(),
+					span: (0, stderr.len()),
+					stderr,
+				}
+				.into()
+			}));
+		}
+
+		if !stderr.is_empty() {
+			debug!(?src, stderr, "less compiled with warnings");
+		}
+
+		Ok(Box::new(
+			String::from_utf8(out.stdout).map_err(|_| ErrorKind::NonUTF8Characters)?,
+		))
+	}
+}
+
+/// turn an error from `lessc` (ex: `ParseError: Unrecognised input in style.less on line 3, column 5:`) into one pointing into the stylesheet
+fn parse_error(src: &Path, stderr: &str) -> Option<ErrorKind> {
+	let first = stderr.lines().next()?;
+	let (message, location) = first.rsplit_once(" on line ")?;
+	let (line, column) = location.trim_end_matches(':').split_once(", column ")?;
+	let (line, column) = (line.parse::<usize>().ok()?, column.parse::<usize>().ok()?);
+
+	// the error may be in an imported stylesheet
+	let (message, file) = match message.rsplit_once(" in ") {
+		Some((message, file)) => (message, PathBuf::from(file)),
+		None => (message, src.to_path_buf()),
+	};
+	let source = fs::read_to_string(&file).ok()?;
+
+	let offset = source
+		.split_inclusive('\n')
+		.take(line.saturating_sub(1))
+		.map(str::len)
+		.sum::<usize>()
+		+ column.saturating_sub(1);
+	let offset = offset.min(source.len().saturating_sub(1));
+
+	Some(ErrorKind::LESSIntegration {
+		span: [LabeledSpan::new_primary_with_span(
+			Some(message.to_string()),
+			(offset, 1),
+		)],
+		src: ::miette::NamedSource::new(file.to_string_lossy(), source).with_language("less"),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use {
+		super::*,
+		::std::{env, process},
+	};
+
+	/// the label of a parsed error, as (offset, message, file)
+	fn located(src: &Path, stderr: &str) -> Option<(usize, String, String)> {
+		match parse_error(src, stderr)? {
+			ErrorKind::LESSIntegration { span: [span], src } => Some((
+				span.offset(),
+				span.label().unwrap_or_default().to_string(),
+				src.name().to_string(),
+			)),
+			err => panic!("expected a less error, got {err:?}"),
+		}
+	}
+
+	#[test]
+	fn parse_error_points_into_the_stylesheet() {
+		let root = env::temp_dir().join(format!("dollgen-less-error-{}", process::id()));
+		fs::create_dir_all(&root).unwrap();
+		let style = root.join("style.less");
+		let import = root.join("import.less");
+		fs::write(&style, "a {\n  color: red\n  bad;\n}\n").unwrap();
+		fs::write(&import, "b {\n  bad;\n}\n").unwrap();
+
+		let in_style = located(
+			&style,
+			&format!(
+				"ParseError: Unrecognised input in {} on line 3, column 3:\n2   color: red\n3   bad;",
+				style.display()
+			),
+		);
+		let in_import = located(
+			&style,
+			&format!(
+				"ParseError: Unrecognised input in {} on line 2, column 3:",
+				import.display()
+			),
+		);
+		let without_file = located(&style, "SyntaxError: expected ';' on line 2, column 13:");
+		fs::remove_dir_all(&root).unwrap();
+
+		assert_eq!(
+			in_style,
+			Some((
+				"a {\n  color: red\n  ".len(),
+				"ParseError: Unrecognised input".to_string(),
+				style.to_string_lossy().into_owned(),
+			))
+		);
+		assert_eq!(
+			in_import.map(|(offset, _, file)| (offset, file)),
+			Some(("b {\n  ".len(), import.to_string_lossy().into_owned()))
+		);
+		assert_eq!(
+			without_file.map(|(offset, message, _)| (offset, message)),
+			Some((
+				"a {\n  color: red".len(),
+				"SyntaxError: expected ';'".to_string()
+			))
+		);
+	}
+
+	#[test]
+	fn parse_error_ignores_unknown_output() {
+		let src = Path::new("style.less");
+
+		assert!(parse_error(src, "").is_none());
+		assert!(parse_error(src, "lessc: command failed").is_none());
+		assert!(parse_error(src, "Error on line three, column 1:").is_none());
+	}
+}
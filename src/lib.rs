@@ -6,263 +6,2497 @@
 )]
 #![allow(clippy::missing_errors_doc, reason = "a lot of ")]
 
-pub use ::capturing_glob::{Entry, Pattern};
+pub use ::capturing_glob::{Entry, MatchOptions, Pattern, PatternError};
 use {
-	::capturing_glob::{glob_with, MatchOptions},
+	crate::{
+		filesystem::{Fs, StdFs},
+		format_str::format_with,
+		util::{hash_bytes, native_separators, normalize_lexically, StableHasher},
+	},
+	::capturing_glob::glob_with,
+	::core::any::{type_name, Any, TypeId},
 	::miette::{Diagnostic, NamedSource, SourceSpan},
 	::std::{
-		collections::HashSet,
+		borrow::Cow,
+		collections::{BTreeMap, HashSet},
+		ffi::{OsStr, OsString},
 		fs,
+		hash::Hasher,
+		io::{self, BufRead, Write},
 		path::{Path, PathBuf},
+		sync::{
+			atomic::{AtomicBool, AtomicUsize, Ordering},
+			Arc,
+			Condvar,
+			Mutex,
+			MutexGuard,
+			PoisonError,
+		},
+		thread,
+		time::{Duration, Instant},
 	},
-	::strfmt::{strfmt_map, DisplayStr, FmtError, Formatter},
-	::tracing::{debug_span, error, info_span, instrument, Level},
+	::strfmt::FmtError,
+	::tracing::{debug, debug_span, error, info_span, instrument, warn, Level},
 };
 
+#[cfg(feature = "assets")]
+pub mod assets;
+
+#[cfg(feature = "command")]
+pub mod command;
+
+#[cfg(feature = "compress")]
+pub mod compress;
+
+#[cfg(feature = "data")]
+pub mod data;
+
+#[cfg(feature = "integrity")]
+pub mod integrity;
+
+#[cfg(feature = "less")]
+pub mod less;
+
 #[cfg(feature = "liquid")]
 pub mod liquid;
 
-#[cfg(feature = "minijinja")]
-pub mod minijinja;
+#[cfg(feature = "livereload")]
+pub mod livereload;
+
+#[cfg(feature = "minijinja")]
+pub mod minijinja;
+
+#[cfg(feature = "scss")]
+pub mod scss;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "watch")]
+pub mod watch;
+
+pub mod filesystem;
+
+pub mod glob;
+
+pub mod graph;
+
+pub mod lang;
+
+pub mod paginate;
+
+pub mod redirects;
+
+#[cfg(feature = "regex")]
+pub mod regex;
+
+#[cfg(any(feature = "liquid", feature = "minijinja"))]
+mod templating;
+
+mod format_str;
+
+mod util;
+
+/// the core of dollgen, defines a list of globs to include, a list of globs to exclude, how to transform the file, and where to emit it to
+pub struct Rule<'a> {
+	/// the name of the rule, which may be used to select it (see [`BuildOptions::select`])
+	pub name: Option<&'a str>,
+	/// tags which may be used to select the rule (see [`BuildOptions::select`])
+	pub tags: &'a [&'a str],
+	/// if set, the rule is only planned when this returns true (ex: only for production builds, based on an environment variable)
+	pub when: Option<&'a dyn Fn() -> bool>,
+	/// which files to include
+	///
+	/// may capture parts of the path (ex: `src/**/*.doll`)
+	pub include: &'a [Pattern],
+	/// how each of `include` matches paths, by index (ex: case-insensitive matching for user uploads)
+	///
+	/// patterns without an entry use [`MATCH_OPTIONS`]
+	pub include_options: &'a [MatchOptions],
+	/// if set, `include` patterns are relative to this directory, which is stripped from matched paths before captures are extracted
+	///
+	/// so captures stay the same wherever the sources are (ex: `src_root: Some(content_dir)` with an include of `(**)/(*).md`, where `content_dir` is an absolute path from configuration)
+	///
+	/// the source paths given to plan closures, `exclude`, and `include_regex` still use the full path
+	pub src_root: Option<&'a Path>,
+	/// regexes which include files, in addition to `include` (see [`regex`])
+	///
+	/// requires `regex` feature
+	#[cfg(feature = "regex")]
+	pub include_regex: &'a [regex::RegexInclude],
+	/// which files to exclude
+	pub exclude: &'a [Pattern],
+	/// exclude paths that contain any of these, in addition to `exclude` (ex: `.draft.`)
+	pub exclude_containing: &'a [&'a str],
+	/// exclude paths with any of these extensions, in addition to `exclude` (ex: `bak`)
+	///
+	/// extensions are matched against the end of the file name, so they may have several parts (ex: `tar.gz`), and a leading `.` is optional
+	pub exclude_extensions: &'a [&'a str],
+	/// whether `include` matches files, directories, or both
+	pub matches: Matches,
+	/// where output files should be emitted
+	///
+	/// format specifiers like `{0}` pull from the captures of whatever `include` glob matched (ex: `dist/{0}/{1}.html`), and may reshape them as paths (ex: `{0|basename}`, see [`format`])
+	///
+	/// `/` is always a separator, and is replaced by the platform's separator (so the resolved path only uses `\` on windows)
+	pub dst: &'static str,
+	/// plan a transformation
+	///
+	/// takes the input path (matched by an `include`), the captures from the `include` that matched, the output path (produced by `dst`), and the shared [`Context`] (see [`BuildOptions::context`])
+	///
+	/// returns plan data to be passed into `execute`
+	pub plan: &'a mut PlanFn<'a>,
+	/// if set, used instead of `plan`, so that a source may expand into several plans that each have their own destination
+	///
+	/// takes the same arguments as `plan`, and every returned plan must also be inside of [`BuildOptions::output_root`] if it is set
+	pub plan_many: Option<&'a mut PlanManyFn<'a>>,
+	/// if set (and `plan_many` isn't), used instead of `plan`, so that the sources this rule matches are planned in parallel, on up to [`BuildOptions::jobs`] threads
+	///
+	/// takes the same arguments as `plan`, but can't mutate its state and must return a [`Send`] transformation, so it is only worth it when planning is expensive (ex: compiling or parsing)
+	///
+	/// plans are still returned in the order the sources were matched, so the output is the same as planning sequentially
+	pub plan_parallel: Option<&'a PlanParallelFn<'a>>,
+	/// if set, applied to every transformation this rule plans, right after `plan` or `plan_many` (ex: to wrap every output in a minifier, without changing the plan closure)
+	pub post: Option<&'a mut PostFn<'a>>,
+}
+
+// not derived with `tyfling`, as it can't skip fields that depend on features
+impl ::core::fmt::Debug for Rule<'_> {
+	fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+		write!(
+			f,
+			"+ {:?}\n- {:?}\n> \"{}\"",
+			self.include
+				.iter()
+				.map(ToString::to_string)
+				.collect::<Vec<_>>(),
+			self.exclude
+				.iter()
+				.map(ToString::to_string)
+				.collect::<Vec<_>>(),
+			self.dst
+		)?;
+
+		if !self.exclude_containing.is_empty() || !self.exclude_extensions.is_empty() {
+			write!(
+				f,
+				"\n- containing {:?}, extensions {:?}",
+				self.exclude_containing, self.exclude_extensions
+			)?;
+		}
+
+		#[cfg(feature = "regex")]
+		if !self.include_regex.is_empty() {
+			write!(
+				f,
+				"\n+ regex {:?}",
+				self.include_regex
+					.iter()
+					.map(|include| include.regex.as_str())
+					.collect::<Vec<_>>()
+			)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// a closure that plans any number of transformations (see [`Rule::plan_many`])
+pub type PlanManyFn<'a> =
+	dyn FnMut(PathBuf, Vec<String>, &Path, &Context) -> Result<Vec<Plan>, ErrorKind> + 'a;
+
+/// a closure that plans a transformation from several threads at once (see [`Rule::plan_parallel`])
+pub type PlanParallelFn<'a> = dyn Fn(
+		PathBuf,
+		Vec<String>,
+		&Path,
+		&Context,
+	) -> Result<Box<dyn PlannedTransformation + Send>, ErrorKind>
+	+ Sync
+	+ 'a;
+
+/// a closure that wraps or inspects a planned transformation (see [`Rule::post`])
+pub type PostFn<'a> =
+	dyn FnMut(Box<dyn PlannedTransformation>) -> Box<dyn PlannedTransformation> + 'a;
+
+/// a closure that plans a transformation (see [`Rule::plan`])
+pub type PlanFn<'a> = dyn FnMut(PathBuf, Vec<String>, &Path, &Context) -> Planned + 'a;
+
+/// what a closure that plans a transformation returns (see [`PlanFn`])
+pub type Planned = Result<Box<dyn PlannedTransformation>, ErrorKind>;
+
+impl<'a> Rule<'a> {
+	/// create a rule without a name, tags, `when` predicate, `plan_many`, `plan_parallel`, or `post` closure, that only matches files
+	///
+	/// other fields may be set with struct update syntax (ex: `Rule { tags: &["content"], ..Rule::new(...) }`)
+	pub fn new(
+		include: &'a [Pattern],
+		exclude: &'a [Pattern],
+		dst: &'static str,
+		plan: &'a mut PlanFn<'a>,
+	) -> Self {
+		Self {
+			name: None,
+			tags: &[],
+			when: None,
+			include,
+			include_options: &[],
+			src_root: None,
+			#[cfg(feature = "regex")]
+			include_regex: &[],
+			exclude,
+			exclude_containing: &[],
+			exclude_extensions: &[],
+			matches: Matches::default(),
+			dst,
+			plan,
+			plan_many: None,
+			plan_parallel: None,
+			post: None,
+		}
+	}
+
+	/// whether this rule's name or any of its tags are in `select`
+	fn is_selected(&self, select: &[&str]) -> bool {
+		select.is_empty()
+			|| self.name.is_some_and(|name| select.contains(&name))
+			|| self.tags.iter().any(|tag| select.contains(tag))
+	}
+
+	/// how the `include` at `include_index` matches paths
+	fn match_options(&self, include_index: usize) -> &MatchOptions {
+		self.include_options
+			.get(include_index)
+			.unwrap_or(&MATCH_OPTIONS)
+	}
+
+	/// the pattern to glob for `include`, which is inside of `src_root` if it is set, and the length of the prefix added for it
+	fn include_glob<'p>(&self, include: &'p Pattern) -> Result<(Cow<'p, str>, usize), ErrorKind> {
+		let Some(src_root) = self.src_root else {
+			return Ok((Cow::Borrowed(include.as_str()), 0));
+		};
+
+		let mut prefix =
+			glob::escape_glob(src_root.to_str().ok_or(ErrorKind::NonUTF8PathCharacters)?);
+		if !prefix.is_empty() && !prefix.ends_with('/') {
+			prefix.push('/');
+		}
+		let len = prefix.len();
+		prefix.push_str(include.as_str());
+
+		Ok((Cow::Owned(prefix), len))
+	}
+
+	/// whether this rule's `when` predicate allows it to be planned
+	fn is_enabled(&self) -> bool {
+		self.when.is_none_or(|when| when())
+	}
+
+	/// whether `path` is excluded by `exclude`, `global` (see [`BuildOptions::exclude`]), `exclude_containing`, or `exclude_extensions`
+	fn is_excluded(&self, path: &Path, global: &[Pattern]) -> bool {
+		if self
+			.exclude
+			.iter()
+			.chain(global)
+			.any(|exclude| exclude.matches_path(path))
+		{
+			return true;
+		}
+
+		let path_str = path.to_string_lossy();
+		if self
+			.exclude_containing
+			.iter()
+			.any(|substring| path_str.contains(substring))
+		{
+			return true;
+		}
+
+		let name = path.file_name().unwrap_or_default().to_string_lossy();
+		self.exclude_extensions.iter().any(|extension| {
+			let extension = extension.strip_prefix('.').unwrap_or(extension);
+			name.len() > extension.len()
+				&& name.ends_with(extension)
+				&& name[..name.len() - extension.len()].ends_with('.')
+		})
+	}
+
+	/// whether any of this rule's `include` patterns match `path`, ignoring `exclude`
+	#[cfg(feature = "watch")]
+	fn includes(&self, path: &Path) -> bool {
+		let glob = self
+			.include
+			.iter()
+			.enumerate()
+			.any(|(index, include)| include.matches_path_with(path, self.match_options(index)));
+
+		#[cfg(feature = "regex")]
+		let glob = glob
+			|| self.include_regex.iter().any(|include| {
+				include
+					.captures(path)
+					.is_ok_and(|captures| captures.is_some())
+			});
+
+		glob
+	}
+}
+
+/// what kind of paths a rule's `include` patterns match
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum Matches {
+	/// only files
+	#[default]
+	Files,
+	/// only directories, so that a directory may be transformed as a unit (ex: bundling a folder of images)
+	Directories,
+	/// both files and directories
+	Both,
+}
+
+impl Matches {
+	/// whether a path is of a kind that should be matched
+	#[must_use]
+	pub fn accepts(self, path: &Path) -> bool {
+		match self {
+			Self::Files => path.is_file(),
+			Self::Directories => path.is_dir(),
+			Self::Both => path.is_file() || path.is_dir(),
+		}
+	}
+}
+
+/// options that change how rules are planned
+#[derive(Default, Debug)]
+pub struct BuildOptions<'a> {
+	/// only plan rules whose name or any of whose tags are in this list
+	///
+	/// if empty, every rule is planned
+	pub select: &'a [&'a str],
+	/// which files to exclude from every rule, in addition to each rule's own `exclude` (ex: `**/.git/**`, `**/node_modules/**`)
+	pub exclude: &'a [Pattern],
+	/// what to do with source files that are empty
+	pub empty_sources: EmptySources,
+	/// the directory every output must be inside of
+	///
+	/// if set, a `dst` that resolves outside of it (ex: a capture containing `..`) fails with [`ErrorKind::PathEscape`], which matters when building untrusted content
+	///
+	/// `.` and `..` are resolved without touching the filesystem, so symlinks are not followed
+	pub output_root: Option<&'a Path>,
+	/// allow a source file to be planned by every rule that matches it, rather than only the first
+	///
+	/// this is useful for intentionally emitting several outputs from one source (ex: an html page and a json representation of it)
+	///
+	/// a single rule still plans each source file at most once
+	pub allow_multiple_rules: bool,
+	/// what to do with rules whose `include` patterns match no paths at all (ex: a typo, or the wrong working directory)
+	///
+	/// this only applies when globbing for paths, not to [`plan_files`]
+	pub empty_rules: EmptyRules,
+	/// shared, read-only state given to every plan closure
+	///
+	/// if `None`, plan closures are given an empty context
+	pub context: Option<&'a Context>,
+	/// how much work the build may do at once (see [`BuildOptions::jobs`] and [`Jobs`])
+	///
+	/// if `None`, the number of CPUs
+	pub jobs: Option<usize>,
+	/// stops the build once set (ex: from a gui or another thread), which is checked before each source file is planned and each plan is executed
+	///
+	/// the build then fails with [`ErrorKind::Cancelled`], leaving whatever was already written, so a runaway build (ex: a glob matching a huge tree) can be aborted without killing the process
+	pub cancel: Option<&'a AtomicBool>,
+	/// rebuild everything, ignoring what earlier builds left behind (ex: after changing something that isn't tracked, like a global)
+	///
+	/// every one of `caches` is cleared before planning, every plan is forced to write its outputs even if they are unchanged (see [`PlannedTransformation::force`]),
+	/// and [`Forced`] is put in the context, for integrations that skip work while planning (ex: wasm always runs bindgen)
+	pub force: bool,
+	/// caches that `force` clears (ex: `&[&*liquid]` for a shared `liquid::Liquid`)
+	pub caches: &'a [&'a dyn ClearCache],
+}
+
+/// a cache of something that may have changed since it was cached (ex: parsed templates), see [`BuildOptions::caches`]
+pub trait ClearCache: Sync {
+	/// forget everything that was cached
+	fn clear_cache(&self);
+}
+
+impl ::core::fmt::Debug for dyn ClearCache + '_ {
+	fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+		f.write_str("ClearCache")
+	}
+}
+
+impl BuildOptions<'_> {
+	/// how much work the build may do at once, which is at least 1
+	///
+	/// this limits both how many sources rules with [`Rule::plan_parallel`] plan at once, and how many external programs integrations run at once (see [`Jobs`]),
+	/// so that every layer agrees on one limit rather than each assuming it has every CPU
+	#[must_use]
+	pub fn jobs(&self) -> usize {
+		self.jobs
+			.or_else(|| thread::available_parallelism().ok().map(usize::from))
+			.unwrap_or(1)
+			.max(1)
+	}
+
+	/// fail with [`ErrorKind::Cancelled`] if [`BuildOptions::cancel`] is set
+	fn check_cancelled(&self) -> Result<(), ErrorKind> {
+		if self
+			.cancel
+			.is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+		{
+			Err(ErrorKind::Cancelled)
+		} else {
+			Ok(())
+		}
+	}
+
+	/// clear every one of [`BuildOptions::caches`], if [`BuildOptions::force`] is set
+	fn clear_caches(&self) {
+		if self.force {
+			debug!(caches = self.caches.len(), "forced, clearing caches");
+
+			for cache in self.caches {
+				cache.clear_cache();
+			}
+		}
+	}
+
+	/// the context given to plan closures
+	fn context(&self) -> &Context {
+		static EMPTY: Context = Context::new();
+
+		self.context.unwrap_or(&EMPTY)
+	}
+
+	/// the context for a build, which has the build's [`Jobs`] unless the context already has some (ex: from an outer build), and [`Forced`] if the build is forced
+	fn build_context(&self) -> Context {
+		let mut context = self.context().clone();
+
+		if context.get::<Jobs>().is_none() {
+			context.insert(Jobs::new(self.jobs()));
+		}
+		if self.force {
+			context.insert(Forced);
+		}
+
+		context
+	}
+}
+
+/// in the [`Context`] of a build with [`BuildOptions::force`] set, so that integrations that skip work while planning do it anyway
+///
+/// ex: `let forced = context.get::<Forced>().is_some();`
+#[derive(Clone, Copy, Debug)]
+pub struct Forced;
+
+/// a limit on how many external programs (ex: `cargo` for wasm, or a `command`) run at once across a build, shared through the [`Context`] given to plan closures
+///
+/// every build puts one in the context, with a limit of [`BuildOptions::jobs`], and integrations that run programs hold a slot while they run (see [`Jobs::acquire`])
+///
+/// ex: `context.get::<Jobs>().map(Jobs::acquire)`
+#[derive(Clone, Debug)]
+pub struct Jobs(Arc<(Mutex<usize>, Condvar, usize)>);
+
+impl Jobs {
+	/// allow `limit` programs to run at once, which is at least 1
+	#[must_use]
+	pub fn new(limit: usize) -> Self {
+		Self(Arc::new((Mutex::new(0), Condvar::new(), limit.max(1))))
+	}
+
+	/// how many programs may run at once
+	#[must_use]
+	pub fn limit(&self) -> usize {
+		self.0 .2
+	}
+
+	/// wait for a slot, which is freed when the returned guard is dropped
+	pub fn acquire(&self) -> JobSlot<'_> {
+		let (running, freed, limit) = &*self.0;
+		let mut running = freed
+			.wait_while(
+				running.lock().unwrap_or_else(PoisonError::into_inner),
+				|running| *running >= *limit,
+			)
+			.unwrap_or_else(PoisonError::into_inner);
+		*running += 1;
+
+		JobSlot(self)
+	}
+}
+
+/// a slot held in [`Jobs`], see [`Jobs::acquire`]
+#[derive(Debug)]
+pub struct JobSlot<'a>(&'a Jobs);
+
+impl Drop for JobSlot<'_> {
+	fn drop(&mut self) {
+		let (running, freed, _) = &*self.0 .0;
+		*running.lock().unwrap_or_else(PoisonError::into_inner) -= 1;
+		freed.notify_one();
+	}
+}
+
+/// shared, read-only state given to every plan closure (ex: a base url, or data produced by an earlier build step), see [`BuildOptions::context`]
+///
+/// values are stored by their type, so wrap them in a newtype to store several values of the same type
+///
+/// cloning a context shares its values, rather than cloning them
+#[derive(Clone, Default)]
+pub struct Context {
+	values: BTreeMap<TypeId, (&'static str, Arc<dyn Any + Send + Sync>)>,
+}
+
+impl Context {
+	/// create an empty context
+	#[must_use]
+	pub const fn new() -> Self {
+		Self {
+			values: BTreeMap::new(),
+		}
+	}
+
+	/// add a value, returning the previous value of the same type if there was one (and no clone of this context still shares it)
+	pub fn insert<T: Any + Send + Sync>(&mut self, value: T) -> Option<T> {
+		self.values
+			.insert(TypeId::of::<T>(), (type_name::<T>(), Arc::new(value)))
+			.and_then(|(_, previous)| previous.downcast().ok())
+			.and_then(|previous| Arc::try_unwrap(previous).ok())
+	}
+
+	/// add a value, replacing any previous value of the same type
+	#[must_use]
+	pub fn with<T: Any + Send + Sync>(mut self, value: T) -> Self {
+		self.insert(value);
+		self
+	}
+
+	/// get the value of a type, if there is one
+	#[must_use]
+	pub fn get<T: Any>(&self) -> Option<&T> {
+		self.values
+			.get(&TypeId::of::<T>())
+			.and_then(|(_, value)| value.downcast_ref())
+	}
+}
+
+impl ::core::fmt::Debug for Context {
+	fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+		f.debug_set()
+			.entries(self.values.values().map(|(name, _)| name))
+			.finish()
+	}
+}
+
+/// what to do with source files that are empty (zero bytes)
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum EmptySources {
+	/// plan them like any other file
+	#[default]
+	Plan,
+	/// skip them, as if they were excluded
+	Skip,
+	/// fail with [`ErrorKind::EmptySource`], as they are usually a mistake
+	Error,
+}
+
+/// what to do with rules that match no paths
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum EmptyRules {
+	/// nothing, as some rules legitimately match nothing
+	#[default]
+	Allow,
+	/// log a warning
+	Warn,
+	/// fail with [`ErrorKind::EmptyRule`]
+	Error,
+}
+
+/// a planned transformation that can be `execute`d
+///
+/// this trait can be downcasted to access the internal plan (this is useful for those that want to plan transformations and peek/modify them before executing)
+pub trait PlannedTransformation: ::core::any::Any + ::core::fmt::Debug {
+	/// executes the planned transformation
+	///
+	/// takes the plan data and output path (produced by `dst`)
+	///
+	/// # Errors
+	///
+	/// if the execution fails
+	fn execute(self: Box<Self>, dst: PathBuf) -> Result<(), ErrorKind>;
+
+	/// executes the planned transformation, writing to a [`Sink`] rather than a path
+	///
+	/// by default, only [`Sink::File`] is supported (which is the same as `execute`), as some transformations write several files relative to their destination
+	///
+	/// # Errors
+	///
+	/// if the execution fails, or the sink is not supported ([`ErrorKind::UnsupportedSink`])
+	fn execute_to(self: Box<Self>, sink: Sink) -> Result<(), ErrorKind> {
+		match sink {
+			Sink::File(dst) => self.execute(dst),
+			_ => Err(ErrorKind::UnsupportedSink(::core::any::type_name::<Self>())),
+		}
+	}
+
+	/// executes the planned transformation against a filesystem (see [`execute_in`])
+	///
+	/// by default, this writes to the sink the filesystem provides for `dst` (see [`Fs::sink`]), so transformations that read or write files themselves (ex: [`copy`]) should go through `fs` instead
+	///
+	/// # Errors
+	///
+	/// if the execution fails, or the filesystem's sink is not supported ([`ErrorKind::UnsupportedSink`])
+	fn execute_in(self: Box<Self>, dst: PathBuf, fs: &dyn Fs) -> Result<(), ErrorKind> {
+		self.execute_to(fs.sink(&dst))
+	}
+
+	/// whether this transformation never writes to its destination
+	///
+	/// if so, `execute` does not create the destination's parent directories
+	fn is_noop(&self) -> bool {
+		false
+	}
+
+	/// every path this transformation writes to when executed to `dst`, without executing it
+	///
+	/// by default, this is just `dst`, but transformations that write several files relative to their destination should list all of them (ex: manifests, link checking, or cleaning stale outputs)
+	fn outputs(&self, dst: &Path) -> Vec<PathBuf> {
+		vec![dst.to_path_buf()]
+	}
+
+	/// every path this transformation reads from when executed (ex: the template it renders), without executing it
+	///
+	/// by default, this is empty, as most transformations read their source while planning rather than while executing
+	fn inputs(&self) -> Vec<PathBuf> {
+		Vec::new()
+	}
+
+	/// write outputs when executed even if they look unchanged, see [`BuildOptions::force`]
+	///
+	/// transformations that skip unchanged outputs (ex: [`CopyIfChanged`]) should stop skipping, and transformations that wrap another should forward this to it
+	///
+	/// by default, this does nothing
+	fn force(&mut self) {}
+
+	/// feed anything that affects the output, other than the contents of `inputs`, into `state` (ex: content rendered while planning, or the arguments of a program), see [`Plan::identity`]
+	///
+	/// transformations that wrap another should feed the inner one's too
+	///
+	/// by default, this feeds nothing
+	fn fingerprint(&self, state: &mut dyn Hasher) {
+		let _ = state;
+	}
+}
+
+/// [`noop`] transformation, does not write to the destination file
+impl PlannedTransformation for () {
+	fn execute(self: Box<Self>, _: PathBuf) -> Result<(), ErrorKind> {
+		Ok(())
+	}
+
+	fn execute_to(self: Box<Self>, _: Sink) -> Result<(), ErrorKind> {
+		Ok(())
+	}
+
+	fn is_noop(&self) -> bool {
+		true
+	}
+
+	fn outputs(&self, _: &Path) -> Vec<PathBuf> {
+		Vec::new()
+	}
+}
+
+/// writes the binary blob to the destination file
+impl PlannedTransformation for Vec<u8> {
+	#[instrument(skip(self), name = "write binary blob", level = Level::DEBUG)]
+	fn execute(self: Box<Self>, dst: PathBuf) -> Result<(), ErrorKind> {
+		fs::write(dst, *self).map_err(ErrorKind::Io)
+	}
+
+	fn execute_to(self: Box<Self>, sink: Sink) -> Result<(), ErrorKind> {
+		sink.write_all(&self)
+	}
+
+	fn fingerprint(&self, state: &mut dyn Hasher) {
+		state.write(self);
+	}
+}
+
+/// writes the string to the destination file
+impl PlannedTransformation for String {
+	#[instrument(skip(self), name = "write string data", level = Level::DEBUG)]
+	fn execute(self: Box<Self>, dst: PathBuf) -> Result<(), ErrorKind> {
+		fs::write(dst, self.as_bytes()).map_err(ErrorKind::Io)
+	}
+
+	fn execute_to(self: Box<Self>, sink: Sink) -> Result<(), ErrorKind> {
+		sink.write_all(self.as_bytes())
+	}
+
+	fn fingerprint(&self, state: &mut dyn Hasher) {
+		state.write(self.as_bytes());
+	}
+}
+
+/// [`copy`] transformation, copies the file path specified to the destination file
+impl PlannedTransformation for PathBuf {
+	#[instrument(name = "copy", level = Level::DEBUG)]
+	fn execute(self: Box<Self>, dst: PathBuf) -> Result<(), ErrorKind> {
+		self.execute_in(dst, &StdFs)
+	}
+
+	fn execute_in(self: Box<Self>, dst: PathBuf, fs: &dyn Fs) -> Result<(), ErrorKind> {
+		fs.copy(&self, &dst).map_err(ErrorKind::Io)
+	}
+
+	fn execute_to(self: Box<Self>, sink: Sink) -> Result<(), ErrorKind> {
+		match sink {
+			Sink::File(dst) => self.execute(dst),
+			sink => {
+				io::copy(&mut fs::File::open(*self)?, &mut sink.open()?)?;
+				Ok(())
+			}
+		}
+	}
+
+	fn inputs(&self) -> Vec<PathBuf> {
+		vec![self.clone()]
+	}
+}
+
+/// hard-links its source path to the output path, rather than copying it, falling back to copying if the paths are on different filesystems
+///
+/// since the output and source are then the same file, edits to the output also affect the source, so only use this where the output is never modified (ex: a read-only deploy tree)
+#[derive(Debug)]
+pub struct HardLink(pub PathBuf);
+
+impl PlannedTransformation for HardLink {
+	#[instrument(name = "hard link", level = Level::DEBUG)]
+	fn execute(self: Box<Self>, dst: PathBuf) -> Result<(), ErrorKind> {
+		self.execute_in(dst, &StdFs)
+	}
+
+	fn execute_in(self: Box<Self>, dst: PathBuf, fs: &dyn Fs) -> Result<(), ErrorKind> {
+		fs.hard_link(&self.0, &dst).map_err(ErrorKind::Io)
+	}
+
+	fn execute_to(self: Box<Self>, sink: Sink) -> Result<(), ErrorKind> {
+		match sink {
+			Sink::File(dst) => self.execute(dst),
+			sink => Box::new(self.0).execute_to(sink),
+		}
+	}
+
+	fn inputs(&self) -> Vec<PathBuf> {
+		vec![self.0.clone()]
+	}
+}
+
+/// copies its source path to the output path, but only if the output doesn't already have the same contents
+///
+/// this leaves unchanged outputs untouched (including their modification times), which saves rewriting large unchanged assets every build
+#[derive(Clone, Debug)]
+pub struct CopyIfChanged {
+	/// the file to copy
+	pub src: PathBuf,
+	/// copy even if the output is unchanged (see [`PlannedTransformation::force`])
+	pub force: bool,
+}
+
+impl CopyIfChanged {
+	/// copy `src`, unless the output is unchanged
+	#[must_use]
+	pub fn new(src: impl Into<PathBuf>) -> Self {
+		Self {
+			src: src.into(),
+			force: false,
+		}
+	}
+}
+
+impl PlannedTransformation for CopyIfChanged {
+	#[instrument(name = "copy if changed", level = Level::DEBUG)]
+	fn execute(self: Box<Self>, dst: PathBuf) -> Result<(), ErrorKind> {
+		self.execute_in(dst, &StdFs)
+	}
+
+	fn execute_in(self: Box<Self>, dst: PathBuf, fs: &dyn Fs) -> Result<(), ErrorKind> {
+		if !self.force && same_contents(fs, &self.src, &dst)? {
+			debug!("skipped (unchanged)");
+			return Ok(());
+		}
+
+		Box::new(self.src).execute_in(dst, fs)
+	}
+
+	fn execute_to(self: Box<Self>, sink: Sink) -> Result<(), ErrorKind> {
+		match sink {
+			Sink::File(dst) => self.execute(dst),
+			sink => Box::new(self.src).execute_to(sink),
+		}
+	}
+
+	fn inputs(&self) -> Vec<PathBuf> {
+		vec![self.src.clone()]
+	}
+
+	fn force(&mut self) {
+		self.force = true;
+	}
+}
+
+/// whether two files have the same contents, comparing sizes first
+///
+/// if `b` doesn't exist, they aren't the same
+fn same_contents(fs: &dyn Fs, a: &Path, b: &Path) -> Result<bool, ErrorKind> {
+	let b_len = match fs.metadata(b) {
+		Ok(metadata) => metadata.len,
+		Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+		Err(err) => return Err(ErrorKind::Io(err)),
+	};
+
+	if fs.metadata(a)?.len != b_len {
+		return Ok(false);
+	}
+
+	let mut a = io::BufReader::new(fs.open(a)?);
+	let mut b = io::BufReader::new(fs.open(b)?);
+
+	loop {
+		let a_chunk = a.fill_buf()?;
+		let b_chunk = b.fill_buf()?;
+
+		let len = a_chunk.len().min(b_chunk.len());
+		if len == 0 {
+			return Ok(a_chunk.is_empty() && b_chunk.is_empty());
+		}
+		if a_chunk[..len] != b_chunk[..len] {
+			return Ok(false);
+		}
+
+		a.consume(len);
+		b.consume(len);
+	}
+}
+
+/// where a transformation writes its output (see [`PlannedTransformation::execute_to`])
+#[derive(Clone, Debug)]
+pub enum Sink {
+	/// a file, which is created or truncated
+	File(PathBuf),
+	/// standard output
+	Stdout,
+	/// an in-memory buffer, which is appended to
+	Buffer(Arc<Mutex<Vec<u8>>>),
+}
+
+impl Sink {
+	/// the path of the file being written to, if this is a [`Sink::File`]
+	#[must_use]
+	pub fn path(&self) -> Option<&Path> {
+		match self {
+			Self::File(path) => Some(path),
+			Self::Stdout | Self::Buffer(_) => None,
+		}
+	}
+
+	/// open the sink for writing
+	pub fn open(&self) -> Result<SinkWriter<'_>, ErrorKind> {
+		Ok(match self {
+			Self::File(path) => SinkWriter::File(fs::File::create(path)?),
+			Self::Stdout => SinkWriter::Stdout(io::stdout().lock()),
+			Self::Buffer(buffer) => {
+				SinkWriter::Buffer(buffer.lock().unwrap_or_else(PoisonError::into_inner))
+			}
+		})
+	}
+
+	/// write all of `bytes` to the sink
+	pub fn write_all(&self, bytes: &[u8]) -> Result<(), ErrorKind> {
+		self.open()?.write_all(bytes)?;
+		Ok(())
+	}
+}
+
+/// an open [`Sink`]
+#[derive(Debug)]
+pub enum SinkWriter<'a> {
+	/// a file
+	File(fs::File),
+	/// standard output
+	Stdout(io::StdoutLock<'static>),
+	/// an in-memory buffer
+	Buffer(MutexGuard<'a, Vec<u8>>),
+}
+
+impl Write for SinkWriter<'_> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		match self {
+			Self::File(file) => file.write(buf),
+			Self::Stdout(stdout) => stdout.write(buf),
+			Self::Buffer(buffer) => buffer.write(buf),
+		}
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		match self {
+			Self::File(file) => file.flush(),
+			Self::Stdout(stdout) => stdout.flush(),
+			Self::Buffer(buffer) => buffer.flush(),
+		}
+	}
+}
+
+/// retries a transformation when it fails with a transient io error (see [`Retry::is_transient`])
+///
+/// the transformation is cloned for each attempt, as executing consumes it
+#[derive(Clone, Debug)]
+pub struct Retry<T> {
+	/// the transformation to retry
+	pub inner: T,
+	/// how many times to attempt the transformation in total
+	pub attempts: u32,
+	/// how long to wait after the first failure, doubled after each further failure
+	pub backoff: Duration,
+}
+
+impl<T> Retry<T> {
+	/// whether an error is likely to go away if the transformation is attempted again
+	///
+	/// this is conservative, only io errors that indicate a temporary condition are considered transient
+	#[must_use]
+	pub fn is_transient(err: &ErrorKind) -> bool {
+		matches!(
+			err,
+			ErrorKind::Io(err) if matches!(
+				err.kind(),
+				io::ErrorKind::Interrupted
+					| io::ErrorKind::WouldBlock
+					| io::ErrorKind::TimedOut
+					| io::ErrorKind::ResourceBusy
+			)
+		)
+	}
+}
+
+impl<T: PlannedTransformation + Clone> Retry<T> {
+	/// attempt to execute a clone of the inner transformation until it succeeds, fails with an error that isn't transient, or runs out of attempts
+	fn retry(
+		&self,
+		mut execute: impl FnMut(Box<T>) -> Result<(), ErrorKind>,
+	) -> Result<(), ErrorKind> {
+		let mut backoff = self.backoff;
+		let mut attempt = 1;
+
+		loop {
+			match execute(Box::new(self.inner.clone())) {
+				Err(err) if attempt < self.attempts && Self::is_transient(&err) => {
+					warn!(attempt, ?backoff, "transient failure, retrying: {err}");
+
+					thread::sleep(backoff);
+					backoff = backoff.saturating_mul(2);
+					attempt += 1;
+				}
+				result => return result,
+			}
+		}
+	}
+}
+
+/// executes the inner transformation, retrying on transient io errors
+impl<T: PlannedTransformation + Clone> PlannedTransformation for Retry<T> {
+	fn execute(self: Box<Self>, dst: PathBuf) -> Result<(), ErrorKind> {
+		self.execute_to(Sink::File(dst))
+	}
+
+	#[instrument(skip(self), name = "retry", level = Level::DEBUG)]
+	fn execute_to(self: Box<Self>, sink: Sink) -> Result<(), ErrorKind> {
+		self.retry(|inner| inner.execute_to(sink.clone()))
+	}
+
+	#[instrument(skip(self, fs), name = "retry", level = Level::DEBUG)]
+	fn execute_in(self: Box<Self>, dst: PathBuf, fs: &dyn Fs) -> Result<(), ErrorKind> {
+		self.retry(|inner| inner.execute_in(dst.clone(), fs))
+	}
+
+	fn is_noop(&self) -> bool {
+		self.inner.is_noop()
+	}
+
+	fn outputs(&self, dst: &Path) -> Vec<PathBuf> {
+		self.inner.outputs(dst)
+	}
+
+	fn inputs(&self) -> Vec<PathBuf> {
+		self.inner.inputs()
+	}
+
+	fn force(&mut self) {
+		self.inner.force();
+	}
+
+	fn fingerprint(&self, state: &mut dyn Hasher) {
+		self.inner.fingerprint(state);
+	}
+}
+
+/// a plan to transform a file
+///
+/// plans usually come from rules, but may also be made directly with [`Plan::new`] for outputs computed outside of any rule (ex: a search index, or a redirects file),
+/// and added to planned ones before they are [`execute`]d, so that they get the same directory creation and error handling
+#[derive(Debug)]
+pub struct Plan {
+	/// the destination file
+	pub dst: PathBuf,
+	/// the plan data produced by the `plan` function
+	pub data: Box<dyn PlannedTransformation>,
+}
+
+impl Plan {
+	/// a plan to write `data` to `dst`, without a rule
+	///
+	/// ex: `plans.push(Plan::new("deploy/robots.txt", String::from("User-agent: *")))`
+	pub fn new(dst: impl Into<PathBuf>, data: impl PlannedTransformation) -> Self {
+		Self {
+			dst: dst.into(),
+			data: Box::new(data),
+		}
+	}
+
+	/// every path this plan writes to when executed (see [`PlannedTransformation::outputs`])
+	#[must_use]
+	pub fn outputs(&self) -> Vec<PathBuf> {
+		self.data.outputs(&self.dst)
+	}
+
+	/// every path this plan reads from when executed (see [`PlannedTransformation::inputs`])
+	#[must_use]
+	pub fn inputs(&self) -> Vec<PathBuf> {
+		self.data.inputs()
+	}
+
+	/// a stable identity for this plan, for diffing builds (ex: to only upload outputs whose identity changed)
+	///
+	/// hashes `dst`, the path and contents of every input, and whatever the transformation feeds to [`PlannedTransformation::fingerprint`] (ex: the content of a [`String`], or the globals of a template),
+	/// so it is the same across runs on the same inputs
+	pub fn identity(&self) -> Result<String, ErrorKind> {
+		let mut state = StableHasher::default();
+
+		let hash_path = |state: &mut StableHasher, path: &Path| {
+			hash_bytes(state, path.as_os_str().as_encoded_bytes());
+		};
+
+		hash_path(&mut state, &self.dst);
+
+		let mut inputs = self.inputs();
+		inputs.sort();
+		for input in inputs {
+			hash_path(&mut state, &input);
+
+			if input.is_file() {
+				let content = fs::read(&input)?;
+				state.write_usize(content.len());
+				state.write(&content);
+			}
+		}
+
+		self.data.fingerprint(&mut state);
+
+		Ok(format!("{:016x}", state.finish()))
+	}
+}
+
+/// equivalent to `execute(plan(rules)?)`
+pub fn run(rules: &mut [Rule<'_>]) -> Result<(), ErrorKind> {
+	execute(plan(rules)?)
+}
+
+/// equivalent to `execute_with(plan_with(rules, options)?, options)`
+pub fn run_with(rules: &mut [Rule<'_>], options: &BuildOptions<'_>) -> Result<(), ErrorKind> {
+	execute_with(plan_with(rules, options)?, options)
+}
+
+/// plan and execute some transformations without stopping at the first error, returning every output that was written and every error
+///
+/// sources and plans that fail are skipped (ex: a page with broken frontmatter doesn't stop the rest of the site), so tooling can present a full summary of the build, with successes and failures together
+///
+/// [`run`] is still the fail-fast equivalent
+pub fn try_run(rules: &mut [Rule<'_>]) -> (Vec<PathBuf>, Vec<ErrorKind>) {
+	try_run_with(rules, &BuildOptions::default())
+}
+
+/// plan and execute some transformations, with options, without stopping at the first error
+///
+/// see [`try_run`], a build cancelled with [`BuildOptions::cancel`] still stops, with [`ErrorKind::Cancelled`] as the last error
+#[instrument(skip(rules))]
+pub fn try_run_with(
+	rules: &mut [Rule<'_>],
+	options: &BuildOptions<'_>,
+) -> (Vec<PathBuf>, Vec<ErrorKind>) {
+	let mut errors = Vec::new();
+	let mut outputs = Vec::new();
+
+	let plans = match plan_reporting(
+		rules,
+		options,
+		&mut BuildReport::default(),
+		&mut Failures::Collect(&mut errors),
+	) {
+		Ok(plans) => plans,
+		Err(err) => {
+			errors.push(err);
+			return (outputs, errors);
+		}
+	};
+
+	for plan in plans {
+		if let Err(err) = options.check_cancelled() {
+			errors.push(err);
+			break;
+		}
+
+		let dst = plan.dst.clone();
+		let planned = if plan.data.is_noop() {
+			Vec::new()
+		} else {
+			plan.data.outputs(&plan.dst)
+		};
+
+		match execute_one(plan) {
+			Ok(()) => outputs.extend(planned),
+			Err(err) => {
+				error!(?dst, %err, "failed");
+				errors.push(err);
+			}
+		}
+	}
+
+	(outputs, errors)
+}
+
+/// plan and execute some transformations into a staging directory next to `output_root`, then swap it into place once every plan has succeeded
+///
+/// so a failed build never leaves `output_root` half-updated, in which case the staging directory is removed and `output_root` is untouched
+///
+/// every `dst` must be inside of `output_root` (see [`BuildOptions::output_root`]), and the previous contents of `output_root` are replaced entirely, including files no rule produces
+///
+/// transformations only see their staged output path while executing, so those that read other outputs from `output_root` (ex: an integrity manifest) see the previous build
+#[instrument(skip(rules))]
+pub fn run_atomic(rules: &mut [Rule<'_>], output_root: &Path) -> Result<(), ErrorKind> {
+	let output_root = normalize_lexically(output_root);
+	let name = output_root.file_name().ok_or_else(|| {
+		io::Error::new(io::ErrorKind::InvalidInput, "output root has no file name")
+	})?;
+	let sibling = |suffix: &str| {
+		let mut sibling = OsString::from(".");
+		sibling.push(name);
+		sibling.push(suffix);
+		output_root.with_file_name(sibling)
+	};
+	let staging = sibling(".staging");
+	let previous = sibling(".previous");
+
+	let plans = plan_with(
+		rules,
+		&BuildOptions {
+			output_root: Some(&output_root),
+			..BuildOptions::default()
+		},
+	)?;
+
+	// leftovers from an interrupted build
+	if staging.exists() {
+		fs::remove_dir_all(&staging)?;
+	}
+	if previous.exists() {
+		if output_root.exists() {
+			fs::remove_dir_all(&previous)?;
+		} else {
+			// interrupted between the two renames, so this is the only copy of the site
+			warn!(?previous, "restoring output root from an interrupted build");
+			fs::rename(&previous, &output_root)?;
+		}
+	}
+	fs::create_dir_all(&staging)?;
+
+	let result = plans.into_iter().try_for_each(|plan| {
+		let relative = normalize_lexically(&plan.dst)
+			.strip_prefix(&output_root)
+			.map_err(|_| ErrorKind::PathEscape {
+				src: plan.data.inputs().into_iter().next().unwrap_or_default(),
+				dst: plan.dst.clone(),
+				output_root: output_root.clone(),
+			})?
+			.to_path_buf();
+
+		execute_one(Plan {
+			dst: staging.join(relative),
+			data: plan.data,
+		})
+	});
+
+	if let Err(err) = result {
+		if let Err(err) = fs::remove_dir_all(&staging) {
+			warn!(?staging, %err, "failed to remove staging directory");
+		}
+
+		return Err(err);
+	}
+
+	if output_root.exists() {
+		fs::rename(&output_root, &previous)?;
+	}
+	if let Err(err) = fs::rename(&staging, &output_root) {
+		if previous.exists() {
+			if let Err(err) = fs::rename(&previous, &output_root) {
+				warn!(?previous, %err, "failed to restore output root");
+			}
+		}
+
+		return Err(err.into());
+	}
+	if previous.exists() {
+		fs::remove_dir_all(&previous)?;
+	}
+
+	Ok(())
+}
+
+/// plan some transformations
+pub fn plan(rules: &mut [Rule<'_>]) -> Result<Vec<Plan>, ErrorKind> {
+	plan_with(rules, &BuildOptions::default())
+}
+
+/// plan some transformations, with options
+pub fn plan_with(
+	rules: &mut [Rule<'_>],
+	options: &BuildOptions<'_>,
+) -> Result<Vec<Plan>, ErrorKind> {
+	plan_reporting(
+		rules,
+		options,
+		&mut BuildReport::default(),
+		&mut Failures::Fail,
+	)
+}
+
+/// plan transformations for a known list of paths, rather than globbing for them
+///
+/// each path is planned by the first selected rule that has an `include` matching it (and no `exclude` matching it), or every such rule if [`BuildOptions::allow_multiple_rules`] is set
+///
+/// paths that no rule matches are ignored
+///
+/// paths must be in the same form the `include` patterns expect (ex: `src/page.doll`, relative to the current working directory)
+///
+/// this never walks the tree, so it is much faster when only a few files are known to have changed (ex: from a file watcher)
+#[instrument(skip(rules))]
+pub fn plan_files(
+	rules: &mut [Rule<'_>],
+	files: &[PathBuf],
+	options: &BuildOptions<'_>,
+) -> Result<Vec<Plan>, ErrorKind> {
+	let mut plans = Vec::new();
+	let mut seen = HashSet::new();
+	let mut report = BuildReport::default();
+
+	let context = options.build_context();
+	let options = &BuildOptions {
+		context: Some(&context),
+		..*options
+	};
+
+	options.clear_caches();
+
+	let active = rules
+		.iter()
+		.map(|rule| rule.is_selected(options.select) && rule.is_enabled())
+		.collect::<Vec<_>>();
+
+	for (rule_index, rule) in rules.iter().enumerate() {
+		if active[rule_index] {
+			validate_dst(rule_index, rule)?;
+		}
+	}
+
+	for file in files {
+		options.check_cancelled()?;
+
+		if !seen.insert(file) {
+			continue;
+		}
+
+		let mut planned = false;
+
+		'rules: for (rule_index, rule) in rules.iter_mut().enumerate() {
+			if planned && !options.allow_multiple_rules {
+				break;
+			}
+
+			if !active[rule_index] {
+				continue;
+			}
+
+			// only this one file is ever planned, so this only stops a rule from planning it twice
+			let mut visited = HashSet::new();
+
+			// `include` is relative to `src_root`, so files outside of it never match
+			let relative = match rule.src_root {
+				Some(src_root) => file.strip_prefix(src_root).ok(),
+				None => Some(file.as_path()),
+			};
+
+			for (include_index, include) in rule.include.iter().enumerate() {
+				let Some(entry) = relative.and_then(|relative| {
+					include.captures_path_with(relative, rule.match_options(include_index))
+				}) else {
+					continue;
+				};
+				let captures = entry_captures(&entry, rule.dst)?;
+
+				if let Some(entry_plans) = plan_entry(
+					rule_index,
+					rule,
+					options,
+					file,
+					captures,
+					&[],
+					&mut visited,
+					&mut report,
+				)? {
+					plans.extend(entry_plans);
+					planned = true;
+					continue 'rules;
+				}
+			}
+
+			#[cfg(feature = "regex")]
+			for include in rule.include_regex {
+				let Some(captures) = include.captures(file)? else {
+					continue;
+				};
+				let names = include.names().collect::<Vec<_>>();
+
+				if let Some(entry_plans) = plan_entry(
+					rule_index,
+					rule,
+					options,
+					file,
+					captures,
+					&names,
+					&mut visited,
+					&mut report,
+				)? {
+					plans.extend(entry_plans);
+					planned = true;
+					continue 'rules;
+				}
+			}
+		}
+
+		if !planned {
+			debug!(?file, "skipped (not planned by any rule)");
+		}
+	}
+
+	Ok(plans)
+}
+
+/// plan and execute some transformations, with options, and summarize what happened
+///
+/// this is useful for printing a summary of the build, without needing a tracing subscriber
+pub fn run_reporting(
+	rules: &mut [Rule<'_>],
+	options: &BuildOptions<'_>,
+) -> Result<BuildReport, ErrorKind> {
+	let start = Instant::now();
+	let mut report = BuildReport::default();
+
+	let plans = plan_reporting(rules, options, &mut report, &mut Failures::Fail)?;
+	report.planned = plans.len();
+
+	for plan in plans {
+		options.check_cancelled()?;
+
+		let outputs = if plan.data.is_noop() {
+			Vec::new()
+		} else {
+			report.written += 1;
+			plan.data.outputs(&plan.dst)
+		};
+
+		execute_one(plan)?;
+
+		for output in outputs {
+			// transformations may list outputs they didn't end up writing
+			if let Ok(metadata) = fs::metadata(&output) {
+				report.bytes_written += metadata.len();
+				report.output_sizes.push((output, metadata.len()));
+			}
+		}
+	}
+
+	report.output_sizes.sort_by(|(_, a), (_, b)| b.cmp(a));
+	report.duration = start.elapsed();
+
+	Ok(report)
+}
+
+/// a summary of a build, produced by [`run_reporting`]
+#[derive(Clone, Default, Debug)]
+pub struct BuildReport {
+	/// how many plans were produced
+	pub planned: usize,
+	/// how many plans wrote to their destination (every plan that isn't a [`noop`])
+	pub written: usize,
+	/// how many matched paths were skipped while planning (not a file, already visited, excluded, or empty)
+	pub skipped: usize,
+	/// how long planning and executing took
+	pub duration: Duration,
+	/// how many bytes were written, across every output
+	pub bytes_written: u64,
+	/// every output that was written and its size in bytes, largest first
+	pub output_sizes: Vec<(PathBuf, u64)>,
+}
+
+impl BuildReport {
+	/// the `n` largest outputs and their sizes in bytes, largest first
+	#[must_use]
+	pub fn largest(&self, n: usize) -> &[(PathBuf, u64)] {
+		&self.output_sizes[..n.min(self.output_sizes.len())]
+	}
+
+	/// every output larger than `budget` bytes (ex: to warn about huge pages or unoptimized assets), largest first
+	pub fn over_budget(&self, budget: u64) -> impl Iterator<Item = &(PathBuf, u64)> {
+		self.output_sizes
+			.iter()
+			.take_while(move |(_, size)| *size > budget)
+	}
+}
+
+/// how `include` patterns match paths, unless overridden by [`Rule::include_options`]
+///
+/// case-sensitive, `*` does not match `/`, and `*` matches a leading `.`
+pub const MATCH_OPTIONS: MatchOptions = MatchOptions {
+	case_sensitive: true,
+	require_literal_leading_dot: false,
+	require_literal_separator: true,
+};
+
+/// what happens to errors while planning
+enum Failures<'e> {
+	/// the first error is returned
+	Fail,
+	/// errors are collected, skipping whatever failed (see [`try_run`])
+	Collect(&'e mut Vec<ErrorKind>),
+}
+
+impl Failures<'_> {
+	/// return the error, or collect it and return `None`
+	fn handle<T>(&mut self, result: Result<T, ErrorKind>) -> Result<Option<T>, ErrorKind> {
+		match (result, self) {
+			(Ok(value), _) => Ok(Some(value)),
+			(Err(err), Self::Fail) => Err(err),
+			(Err(err), Self::Collect(errors)) => {
+				error!(%err, "failed");
+				errors.push(err);
+				Ok(None)
+			}
+		}
+	}
+}
+
+#[instrument(name = "plan", skip(rules, report, failures))]
+fn plan_reporting(
+	rules: &mut [Rule<'_>],
+	options: &BuildOptions<'_>,
+	report: &mut BuildReport,
+	failures: &mut Failures<'_>,
+) -> Result<Vec<Plan>, ErrorKind> {
+	let mut plans = Vec::new();
+	let mut visited = HashSet::new();
+
+	let context = options.build_context();
+	let options = &BuildOptions {
+		context: Some(&context),
+		..*options
+	};
+
+	options.clear_caches();
+
+	for (rule_index, rule) in rules.iter_mut().enumerate() {
+		let _span = debug_span!("rule", rule_index, ?rule).entered();
+
+		if !rule.is_selected(options.select) {
+			debug!("skipped (not selected)");
+			continue;
+		}
+
+		if !rule.is_enabled() {
+			debug!("skipped (`when` is false)");
+			continue;
+		}
+
+		if failures.handle(validate_dst(rule_index, rule))?.is_none() {
+			continue;
+		}
+
+		if options.allow_multiple_rules {
+			visited.clear();
+		}
+
+		let mut matched = 0_usize;
+		// sources for `plan_parallel`, which are planned together once every include has matched
+		let parallel = rule.plan_parallel.is_some() && rule.plan_many.is_none();
+		let mut pending = Vec::new();
+
+		for (include_index, include) in rule.include.iter().enumerate() {
+			let _span =
+				debug_span!("include", include_index, include = include.to_string()).entered();
+
+			let Some((pattern, prefix)) = failures.handle(rule.include_glob(include))? else {
+				continue;
+			};
+
+			let entries = glob_with(&pattern, rule.match_options(include_index)).map_err(|err| {
+				ErrorKind::Pattern {
+					rule_index,
+					include_index,
+					label: vec![::miette::LabeledSpan::new_primary_with_span(
+						Some(err.msg.to_string()),
+						SourceSpan::new(err.pos.saturating_sub(prefix).into(), 1),
+					)],
+					src: NamedSource::new(
+						format!("rules[{rule_index}].include[{include_index}]"),
+						include.to_string(),
+					),
+				}
+			});
+			let Some(entries) = failures.handle(entries)? else {
+				continue;
+			};
+
+			for entry in entries {
+				options.check_cancelled()?;
+
+				let Some(entry) = failures.handle(entry.map_err(ErrorKind::from))? else {
+					continue;
+				};
+				matched += 1;
+
+				let source = entry_captures(&entry, rule.dst).and_then(|captures| {
+					prepare_entry(
+						rule,
+						options,
+						entry.path(),
+						captures,
+						&[],
+						&mut visited,
+						report,
+					)
+				});
+
+				if let Some(source) = failures.handle(source)?.flatten() {
+					if parallel {
+						pending.push(source);
+					} else if let Some(source_plans) =
+						failures.handle(plan_source(rule_index, rule, options, source))?
+					{
+						plans.extend(source_plans);
+					}
+				}
+			}
+		}
+
+		#[cfg(feature = "regex")]
+		for (include_index, include) in rule.include_regex.iter().enumerate() {
+			let _span = debug_span!(
+				"include_regex",
+				include_index,
+				include = include.regex.as_str()
+			)
+			.entered();
+
+			let names = include.names().collect::<Vec<_>>();
+
+			let Some(matches) = failures.handle(include.matches())? else {
+				continue;
+			};
+
+			for (src_file, captures) in matches {
+				options.check_cancelled()?;
+				matched += 1;
+
+				let source = prepare_entry(
+					rule,
+					options,
+					&src_file,
+					captures,
+					&names,
+					&mut visited,
+					report,
+				);
+
+				if let Some(source) = failures.handle(source)?.flatten() {
+					if parallel {
+						pending.push(source);
+					} else if let Some(source_plans) =
+						failures.handle(plan_source(rule_index, rule, options, source))?
+					{
+						plans.extend(source_plans);
+					}
+				}
+			}
+		}
+
+		if !pending.is_empty() {
+			plans.extend(plan_in_parallel(
+				rule_index, rule, options, pending, failures,
+			)?);
+		}
+
+		if matched == 0 {
+			match options.empty_rules {
+				EmptyRules::Allow => {}
+				EmptyRules::Warn => warn!("no paths matched"),
+				EmptyRules::Error => {
+					failures.handle(Err::<(), _>(ErrorKind::EmptyRule { rule_index }))?;
+				}
+			}
+		}
+	}
+
+	Ok(plans)
+}
+
+/// pull the captures of a glob match out into a vec
+///
+/// captures only need to be utf8 if `dst` refers to them, others are converted lossily (replacing invalid sequences with `U+FFFD`), so that a non-utf8 part of a path that isn't used doesn't fail the build
+fn entry_captures(entry: &Entry, dst: &str) -> Result<Vec<String>, ErrorKind> {
+	// skip 0, which is just the entire match
+	convert_captures((1..).map_while(|i| entry.group(i)), dst)
+}
+
+/// convert each capture, see [`entry_captures`]
+fn convert_captures<'a>(
+	groups: impl IntoIterator<Item = &'a OsStr>,
+	dst: &str,
+) -> Result<Vec<String>, ErrorKind> {
+	let mut captures = Vec::new();
+
+	for capture in groups {
+		let index = captures.len();
+
+		captures.push(match capture.to_str() {
+			Some(capture) => capture.to_string(),
+			None if refers_to_capture(dst, index)? => {
+				return Err(ErrorKind::NonUTF8PathCharacters);
+			}
+			None => {
+				debug!(
+					index,
+					?capture,
+					"converted non-utf8 capture lossily, as `dst` doesn't refer to it"
+				);
+				capture.to_string_lossy().into_owned()
+			}
+		});
+	}
+
+	Ok(captures)
+}
+
+/// plan a single path matched by one of a rule's includes, unless it should be skipped
+///
+/// `names` are the names of each capture, if the include names them
+#[allow(
+	clippy::too_many_arguments,
+	reason = "this is the shared body of each planning loop"
+)]
+fn plan_entry(
+	rule_index: usize,
+	rule: &mut Rule<'_>,
+	options: &BuildOptions<'_>,
+	src_file: &Path,
+	captures: Vec<String>,
+	names: &[Option<&str>],
+	visited: &mut HashSet<PathBuf>,
+	report: &mut BuildReport,
+) -> Result<Option<Vec<Plan>>, ErrorKind> {
+	match prepare_entry(rule, options, src_file, captures, names, visited, report)? {
+		Some(source) => plan_source(rule_index, rule, options, source).map(Some),
+		None => Ok(None),
+	}
+}
+
+/// a source that should be planned, with where its output goes
+#[derive(Debug)]
+struct Source {
+	src: PathBuf,
+	captures: Vec<String>,
+	dst: PathBuf,
+}
+
+/// check a single path matched by one of a rule's includes, returning it if it should be planned, and marking it as visited
+///
+/// `names` are the names of each capture, if the include names them
+fn prepare_entry(
+	rule: &Rule<'_>,
+	options: &BuildOptions<'_>,
+	src_file: &Path,
+	captures: Vec<String>,
+	names: &[Option<&str>],
+	visited: &mut HashSet<PathBuf>,
+	report: &mut BuildReport,
+) -> Result<Option<Source>, ErrorKind> {
+	let span = info_span!(
+		"check file",
+		src = %src_file.display(),
+		dst = ::tracing::field::Empty
+	)
+	.entered();
+
+	// make sure it isnt excluded and that it hasn't been visited yet, before `dst` is formatted and checked, so that skipped paths can't fail the build
+
+	if !rule.matches.accepts(src_file) {
+		error!(matches = ?rule.matches, "skipped (wrong kind of path)");
+		report.skipped += 1;
+		return Ok(None);
+	}
+
+	if visited.contains(src_file) {
+		error!("skipped (already visited)");
+		report.skipped += 1;
+		return Ok(None);
+	}
+
+	if rule.is_excluded(src_file, options.exclude) {
+		error!("skipped (matched ignore)");
+		report.skipped += 1;
+		return Ok(None);
+	}
+
+	if options.empty_sources != EmptySources::Plan
+		&& src_file.is_file()
+		&& src_file.metadata()?.len() == 0
+	{
+		if options.empty_sources == EmptySources::Error {
+			return Err(ErrorKind::EmptySource(src_file.to_path_buf()));
+		}
+
+		error!("skipped (empty)");
+		report.skipped += 1;
+		return Ok(None);
+	}
+
+	let dst_file = native_separators(&format_named(rule.dst, &captures, names)?);
+	span.record("dst", dst_file.to_str().unwrap());
+
+	check_escape(src_file, &dst_file, options)?;
+
+	visited.insert(src_file.to_path_buf());
+
+	Ok(Some(Source {
+		src: src_file.to_path_buf(),
+		captures,
+		dst: dst_file,
+	}))
+}
+
+/// plan a source with a rule's plan closure, then its `post` closure
+fn plan_source(
+	rule_index: usize,
+	rule: &mut Rule<'_>,
+	options: &BuildOptions<'_>,
+	source: Source,
+) -> Result<Vec<Plan>, ErrorKind> {
+	let Source { src, captures, dst } = source;
+
+	let _span = info_span!(
+		"plan file",
+		src = %src.display(),
+		dst = dst.to_str().unwrap()
+	)
+	.entered();
+
+	let in_rule = |err| ErrorKind::InRule {
+		rule_index,
+		src: src.clone(),
+		source: Box::new(err),
+	};
+
+	let plans = if let Some(plan_many) = &mut rule.plan_many {
+		let plans = plan_many(src.clone(), captures, &dst, options.context()).map_err(in_rule)?;
+		for plan in &plans {
+			check_escape(&src, &plan.dst, options)?;
+		}
+		plans
+	} else if let Some(plan_parallel) = rule.plan_parallel {
+		vec![Plan {
+			data: plan_parallel(src.clone(), captures, &dst, options.context()).map_err(in_rule)?,
+			dst,
+		}]
+	} else {
+		vec![Plan {
+			data: (rule.plan)(src.clone(), captures, &dst, options.context()).map_err(in_rule)?,
+			dst,
+		}]
+	};
+
+	Ok(post_process(rule, options, plans))
+}
+
+/// plan sources with a rule's `plan_parallel` closure on up to [`BuildOptions::jobs`] threads, then its `post` closure, keeping the order of `sources`
+///
+/// if several sources fail, the error is that of the first, unless errors are collected
+fn plan_in_parallel(
+	rule_index: usize,
+	rule: &mut Rule<'_>,
+	options: &BuildOptions<'_>,
+	sources: Vec<Source>,
+	failures: &mut Failures<'_>,
+) -> Result<Vec<Plan>, ErrorKind> {
+	let plan_parallel = rule
+		.plan_parallel
+		.expect("only called for rules with `plan_parallel`");
+	let context = options.context();
+	let next = AtomicUsize::new(0);
+
+	let _span = debug_span!("plan in parallel", sources = sources.len()).entered();
+
+	let mut results = thread::scope(|scope| {
+		let workers = (0..options.jobs().min(sources.len()))
+			.map(|_| {
+				scope.spawn(|| {
+					let mut results = Vec::new();
+
+					// each worker takes the next unplanned source, so slow sources don't hold up the rest
+					loop {
+						if options.check_cancelled().is_err() {
+							break;
+						}
+
+						let index = next.fetch_add(1, Ordering::Relaxed);
+						let Some(Source { src, captures, dst }) = sources.get(index) else {
+							break;
+						};
+
+						let _span = info_span!(
+							"plan file",
+							src = %src.display(),
+							dst = dst.to_str().unwrap()
+						)
+						.entered();
+
+						results.push((
+							index,
+							plan_parallel(src.clone(), captures.clone(), dst, context),
+						));
+					}
+
+					results
+				})
+			})
+			.collect::<Vec<_>>();
+
+		workers
+			.into_iter()
+			.flat_map(|worker| {
+				worker
+					.join()
+					.unwrap_or_else(|panic| ::std::panic::resume_unwind(panic))
+			})
+			.collect::<Vec<_>>()
+	});
+	// workers stop early once cancelled, so some sources may not have been planned
+	options.check_cancelled()?;
+	results.sort_unstable_by_key(|(index, _)| *index);
+
+	let mut plans = Vec::with_capacity(sources.len());
+	for (source, (_, data)) in sources.into_iter().zip(results) {
+		let data = data.map_err(|err| ErrorKind::InRule {
+			rule_index,
+			src: source.src,
+			source: Box::new(err),
+		});
+
+		if let Some(data) = failures.handle(data)? {
+			plans.push(Plan {
+				data,
+				dst: source.dst,
+			});
+		}
+	}
+
+	Ok(post_process(rule, options, plans))
+}
+
+/// apply a rule's `post` closure to every plan, if it has one
+///
+/// if [`BuildOptions::force`] is set, every plan is then forced (see [`PlannedTransformation::force`]), including whatever `post` wrapped it in
+fn post_process(
+	rule: &mut Rule<'_>,
+	options: &BuildOptions<'_>,
+	mut plans: Vec<Plan>,
+) -> Vec<Plan> {
+	if let Some(post) = &mut rule.post {
+		for plan in &mut plans {
+			let data = ::core::mem::replace(&mut plan.data, Box::new(()));
+			plan.data = post(data);
+		}
+	}
+
+	if options.force {
+		for plan in &mut plans {
+			plan.data.force();
+		}
+	}
+
+	plans
+}
+
+/// make sure `dst` is inside of [`BuildOptions::output_root`], if it is set
+fn check_escape(
+	src_file: &Path,
+	dst_file: &Path,
+	options: &BuildOptions<'_>,
+) -> Result<(), ErrorKind> {
+	if let Some(output_root) = options.output_root {
+		if !normalize_lexically(dst_file).starts_with(normalize_lexically(output_root)) {
+			return Err(ErrorKind::PathEscape {
+				src: src_file.to_path_buf(),
+				dst: dst_file.to_path_buf(),
+				output_root: output_root.to_path_buf(),
+			});
+		}
+	}
+
+	Ok(())
+}
+
+/// execute some plans, in order
+///
+/// plans may come from [`plan`], be made with [`Plan::new`], or both, so this is where outputs from outside of the rule system are added
+#[instrument(skip(plans))]
+pub fn execute(plans: Vec<Plan>) -> Result<(), ErrorKind> {
+	for plan in plans {
+		execute_one(plan)?;
+	}
+
+	Ok(())
+}
+
+/// execute some plans, in order, stopping once [`BuildOptions::cancel`] is set
+///
+/// only [`BuildOptions::cancel`] is used
+#[instrument(skip(plans))]
+pub fn execute_with(plans: Vec<Plan>, options: &BuildOptions<'_>) -> Result<(), ErrorKind> {
+	for plan in plans {
+		options.check_cancelled()?;
+		execute_one(plan)?;
+	}
+
+	Ok(())
+}
+
+/// execute some plans, continuing past any that fail
+///
+/// every plan is attempted, and any failures are returned together as [`ErrorKind::Multiple`], including failures to create the directory of an output (ex: [`ErrorKind::FileInOutputPath`])
+#[instrument(skip(plans))]
+pub fn execute_lenient(plans: Vec<Plan>) -> Result<(), ErrorKind> {
+	let mut errors = Vec::new();
+
+	for plan in plans {
+		let dst = plan.dst.clone();
+		if let Err(err) = execute_one(plan) {
+			error!(?dst, %err, "failed");
+			errors.push(err);
+		}
+	}
+
+	if errors.is_empty() {
+		Ok(())
+	} else {
+		Err(ErrorKind::Multiple(errors))
+	}
+}
+
+/// execute some plans, recording each one that succeeds in a checkpoint file, so that an interrupted build can be resumed without redoing them
+///
+/// - `checkpoint` - the checkpoint file (ex: `target/dollgen/checkpoint`)
+/// - `build_id` - identifies the logical build (ex: a commit hash), a checkpoint left by a different build is discarded
+///
+/// plans whose `dst` the checkpoint records are skipped, and the checkpoint is removed once every plan has succeeded
+///
+/// unlike comparing modification times, this only skips work that was done by the same logical build, so it is safe when sources changed in between
+#[instrument(skip(plans))]
+pub fn execute_resumable(
+	plans: Vec<Plan>,
+	checkpoint: &Path,
+	build_id: &str,
+) -> Result<(), ErrorKind> {
+	// the first line is the build id, the rest are the destinations that were written
+	let completed = match fs::read_to_string(checkpoint) {
+		Ok(contents) => {
+			let mut lines = contents.lines();
+			if lines.next() == Some(build_id) {
+				lines.map(PathBuf::from).collect()
+			} else {
+				debug!("discarding checkpoint from a different build");
+				HashSet::new()
+			}
+		}
+		Err(err) if err.kind() == io::ErrorKind::NotFound => HashSet::new(),
+		Err(err) => return Err(err.into()),
+	};
+
+	if let Some(parent) = checkpoint.parent() {
+		fs::create_dir_all(parent)?;
+	}
+
+	let mut file = if completed.is_empty() {
+		let mut file = fs::File::create(checkpoint)?;
+		writeln!(file, "{build_id}")?;
+		file
+	} else {
+		debug!(completed = completed.len(), "resuming");
+		fs::OpenOptions::new().append(true).open(checkpoint)?
+	};
 
-#[cfg(feature = "scss")]
-pub mod scss;
+	for plan in plans {
+		if completed.contains(&plan.dst) {
+			debug!(dst = ?plan.dst, "skipped (completed before interruption)");
+			continue;
+		}
 
-#[cfg(feature = "wasm")]
-pub mod wasm;
+		let dst = plan
+			.dst
+			.to_str()
+			.ok_or(ErrorKind::NonUTF8PathCharacters)?
+			.to_string();
+		execute_one(plan)?;
+		writeln!(file, "{dst}")?;
+	}
 
-pub mod lang;
+	drop(file);
+	fs::remove_file(checkpoint)?;
 
-mod util;
+	Ok(())
+}
 
-/// the core of dollgen, defines a list of globs to include, a list of globs to exclude, how to transform the file, and where to emit it to
-#[::tyfling::debug(
-	"+ {:?}\n- {:?}\n> \"{dst}\"",
-	include.iter().map(ToString::to_string).collect::<Vec<_>>(),
-	exclude.iter().map(ToString::to_string).collect::<Vec<_>>()
-)]
-pub struct Rule<'a> {
-	/// which files to include
-	///
-	/// may capture parts of the path (ex: `src/**/*.doll`)
-	pub include: &'a [Pattern],
-	/// which files to exclude
-	pub exclude: &'a [Pattern],
-	/// where output files should be emitted
-	///
-	/// format specifiers like `{0}` pull from the captures of whatever `include` glob matched (ex: `dist/{0}/{1}.html`)
-	pub dst: &'static str,
-	/// plan a transformation
-	///
-	/// takes the input path (matched by an `include`), and the captures from the `include` that matched
-	///
-	/// returns plan data to be passed into `execute`
-	pub plan: &'a mut dyn FnMut(
-		PathBuf,
-		Vec<String>,
-	) -> Result<Box<dyn PlannedTransformation>, ErrorKind>,
+/// execute some plans against a filesystem other than the real one (ex: [`MemoryFs`](filesystem::MemoryFs) to test transformations)
+///
+/// each plan is executed with [`PlannedTransformation::execute_in`], which by default writes to the [`Sink`] that the filesystem provides for its destination (see [`Fs::sink`])
+#[instrument(skip(plans, fs))]
+pub fn execute_in(plans: Vec<Plan>, fs: &dyn Fs) -> Result<(), ErrorKind> {
+	for plan in plans {
+		execute_one_in(plan, fs)?;
+	}
+
+	Ok(())
 }
 
-/// a planned transformation that can be `execute`d
+/// plan and execute a single source file, returning its output instead of writing it (ex: to render pages on request in a preview server)
 ///
-/// this trait can be downcasted to access the internal plan (this is useful for those that want to plan transformations and peek/modify them before executing)
-pub trait PlannedTransformation: ::core::any::Any + ::core::fmt::Debug {
-	/// executes the planned transformation
-	///
-	/// takes the plan data and output path (produced by `dst`)
-	///
-	/// # Errors
-	///
-	/// if the execution fails
-	fn execute(self: Box<Self>, dst: PathBuf) -> Result<(), ErrorKind>;
+/// `path` is planned like [`plan_files`] would plan it, if it expands into several plans (see [`Rule::plan_many`]), only the first is rendered
+///
+/// if the planned transformation is a noop (ex: [`noop`]), the output is the source file's contents
+pub fn render_one(rules: &mut [Rule<'_>], path: &Path) -> Result<Vec<u8>, ErrorKind> {
+	render_one_with(rules, path, &BuildOptions::default())
 }
 
-/// [`noop`] transformation, does not write to the destination file
-impl PlannedTransformation for () {
-	fn execute(self: Box<Self>, _: PathBuf) -> Result<(), ErrorKind> {
-		Ok(())
+/// plan and execute a single source file, with options, returning its output instead of writing it
+///
+/// see [`render_one`]
+pub fn render_one_with(
+	rules: &mut [Rule<'_>],
+	path: &Path,
+	options: &BuildOptions<'_>,
+) -> Result<Vec<u8>, ErrorKind> {
+	let Some(plan) = plan_files(rules, &[path.to_path_buf()], options)?
+		.into_iter()
+		.next()
+	else {
+		return Err(ErrorKind::NotPlanned(path.to_path_buf()));
+	};
+
+	if plan.data.is_noop() {
+		return Ok(fs::read(path)?);
 	}
+
+	let buffer = Arc::new(Mutex::new(Vec::new()));
+	plan.data.execute_to(Sink::Buffer(buffer.clone()))?;
+
+	let output = ::core::mem::take(&mut *buffer.lock().unwrap_or_else(PoisonError::into_inner));
+	Ok(output)
 }
 
-/// writes the binary blob to the destination file
-impl PlannedTransformation for Vec<u8> {
-	#[instrument(skip(self), name = "write binary blob", level = Level::DEBUG)]
-	fn execute(self: Box<Self>, dst: PathBuf) -> Result<(), ErrorKind> {
-		fs::write(dst, *self).map_err(ErrorKind::Io)
-	}
+fn execute_one(plan: Plan) -> Result<(), ErrorKind> {
+	execute_one_in(plan, &StdFs)
 }
 
-/// writes the string to the destination file
-impl PlannedTransformation for String {
-	#[instrument(skip(self), name = "write string data", level = Level::DEBUG)]
-	fn execute(self: Box<Self>, dst: PathBuf) -> Result<(), ErrorKind> {
-		fs::write(dst, self.as_bytes()).map_err(ErrorKind::Io)
+fn execute_one_in(plan: Plan, fs: &dyn Fs) -> Result<(), ErrorKind> {
+	// ensure the directory is there
+	if !plan.data.is_noop() {
+		let dir = plan.dst.parent().unwrap();
+		fs.create_dir_all(dir)
+			.map_err(|err| output_dir_error(fs, dir, &plan.dst, err))?;
 	}
+
+	plan.data.execute_in(plan.dst, fs)
 }
 
-/// [`copy`] transformation, copies the file path specified to the destination file
-impl PlannedTransformation for PathBuf {
-	#[instrument(name = "copy", level = Level::DEBUG)]
-	fn execute(self: Box<Self>, dst: PathBuf) -> Result<(), ErrorKind> {
-		fs::copy(*self, dst).map_err(ErrorKind::Io)?;
-		Ok(())
+/// explain why the directory of an output couldn't be created, which is usually because a file is where one of its directories should be
+fn output_dir_error(fs: &dyn Fs, dir: &Path, dst: &Path, err: io::Error) -> ErrorKind {
+	// the closest ancestor that exists is the one in the way, if it isn't a directory
+	match dir
+		.ancestors()
+		.filter(|ancestor| !ancestor.as_os_str().is_empty())
+		.find_map(|ancestor| {
+			fs.metadata(ancestor)
+				.ok()
+				.map(|metadata| (ancestor, metadata))
+		}) {
+		Some((file, metadata)) if !metadata.is_dir => ErrorKind::FileInOutputPath {
+			file: file.to_path_buf(),
+			dst: dst.to_path_buf(),
+		},
+		_ => ErrorKind::OutputDirectory {
+			dir: dir.to_path_buf(),
+			dst: dst.to_path_buf(),
+			source: err,
+		},
 	}
 }
 
-/// a plan to transform a file
-#[derive(Debug)]
-pub struct Plan {
-	/// the destination file
-	pub dst: PathBuf,
-	/// the plan data produced by the `plan` function
-	pub data: Box<dyn PlannedTransformation>,
-}
+/// make sure every key in a rule's `dst` refers to a capture that its `include` patterns can produce
+fn validate_dst(rule_index: usize, rule: &Rule<'_>) -> Result<(), ErrorKind> {
+	let available = rule.include.iter().map(capture_count);
+	#[cfg(feature = "regex")]
+	let available = available.chain(
+		rule.include_regex
+			.iter()
+			.map(regex::RegexInclude::capture_count),
+	);
+	let available = available.max().unwrap_or_default();
 
-/// equivalent to `execute(plan(rules)?)`
-pub fn run(rules: &mut [Rule<'_>]) -> Result<(), ErrorKind> {
-	execute(plan(rules)?)
+	#[cfg(feature = "regex")]
+	let names = rule
+		.include_regex
+		.iter()
+		.flat_map(regex::RegexInclude::names)
+		.collect::<Vec<_>>();
+	#[cfg(not(feature = "regex"))]
+	let names = [];
+
+	match out_of_range_key(rule.dst, available, &names)? {
+		Some(index) => Err(ErrorKind::CaptureOutOfRange {
+			rule_index,
+			index,
+			available,
+		}),
+		None => Ok(()),
+	}
 }
 
-/// plan some transformations
-#[instrument(skip(rules))]
-pub fn plan(rules: &mut [Rule<'_>]) -> Result<Vec<Plan>, ErrorKind> {
-	let mut plans = Vec::new();
-	let mut visited = HashSet::new();
+/// find the first key in a format-string that does not refer to one of `available` captures
+///
+/// keys may also be any of `names`
+pub(crate) fn out_of_range_key(
+	fmt: &str,
+	available: usize,
+	names: &[Option<&str>],
+) -> Result<Option<usize>, ErrorKind> {
+	let mut out_of_range = None;
 
-	for (rule_index, rule) in rules.iter_mut().enumerate() {
-		let _span = debug_span!("rule", rule_index, ?rule).entered();
+	format_with(fmt, |placeholder, _| {
+		if placeholder.default.is_some() || names.contains(&Some(placeholder.key)) {
+			return Ok(());
+		}
 
-		for (include_index, include) in rule.include.iter().enumerate() {
-			let _span =
-				debug_span!("include", include_index, include = include.to_string()).entered();
+		let index = placeholder
+			.key
+			.parse::<usize>()
+			.map_err(|_| FmtError::KeyError(format!("non-numeric key: \"{}\"", placeholder.key)))?;
 
-			for entry in glob_with(
-				include.as_str(),
-				&MatchOptions {
-					case_sensitive: true,
-					require_literal_leading_dot: false,
-					require_literal_separator: true,
-				},
-			)
-			.map_err(|err| ErrorKind::Pattern {
-				label: [::miette::LabeledSpan::new_primary_with_span(
-					Some(err.msg.to_string()),
-					SourceSpan::new(err.pos.into(), 1),
-				)],
-				src: NamedSource::new(
-					format!("rules[{rule_index}].include[{include_index}]"),
-					include.to_string(),
-				),
-			})? {
-				let entry = entry?;
-				let src_file = entry.path();
-
-				// pull captures out into a vec
-				let captures = {
-					let mut captures = Vec::new();
-
-					let mut i = 1; // skip 0, which is just the entire match
-					while let Some(capture) = entry.group(i) {
-						i += 1;
-						captures.push(
-							capture
-								.to_str()
-								.ok_or(ErrorKind::NonUTF8PathCharacters)?
-								.to_string(),
-						);
-					}
+		if index >= available && out_of_range.is_none() {
+			out_of_range = Some(index);
+		}
 
-					captures
-				};
+		Ok(())
+	})?;
 
-				let dst_file = format(rule.dst, &captures)?;
-				let dst_file = Path::new(&*dst_file);
+	Ok(out_of_range)
+}
 
-				let _span = info_span!(
-					"plan file",
-					src = src_file.to_str().unwrap(),
-					dst = dst_file.to_str().unwrap()
-				)
-				.entered();
+/// whether a format-string refers to the capture at `index`
+fn refers_to_capture(fmt: &str, index: usize) -> Result<bool, ErrorKind> {
+	let mut refers = false;
 
-				// make sure it isnt excluded and that it hasn't been visited yet
+	format_with(fmt, |placeholder, _| {
+		refers |= placeholder.key.parse::<usize>() == Ok(index);
+		Ok(())
+	})?;
 
-				if !src_file.is_file() {
-					error!("skipped (not a file)");
-					continue;
-				}
+	Ok(refers)
+}
 
-				if visited.contains(src_file) {
-					error!("skipped (already visited)");
-					continue;
-				}
+/// count the capture groups in an `include` pattern, without matching any paths
+///
+/// this is how many captures a `dst` may reference (ex: `src/(*)/(*).doll` has 2, so `{0}` and `{1}` are valid)
+///
+/// parentheses inside of a character class (ex: `[(]`, which is how they are escaped) are literal, and do not count
+#[must_use]
+pub fn capture_count(pattern: &Pattern) -> usize {
+	let chars = pattern.as_str().chars().collect::<Vec<_>>();
+	let mut count = 0;
+	let mut i = 0;
 
-				if rule.exclude.iter().any(|ignore| {
-					if ignore.matches_path(src_file) {
-						error!("skipped (matched ignore)");
-						true
-					} else {
-						false
-					}
-				}) {
-					continue;
+	while i < chars.len() {
+		match chars[i] {
+			'[' => {
+				// mirrors how `capturing_glob` finds the end of a character class, where the first character is always literal
+				let skip = if chars.get(i + 1) == Some(&'!') { 3 } else { 2 };
+				match chars
+					.get(i + skip..)
+					.and_then(|rest| rest.iter().position(|c| *c == ']'))
+				{
+					Some(end) => i += skip + end + 1,
+					None => i += 1,
 				}
-
-				plans.push(Plan {
-					dst: dst_file.to_path_buf(),
-					data: (rule.plan)(src_file.to_path_buf(), captures)?,
-				});
-
-				visited.insert(src_file.to_path_buf());
 			}
+			'(' => {
+				count += 1;
+				i += 1;
+			}
+			_ => i += 1,
 		}
 	}
 
-	Ok(plans)
-}
-
-/// execute some plans
-#[instrument(skip(plans))]
-pub fn execute(plans: Vec<Plan>) -> Result<(), ErrorKind> {
-	for plan in plans {
-		// ensure the directory is there
-		fs::create_dir_all(plan.dst.parent().unwrap())?;
-
-		plan.data.execute(plan.dst)?;
-	}
-
-	Ok(())
+	count
 }
 
 /// quickly format a format-string with a given set of captures
 ///
 /// ex: `dist/{0}/{1}.html`
+///
+/// captures may be reshaped as paths by operations after a `|`, which are applied in order:
+///
+/// - `dirname` - everything before the last segment (ex: `{0|dirname}` makes `blog/2024/post` -> `blog/2024`)
+/// - `basename` - only the last segment (ex: `{0|basename}` makes `blog/2024/post` -> `post`)
+/// - `drop_prefix:n` - everything after the first `n` segments (ex: `{0|drop_prefix:1}` makes `blog/2024/post` -> `2024/post`)
+///
+/// a capture may have a format spec after a `:` (ex: `{0:>8}`, or `{0:->8}` to pad with `-`), and a default after a `:-` at the very end, which is used if the capture is missing or empty (ex: `{1:-index}` for an optional trailing group, or `{1|basename:>8:-index}`)
+///
+/// the default is everything after the first `:-` that isn't a `-` fill followed by an alignment, so it may contain `:` and `|`, but not braces, and can't start with `<`, `^`, or `>`
 pub fn format<T: AsRef<str>>(fmt: &str, captures: &[T]) -> Result<String, ErrorKind> {
-	Ok(strfmt_map(fmt, |mut fmt: Formatter| {
-		captures
-			.get(
-				fmt.key
-					.parse::<usize>()
-					.map_err(|_| FmtError::KeyError(format!("non-numeric key: \"{}\"", fmt.key)))?,
-			)
-			.ok_or_else(|| FmtError::KeyError(format!("key {} out of range", fmt.key)))?
-			.as_ref()
-			.display_str(&mut fmt)
+	format_named(fmt, captures, &[])
+}
+
+/// format a format-string with a given set of captures, which may also be referred to by `names`
+fn format_named<T: AsRef<str>>(
+	fmt: &str,
+	captures: &[T],
+	names: &[Option<&str>],
+) -> Result<String, ErrorKind> {
+	Ok(format_with(fmt, |placeholder, out| {
+		let index = match names.iter().position(|name| *name == Some(placeholder.key)) {
+			Some(index) => index,
+			None => placeholder.key.parse::<usize>().map_err(|_| {
+				FmtError::KeyError(format!("non-numeric key: \"{}\"", placeholder.key))
+			})?,
+		};
+
+		let capture = match captures.get(index) {
+			Some(capture) => capture.as_ref(),
+			None if placeholder.default.is_some() => "",
+			None => {
+				return Err(FmtError::KeyError(format!(
+					"key {} out of range",
+					placeholder.key
+				)))
+			}
+		};
+
+		placeholder.write(capture, out)
 	})?)
 }
 
+/// compute the public url of an output path, relative to the root of the output directory
+///
+/// the url always starts with `/`, and a trailing `index.html` is dropped (ex: `deploy/blog/index.html` -> `/blog/`, `deploy/about.html` -> `/about.html`)
+///
+/// returns `None` if `dst` is not inside `output_root` or contains non-UTF8 characters
+#[must_use]
+pub fn url(dst: &Path, output_root: &Path) -> Option<String> {
+	let mut url = String::new();
+
+	for component in dst.strip_prefix(output_root).ok()?.components() {
+		url.push('/');
+		url.push_str(component.as_os_str().to_str()?);
+	}
+
+	if let Some(dir) = url.strip_suffix("/index.html") {
+		url.truncate(dir.len() + 1);
+	}
+
+	if url.is_empty() {
+		url.push('/');
+	}
+
+	Some(url)
+}
+
 /// the most primitive transformer, does absolutely nothing
+///
+/// nothing is written to `dst` and no directories are created for it, so wrapping this is useful for rules that only exist for their side effects (ex: validating or linting each matched file while planning)
 #[instrument(level = Level::DEBUG)]
-pub fn noop(_: PathBuf, _: Vec<String>) -> Result<Box<dyn PlannedTransformation>, ErrorKind> {
+pub fn noop(
+	_: PathBuf,
+	_: Vec<String>,
+	_: &Path,
+	_: &Context,
+) -> Result<Box<dyn PlannedTransformation>, ErrorKind> {
 	Ok(Box::new(()))
 }
 
 /// a primitive transformer that just [`fs::copy`]'s its input path to its output path
 #[instrument(level = Level::DEBUG)]
-pub fn copy(src: PathBuf, _: Vec<String>) -> Result<Box<dyn PlannedTransformation>, ErrorKind> {
+pub fn copy(
+	src: PathBuf,
+	_: Vec<String>,
+	_: &Path,
+	_: &Context,
+) -> Result<Box<dyn PlannedTransformation>, ErrorKind> {
 	Ok(Box::new(src))
 }
 
+/// a primitive transformer that copies its input path to its output path, unless the output is already identical (see [`CopyIfChanged`])
+#[instrument(level = Level::DEBUG)]
+pub fn copy_if_changed(
+	src: PathBuf,
+	_: Vec<String>,
+	_: &Path,
+	_: &Context,
+) -> Result<Box<dyn PlannedTransformation>, ErrorKind> {
+	Ok(Box::new(CopyIfChanged::new(src)))
+}
+
+/// a primitive transformer that hard-links its input path to its output path (see [`HardLink`])
+#[instrument(level = Level::DEBUG)]
+pub fn hard_link(
+	src: PathBuf,
+	_: Vec<String>,
+	_: &Path,
+	_: &Context,
+) -> Result<Box<dyn PlannedTransformation>, ErrorKind> {
+	Ok(Box::new(HardLink(src)))
+}
+
+/// which features dollgen was compiled with, and the versions of the engines behind them (see [`features`])
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[allow(
+	clippy::struct_excessive_bools,
+	reason = "each is an independent cargo feature"
+)]
+pub struct Features {
+	/// the version of dollgen itself
+	pub version: &'static str,
+	/// whether the `liquid` feature is enabled
+	pub liquid: bool,
+	/// whether the `minijinja` feature is enabled
+	pub minijinja: bool,
+	/// whether the `scss` feature is enabled
+	pub scss: bool,
+	/// whether the `wasm` feature is enabled
+	pub wasm: bool,
+	/// whether the `lang-markdoll` feature is enabled
+	pub lang_markdoll: bool,
+	/// whether the `data` feature is enabled
+	pub data: bool,
+	/// whether the `regex` feature is enabled
+	pub regex: bool,
+	/// whether the `integrity` feature is enabled
+	pub integrity: bool,
+	/// whether the `livereload` feature is enabled
+	pub livereload: bool,
+	/// whether the `watch` feature is enabled
+	pub watch: bool,
+	/// whether the `compress` feature is enabled
+	pub compress: bool,
+	/// whether the `less` feature is enabled
+	pub less: bool,
+	/// whether the `assets` feature is enabled
+	pub assets: bool,
+	/// whether the `command` feature is enabled
+	pub command: bool,
+	/// the versions of the engines behind the enabled features
+	pub versions: EngineVersions,
+}
+
+/// the resolved versions of the engines behind dollgen's integrations, read from `Cargo.lock` when dollgen is compiled (see [`Features::versions`])
+///
+/// each is `None` if its feature is disabled, or if the lock file couldn't be found (it is looked for above the target directory)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EngineVersions {
+	/// the version of `liquid`, for the `liquid` feature
+	pub liquid: Option<&'static str>,
+	/// the version of `minijinja`, for the `minijinja` feature
+	pub minijinja: Option<&'static str>,
+	/// the version of `grass`, for the `scss` feature
+	pub grass: Option<&'static str>,
+	/// the version of `wasm-bindgen` (of its cli support, which is released alongside it), for the `wasm` feature
+	pub wasm_bindgen: Option<&'static str>,
+	/// the version of `markdoll`, for the `lang-markdoll` feature
+	pub markdoll: Option<&'static str>,
+	/// the version of `notify`, for the `watch` feature
+	pub notify: Option<&'static str>,
+}
+
+/// which features dollgen was compiled with, useful for diagnosing a missing integration (ex: scss not working because the `scss` feature is disabled)
+///
+/// ex: `features().versions.grass` is the version of the scss compiler, if the `scss` feature is enabled
+#[must_use]
+pub const fn features() -> Features {
+	/// the version a build script found for an engine, if its feature is enabled
+	macro_rules! version {
+		($feature:literal, $var:literal) => {
+			if cfg!(feature = $feature) {
+				option_env!($var)
+			} else {
+				None
+			}
+		};
+	}
+
+	Features {
+		version: env!("CARGO_PKG_VERSION"),
+		liquid: cfg!(feature = "liquid"),
+		minijinja: cfg!(feature = "minijinja"),
+		scss: cfg!(feature = "scss"),
+		wasm: cfg!(feature = "wasm"),
+		lang_markdoll: cfg!(feature = "lang-markdoll"),
+		data: cfg!(feature = "data"),
+		regex: cfg!(feature = "regex"),
+		integrity: cfg!(feature = "integrity"),
+		livereload: cfg!(feature = "livereload"),
+		watch: cfg!(feature = "watch"),
+		compress: cfg!(feature = "compress"),
+		less: cfg!(feature = "less"),
+		assets: cfg!(feature = "assets"),
+		command: cfg!(feature = "command"),
+		versions: EngineVersions {
+			liquid: version!("liquid", "DOLLGEN_LIQUID_VERSION"),
+			minijinja: version!("minijinja", "DOLLGEN_MINIJINJA_VERSION"),
+			grass: version!("scss", "DOLLGEN_GRASS_VERSION"),
+			wasm_bindgen: version!("wasm", "DOLLGEN_WASM_BINDGEN_VERSION"),
+			markdoll: version!("lang-markdoll", "DOLLGEN_MARKDOLL_VERSION"),
+			notify: version!("watch", "DOLLGEN_NOTIFY_VERSION"),
+		},
+	}
+}
+
 /// an error
 #[derive(::thiserror::Error, ::miette::Diagnostic, Debug)]
 pub enum ErrorKind {
@@ -270,14 +2504,33 @@ pub enum ErrorKind {
 	#[error("pattern failure to compile")]
 	#[diagnostic(code(dollgen::glob::bad_pattern))]
 	Pattern {
+		/// the index of the rule the pattern belongs to
+		rule_index: usize,
+		/// the index of the pattern within the rule's `include`
+		include_index: usize,
 		/// the error labels
 		#[label(collection)]
-		label: [::miette::LabeledSpan; 1],
+		label: Vec<::miette::LabeledSpan>,
 		/// the source data
 		#[source_code]
 		src: ::miette::NamedSource<String>,
 	},
 
+	/// a rule's `dst` references a capture that none of its `include` patterns can produce
+	#[error("rules[{rule_index}].dst references capture {{{index}}}, but `include` only captures {available}")]
+	#[diagnostic(
+		code(dollgen::format_str::capture_out_of_range),
+		help("captures are numbered from 0, in the order their `(` appears in the pattern")
+	)]
+	CaptureOutOfRange {
+		/// the index of the rule
+		rule_index: usize,
+		/// the capture that was referenced
+		index: usize,
+		/// the largest number of captures any `include` pattern produces
+		available: usize,
+	},
+
 	/// searching failure
 	#[error("glob failure")]
 	#[diagnostic(code(dollgen::glob::failure))]
@@ -296,6 +2549,18 @@ pub enum ErrorKind {
 		::strfmt::FmtError,
 	),
 
+	/// data serialization failure
+	///
+	/// requires `data` feature
+	#[cfg(feature = "data")]
+	#[error("data serialization failure")]
+	#[diagnostic(code(dollgen::data))]
+	Serialization(
+		#[source]
+		#[from]
+		data::DataErrorKind,
+	),
+
 	/// liquid integration failure
 	///
 	/// requires `liquid` feature
@@ -305,6 +2570,7 @@ pub enum ErrorKind {
 	LiquidIntegration(
 		#[source]
 		#[from]
+		#[diagnostic_source]
 		liquid::LiquidErrorKind,
 	),
 
@@ -317,6 +2583,7 @@ pub enum ErrorKind {
 	MinijinjaIntegration(
 		#[source]
 		#[from]
+		#[diagnostic_source]
 		minijinja::MinijinjaErrorKind,
 	),
 
@@ -335,6 +2602,21 @@ pub enum ErrorKind {
 		src: ::miette::NamedSource<String>,
 	},
 
+	/// less integration failure
+	///
+	/// requires `less` feature
+	#[cfg(feature = "less")]
+	#[error("less integration failure")]
+	#[diagnostic(code(dollgen::less))]
+	LESSIntegration {
+		/// the section of the source that failure
+		#[label(collection)]
+		span: [::miette::LabeledSpan; 1],
+		/// the source file that failure
+		#[source_code]
+		src: ::miette::NamedSource<String>,
+	},
+
 	/// wasm integration failure
 	///
 	/// requires `wasm` feature
@@ -347,6 +2629,31 @@ pub enum ErrorKind {
 		wasm::WASMErrorKind,
 	),
 
+	/// file watching failure
+	///
+	/// requires `watch` feature
+	#[cfg(feature = "watch")]
+	#[error("file watching failure")]
+	#[diagnostic(code(dollgen::watch))]
+	Watch(
+		#[source]
+		#[from]
+		::notify::Error,
+	),
+
+	/// external command failure
+	///
+	/// requires `command` feature
+	#[cfg(feature = "command")]
+	#[error("external command failure")]
+	#[diagnostic(code(dollgen::command))]
+	Command(
+		#[source]
+		#[from]
+		#[diagnostic_source]
+		command::CommandErrorKind,
+	),
+
 	/// template source lang failure
 	#[error("template source lang failure")]
 	#[diagnostic(code(dollgen::lang))]
@@ -365,6 +2672,97 @@ pub enum ErrorKind {
 		::std::io::Error,
 	),
 
+	/// a rule's `include` patterns matched no paths, and [`BuildOptions::empty_rules`] is [`EmptyRules::Error`]
+	#[error("rules[{rule_index}] matched no paths")]
+	#[diagnostic(
+		code(dollgen::empty_rule),
+		help("check the rule's `include` patterns, and that the build is run from the right directory")
+	)]
+	EmptyRule {
+		/// the index of the rule
+		rule_index: usize,
+	},
+
+	/// a source file was empty, and [`BuildOptions::empty_sources`] is [`EmptySources::Error`]
+	#[error("source file {} is empty", .0.display())]
+	#[diagnostic(
+		code(dollgen::io::empty_source),
+		help("add content to the file, or exclude it from the rule")
+	)]
+	EmptySource(PathBuf),
+
+	/// a `dst` resolved outside of [`BuildOptions::output_root`]
+	#[error("output path {} (planned from {}) escapes the output root {}", .dst.display(), .src.display(), .output_root.display())]
+	#[diagnostic(
+		code(dollgen::path_escape),
+		help("a capture may contain `..`, check the source path or the rule's `dst`")
+	)]
+	PathEscape {
+		/// the source path
+		src: PathBuf,
+		/// the output path
+		dst: PathBuf,
+		/// the output root it escaped
+		output_root: PathBuf,
+	},
+
+	/// the directory of an output couldn't be created
+	#[error("failed to create directory {} for {}", .dir.display(), .dst.display())]
+	#[diagnostic(code(dollgen::io::output_directory))]
+	OutputDirectory {
+		/// the directory
+		dir: PathBuf,
+		/// the output it was for
+		dst: PathBuf,
+		/// why it couldn't be created
+		#[source]
+		source: ::std::io::Error,
+	},
+
+	/// a file is where a directory of an output should be (ex: `deploy/blog` is a file, but `deploy/blog/post.html` is being written)
+	#[error("{} is a file, so {} can't be written inside of it", .file.display(), .dst.display())]
+	#[diagnostic(
+		code(dollgen::io::file_in_output_path),
+		help("another rule may write a file where this one expects a directory, check their `dst`s, or remove the file if it was left by an old build")
+	)]
+	FileInOutputPath {
+		/// the file in the way
+		file: PathBuf,
+		/// the output being written
+		dst: PathBuf,
+	},
+
+	/// a transformation can only be executed to a [`Sink::File`]
+	#[error("{0} can only be executed to a file")]
+	#[diagnostic(code(dollgen::unsupported_sink))]
+	UnsupportedSink(&'static str),
+
+	/// no rule planned a path passed to [`render_one`]
+	#[error("no rule plans {0:?}")]
+	#[diagnostic(
+		code(dollgen::not_planned),
+		help("check that a rule's `include` matches the path, that no `exclude` does, and that the rule is selected")
+	)]
+	NotPlanned(PathBuf),
+
+	/// the build was cancelled with [`BuildOptions::cancel`]
+	#[error("build cancelled")]
+	#[diagnostic(code(dollgen::cancelled))]
+	Cancelled,
+
+	/// a string from configuration referred to an environment variable that isn't set (see [`glob::expand_vars`])
+	#[error("`${name}` in {input:?} is not set")]
+	#[diagnostic(
+		code(dollgen::unset_variable),
+		help("set the variable, or write `$$` for a literal `$`")
+	)]
+	UnsetVariable {
+		/// the variable
+		name: String,
+		/// the string it was in
+		input: String,
+	},
+
 	/// a path contained non-utf8 characters
 	#[error("non-utf8 path characters")]
 	#[diagnostic(code(dollgen::io::non_utf8_path))]
@@ -375,8 +2773,223 @@ pub enum ErrorKind {
 	#[diagnostic(code(dollgen::io::non_utf8_content))]
 	NonUTF8Characters,
 
+	/// a rule's plan closure failed, which says which rule and source file it failed for
+	///
+	/// the diagnostic (code, labels, source code, etc) is that of the error from the plan closure
+	#[error("rules[{rule_index}] failed to plan {}", .src.display())]
+	#[diagnostic(forward(source))]
+	InRule {
+		/// the index of the rule
+		rule_index: usize,
+		/// the source file being planned
+		src: PathBuf,
+		/// the error from the plan closure
+		#[source]
+		source: Box<ErrorKind>,
+	},
+
+	/// several failures, collected rather than stopping at the first
+	#[error("{} failures", .0.len())]
+	#[diagnostic(code(dollgen::multiple))]
+	Multiple(#[related] Vec<ErrorKind>),
+
 	/// something else
 	#[error("other")]
 	#[diagnostic(transparent)]
 	Other(#[source] Box<dyn Diagnostic + Send + Sync>),
 }
+
+#[cfg(test)]
+mod tests {
+	use {
+		super::*,
+		::std::{env, process},
+	};
+
+	#[test]
+	fn jobs_limit_running_programs() {
+		let jobs = Jobs::new(2);
+		let running = AtomicUsize::new(0);
+		let most = AtomicUsize::new(0);
+
+		thread::scope(|scope| {
+			for _ in 0..8 {
+				scope.spawn(|| {
+					let _slot = jobs.acquire();
+					most.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+					thread::sleep(Duration::from_millis(10));
+					running.fetch_sub(1, Ordering::SeqCst);
+				});
+			}
+		});
+
+		assert_eq!(most.load(Ordering::SeqCst), 2);
+	}
+
+	#[test]
+	fn builds_share_jobs_through_the_context() {
+		let options = BuildOptions {
+			jobs: Some(3),
+			..BuildOptions::default()
+		};
+		let context = options.build_context();
+		assert_eq!(context.get::<Jobs>().map(Jobs::limit), Some(3));
+
+		// a nested build keeps the outer build's limit
+		let nested = BuildOptions {
+			context: Some(&context),
+			jobs: Some(1),
+			..BuildOptions::default()
+		};
+		assert_eq!(
+			nested.build_context().get::<Jobs>().map(Jobs::limit),
+			Some(3)
+		);
+	}
+
+	#[test]
+	fn planned_dst_uses_native_separators() {
+		let root = env::temp_dir().join(format!("dollgen-native-separators-{}", process::id()));
+		let src = root.join("src").join("blog").join("2024").join("post.txt");
+		fs::create_dir_all(src.parent().unwrap()).unwrap();
+		fs::write(&src, "post").unwrap();
+
+		let include = [Pattern::new(&format!(
+			"{}/src/(**)/(*).txt",
+			glob::escape_glob(&root.to_string_lossy())
+		))
+		.unwrap()];
+		let mut plan = copy;
+		let plans = plan_files(
+			&mut [Rule::new(&include, &[], "out/{0}/{1}.html", &mut plan)],
+			&[src],
+			&BuildOptions::default(),
+		);
+		fs::remove_dir_all(&root).unwrap();
+
+		let expected = ["out", "blog", "2024", "post.html"]
+			.iter()
+			.collect::<PathBuf>();
+		assert_eq!(plans.unwrap()[0].dst.as_os_str(), expected.as_os_str());
+	}
+
+	#[test]
+	fn skipped_paths_dont_check_dst() {
+		let root = env::temp_dir().join(format!("dollgen-skipped-dst-{}", process::id()));
+		let src = root.join("draft.txt");
+		fs::create_dir_all(&root).unwrap();
+		fs::write(&src, "draft").unwrap();
+
+		let include = [Pattern::new(&format!(
+			"{}/(*).txt",
+			glob::escape_glob(&root.to_string_lossy())
+		))
+		.unwrap()];
+		let exclude = [Pattern::new("**/draft.txt").unwrap()];
+		let mut plan = copy;
+		// `dst` escapes `output_root`, but the only match is excluded
+		let plans = plan_files(
+			&mut [Rule::new(&include, &exclude, "../{0}.html", &mut plan)],
+			&[src],
+			&BuildOptions {
+				output_root: Some(Path::new("out")),
+				..BuildOptions::default()
+			},
+		);
+		fs::remove_dir_all(&root).unwrap();
+
+		assert!(plans.unwrap().is_empty());
+	}
+
+	#[test]
+	fn capture_count_counts_groups() {
+		let count = |pattern| capture_count(&Pattern::new(pattern).unwrap());
+
+		assert_eq!(count("src/**/*.doll"), 0);
+		assert_eq!(count("src/(**)/(*).doll"), 2);
+		assert_eq!(count("src/(*)/(*)/(*).doll"), 3);
+	}
+
+	#[test]
+	fn capture_count_skips_character_classes() {
+		let count = |pattern| capture_count(&Pattern::new(pattern).unwrap());
+
+		assert_eq!(count("src/[(]draft[)]/(*).doll"), 1);
+		assert_eq!(count("src/[!(]*/(*).doll"), 1);
+		// the first character of a class is literal, even if it is `]`
+		assert_eq!(count("src/[]()]/(*).doll"), 1);
+	}
+
+	#[test]
+	fn refers_to_capture_by_index() {
+		assert_eq!(refers_to_capture("out/{0}/{1}.html", 1).ok(), Some(true));
+		assert_eq!(
+			refers_to_capture("out/{1|basename}.html", 1).ok(),
+			Some(true)
+		);
+		assert_eq!(refers_to_capture("out/{1}.html", 0).ok(), Some(false));
+		assert_eq!(
+			refers_to_capture("out/{{0}}/{10}.html", 0).ok(),
+			Some(false)
+		);
+	}
+
+	/// only captures that `dst` refers to need to be utf8
+	///
+	/// `capturing_glob` skips paths that aren't utf8, so this uses captures directly
+	#[cfg(unix)]
+	#[test]
+	fn unused_non_utf8_captures_are_lossy() {
+		use ::std::os::unix::ffi::OsStrExt;
+
+		let groups = [OsStr::from_bytes(b"caf\xe9"), OsStr::new("post")];
+
+		assert_eq!(
+			convert_captures(groups, "out/{1}.html").ok(),
+			Some(vec!["caf\u{fffd}".to_string(), "post".to_string()])
+		);
+		assert!(matches!(
+			convert_captures(groups, "out/{0}/{1}.html"),
+			Err(ErrorKind::NonUTF8PathCharacters)
+		));
+	}
+
+	#[test]
+	fn features_report_engine_versions() {
+		let features = features();
+
+		assert_eq!(features.versions.liquid.is_some(), features.liquid);
+		assert_eq!(features.versions.minijinja.is_some(), features.minijinja);
+		assert_eq!(features.versions.grass.is_some(), features.scss);
+		assert_eq!(features.versions.wasm_bindgen.is_some(), features.wasm);
+		assert_eq!(features.versions.markdoll.is_some(), features.lang_markdoll);
+		assert_eq!(features.versions.notify.is_some(), features.watch);
+	}
+
+	#[test]
+	fn force_reaches_wrapped_plans() {
+		let mut plan: Box<dyn PlannedTransformation> = Box::new(Retry {
+			inner: CopyIfChanged::new("src/a.txt"),
+			attempts: 1,
+			backoff: Duration::ZERO,
+		});
+		plan.force();
+
+		let retry = (&*plan as &dyn Any).downcast_ref::<Retry<CopyIfChanged>>();
+		assert!(retry.is_some_and(|retry| retry.inner.force));
+	}
+
+	#[test]
+	fn forced_builds_say_so_in_the_context() {
+		let forced = BuildOptions {
+			force: true,
+			..BuildOptions::default()
+		};
+
+		assert!(forced.build_context().get::<Forced>().is_some());
+		assert!(BuildOptions::default()
+			.build_context()
+			.get::<Forced>()
+			.is_none());
+	}
+}
@@ -1,61 +1,109 @@
 //! compile liquid templates, based on input languages
 //!
-//! languages parse their source code and may provide a frontmatter string, which is parsed as TOML:
+//! languages parse their source code and may provide a frontmatter string, which is parsed in the format the language gives (see [`FrontmatterFormat`](crate::lang::FrontmatterFormat)):
 //!
 //! - `template` (optional)
+//!   - if `template` is a string, the template with that name is used (see [`DefaultTemplate`])
 //!   - if `template.local` is true:
 //!     - if `template.path` is defined, that template is used, and the path is assumed to be relative to the directory containing the source file
 //!     - if `template.path` is not defined, it uses the template with the same name as the source file (ex: `page.doll` will use `page.liquid` in the same directory)
 //!   - if `template.local` is false or not specified:
-//!     - if `template.path` is defined, that template is used, and the path is assumed to be relative to the root of the build
+//!     - if `template.path` is defined, that template is used, and the path is assumed to be relative to the root of the build, or to the first of the [`SearchDirs`] that has it
 //!     - if `template.path` is not defined, the default template is used
 //! - `props` (optional)
 //!   - values are fed into the liquid template
+//! - `draft` (optional)
+//!   - if `true`, the source file is skipped, unless drafts are included (see [`Liquid::drafts`])
+//!
+//! template paths from the frontmatter have [`Liquid::template_extension`] added to them (ex: `layouts/post` is `layouts/post.liquid`)
+//!
+//! the entire frontmatter (including any other keys) is given to the `globals` closure, so other top-level keys (ex: `title`, `date`) may be used too
+//!
+//! if [`Liquid::output_root`] is set, the public url of the output file (see [`url`](crate::url)) is given to the `globals` closure, and [`default_globals`] provides it as the global `url`
+//!
+//! if [`Liquid::reading_time`] is set, the word count and reading time of the content are provided as the globals `word_count` and `reading_time` (see [`ReadingTime`])
 //!
 //! requires `liquid` feature
 
+pub use crate::templating::{DefaultTemplate, Drafts, ReadingTime, SearchDirs};
 use {
-	crate::{util::with_added_extension_but_stable, ErrorKind, PlannedTransformation},
-	::core::cell::RefCell,
+	crate::{
+		lang::FrontmatterFormat,
+		templating::{parse_frontmatter, resolve_template, ResolveError},
+		util::{hash_bytes, hash_serialized},
+		ClearCache,
+		Context,
+		ErrorKind,
+		PlannedTransformation,
+		Sink,
+	},
+	::core::hash::Hasher,
 	::hashbrown::{hash_map::EntryRef, HashMap},
-	::liquid::{object, Object, Parser, Template},
-	::serde::Deserialize,
+	::liquid::{model::Value, object, Object, Parser, Template},
+	::serde::Serialize,
 	::std::{
-		fs::{self, OpenOptions},
+		fs,
+		io::{BufWriter, Write},
 		path::{Path, PathBuf},
-		rc::Rc,
+		sync::{Arc, Mutex, PoisonError},
 	},
-	::toml::from_str,
-	::tracing::{instrument, trace_span, Level},
+	::tracing::{debug, instrument, trace_span, Level},
 };
 
 pub extern crate liquid;
 
+pub mod partials;
+
 /// parses and caches liquid templates
 ///
 /// ensure to [`clear_cache`](Liquid::clear_cache) in case templates change
+///
+/// this is shared behind an [`Arc`]`<`[`Mutex`]`>`, and the parsed templates are [`Arc`]s, so liquid plans may be sent to and executed on other threads
 pub struct Liquid {
 	/// the parser
 	pub parser: Parser,
-	cache: HashMap<PathBuf, Rc<Template>>,
+	/// the root of the output directory, used to compute the url given to `globals` closures (see [`url`](crate::url))
+	///
+	/// if `None`, they are given no url
+	pub output_root: Option<PathBuf>,
+	/// how many bytes of rendered output are buffered before being written, so that large templates don't make many small writes
+	///
+	/// if `0`, output is written unbuffered
+	pub buffer_capacity: usize,
+	/// the extension added to template paths from frontmatter (ex: `liquid`, `html.liquid`, or `liquid.html`)
+	///
+	/// defaults to `liquid`
+	pub template_extension: String,
+	/// how source files marked as drafts in their frontmatter are handled (by default, `draft = true` skips them)
+	pub drafts: Drafts,
+	/// how the reading time globals are computed, see [`ReadingTime`]
+	///
+	/// if `None`, they aren't provided
+	pub reading_time: Option<ReadingTime>,
+	cache: HashMap<PathBuf, Arc<Template>>,
 }
 
 impl Liquid {
 	/// create from a liquid parser builder
 	#[must_use]
-	pub fn new(parser: Parser) -> Rc<RefCell<Self>> {
-		Rc::new(RefCell::new(Self {
+	pub fn new(parser: Parser) -> Arc<Mutex<Self>> {
+		Arc::new(Mutex::new(Self {
 			parser,
+			output_root: None,
+			buffer_capacity: 8 * 1024,
+			template_extension: "liquid".to_string(),
+			drafts: Drafts::default(),
+			reading_time: None,
 			cache: HashMap::new(),
 		}))
 	}
 
 	/// parse a template file or retrieve from cache
-	pub fn parse(&mut self, path: &Path) -> Result<Rc<Template>, ErrorKind> {
+	pub fn parse(&mut self, path: &Path) -> Result<Arc<Template>, ErrorKind> {
 		Ok(match self.cache.entry_ref(path) {
 			EntryRef::Occupied(entry) => entry.into_mut(),
 			EntryRef::Vacant(entry) => {
-				entry.insert(Rc::new(self.parser.parse_file(path).map_err(|err| {
+				entry.insert(Arc::new(self.parser.parse_file(path).map_err(|err| {
 					let source_code = match fs::read_to_string(path) {
 						Ok(src) => src,
 						Err(err) => return ErrorKind::Io(err),
@@ -72,137 +120,378 @@ impl Liquid {
 		.clone())
 	}
 
+	/// parse every template in `paths` up front, so that parse errors surface immediately and the first render isn't slowed down by parsing
+	///
+	/// every template is attempted, and any failures are returned together as [`ErrorKind::Multiple`]
+	pub fn precompile(&mut self, paths: &[&Path]) -> Result<(), ErrorKind> {
+		let errors = paths
+			.iter()
+			.filter_map(|path| self.parse(path).err())
+			.collect::<Vec<_>>();
+
+		if errors.is_empty() {
+			Ok(())
+		} else {
+			Err(ErrorKind::Multiple(errors))
+		}
+	}
+
 	/// clear the cache
 	pub fn clear_cache(&mut self) {
 		self.cache.clear();
 	}
-}
 
-#[derive(Deserialize, Debug)]
-struct Frontmatter {
-	pub template: Option<FrontmatterTemplate>,
-	pub props: Option<liquid::Object>,
+	/// the public url of `dst`, if there is an output root
+	fn url(&self, dst: &Path) -> Option<String> {
+		self.output_root
+			.as_deref()
+			.and_then(|root| crate::url(dst, root))
+	}
+
+	/// provide the globals measured by [`ReadingTime`], if any
+	fn insert_reading_time(globals: &mut Object, measured: Option<(usize, usize)>) {
+		let Some((words, minutes)) = measured else {
+			return;
+		};
+
+		for (key, value) in [("word_count", words), ("reading_time", minutes)] {
+			if !globals.contains_key(key) {
+				globals.insert(
+					key.into(),
+					Value::scalar(i64::try_from(value).unwrap_or(i64::MAX)),
+				);
+			}
+		}
+	}
 }
 
-#[derive(Deserialize, Debug)]
-struct FrontmatterTemplate {
-	pub path: Option<PathBuf>,
-	#[serde(default)]
-	pub local: bool,
+impl ClearCache for Mutex<Liquid> {
+	fn clear_cache(&self) {
+		self.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.clear_cache();
+	}
 }
 
-/// the default globals for [`create_templated`], which passes `props` as the global `props` and `body` as the global `body`
+/// the default globals for [`create_templated`], which passes `props` from the frontmatter as the global `props`, the entire frontmatter as the global `frontmatter`, `body` as the global `body`, and the url as the global `url` (if there is one)
 #[must_use]
-pub fn default_globals(_: PathBuf, props: Option<Object>, body: String) -> Object {
-	object!({
+#[allow(
+	clippy::needless_pass_by_value,
+	reason = "must match the signature of the `globals` closure"
+)]
+pub fn default_globals(
+	_: PathBuf,
+	_: &Path,
+	url: Option<String>,
+	frontmatter: Object,
+	body: String,
+) -> Object {
+	let mut globals = object!({
 		"body": body,
-		"props": props.unwrap_or_default(),
-	})
+		"props": frontmatter.get("props").cloned().unwrap_or_else(|| Value::Object(Object::new())),
+		"frontmatter": frontmatter,
+	});
+
+	if let Some(url) = url {
+		globals.insert("url".into(), Value::scalar(url));
+	}
+
+	globals
+}
+
+/// convert serializable data (ex: a struct deriving `Serialize`) into globals, so that `globals` closures may build them from typed data rather than with [`object!`]
+pub fn globals_from<T: Serialize>(value: &T) -> Result<Object, ErrorKind> {
+	Ok(::liquid::model::to_object(value).map_err(LiquidErrorKind::GlobalsConversion)?)
 }
 
 /// a plan to render a liquid template
 #[::tyfling::debug(.globals)]
 pub struct LiquidPlan {
 	/// the template
-	pub template: Rc<Template>,
+	pub template: Arc<Template>,
+	/// the path the template was parsed from, used to show its source if rendering fails
+	///
+	/// for templates parsed from a string, this is only the name used in errors (see [`LiquidPlan::from_source`])
+	pub template_path: PathBuf,
+	/// the source of the template, if it was parsed from a string rather than a file
+	pub source: Option<Arc<str>>,
 	/// the globals
 	pub globals: Object,
+	/// how many bytes of rendered output are buffered before being written (see [`Liquid::buffer_capacity`])
+	pub buffer_capacity: usize,
+}
+
+impl LiquidPlan {
+	/// plan to render a template file against globals directly, without a source file (ex: for generated index or tag pages, added with [`Plan::new`](crate::Plan::new))
+	///
+	/// unlike [`create_templated`], no url is computed, so add a `url` to `globals` if templates need it
+	pub fn new(
+		liquid: &mut Liquid,
+		template_path: PathBuf,
+		globals: Object,
+	) -> Result<Self, ErrorKind> {
+		Ok(Self {
+			template: liquid.parse(&template_path)?,
+			template_path,
+			source: None,
+			globals,
+			buffer_capacity: liquid.buffer_capacity,
+		})
+	}
+
+	/// plan to render a template from a string against globals directly, without a source file or template file
+	///
+	/// - `name` - the name of the template, used in errors (ex: `tag index`)
+	/// - `source` - the template
+	///
+	/// the template is parsed again by every call, unlike template files, which are cached
+	pub fn from_source(
+		liquid: &Liquid,
+		name: &str,
+		source: impl Into<Arc<str>>,
+		globals: Object,
+	) -> Result<Self, ErrorKind> {
+		let source = source.into();
+
+		Ok(Self {
+			template: Arc::new(liquid.parser.parse(&source).map_err(|err| {
+				LiquidErrorKind::LiquidParsing(err, PathBuf::from(name), source.to_string())
+			})?),
+			template_path: PathBuf::from(name),
+			source: Some(source),
+			globals,
+			buffer_capacity: liquid.buffer_capacity,
+		})
+	}
+
+	/// render the template to bytes, rather than to a file
+	pub fn render_to_bytes(&self) -> Result<Vec<u8>, ErrorKind> {
+		let mut out = Vec::new();
+		self.render(&mut out, None)?;
+		Ok(out)
+	}
+
+	/// render the template to a string, rather than to a file
+	pub fn render_to_string(&self) -> Result<String, ErrorKind> {
+		String::from_utf8(self.render_to_bytes()?).map_err(|_| ErrorKind::NonUTF8Characters)
+	}
+
+	fn render(&self, out: &mut dyn Write, dst: Option<&Path>) -> Result<(), ErrorKind> {
+		self.template.render_to(out, &self.globals).map_err(|err| {
+			let source_code = match &self.source {
+				Some(source) => source.to_string(),
+				None => match fs::read_to_string(&self.template_path) {
+					Ok(src) => src,
+					Err(err) => return ErrorKind::Io(err),
+				},
+			};
+
+			// liquid doesn't track positions, but traces what it was rendering (innermost first), which usually includes the source of the tag or the name of the filter that failed
+			let message = err.to_string();
+			let reason = message.lines().next().unwrap_or_default();
+			let label = message
+				.lines()
+				.filter_map(|line| {
+					line.strip_prefix("from: ").or_else(|| {
+						line.trim_start()
+							.strip_prefix("filter=")
+							.map(|filter| filter.split(':').next().unwrap_or_default().trim())
+					})
+				})
+				.find_map(|snippet| snippet_label(&source_code, snippet, reason.to_string()))
+				.into_iter()
+				.collect();
+
+			ErrorKind::LiquidIntegration(LiquidErrorKind::LiquidRendering {
+				err,
+				label,
+				dst: dst.map(Path::to_path_buf),
+				template: self.template_path.clone(),
+				source_code,
+			})
+		})
+	}
 }
 
 impl PlannedTransformation for LiquidPlan {
-	#[instrument(, name = "render liquid template", level = Level::DEBUG)]
 	fn execute(self: Box<Self>, dst: PathBuf) -> Result<(), ErrorKind> {
-		self.template
-			.render_to(
-				&mut OpenOptions::new()
-					.create(true)
-					.write(true)
-					.truncate(true)
-					.append(false)
-					.read(false)
-					.open(&dst)?,
-				&self.globals,
-			)
-			.map_err(|err| ErrorKind::LiquidIntegration(LiquidErrorKind::LiquidRendering(err, dst)))
+		self.execute_to(Sink::File(dst))
+	}
+
+	#[instrument(, name = "render liquid template", level = Level::DEBUG)]
+	fn execute_to(self: Box<Self>, sink: Sink) -> Result<(), ErrorKind> {
+		let mut out = BufWriter::with_capacity(self.buffer_capacity, sink.open()?);
+		self.render(&mut out, sink.path())?;
+		out.flush()?;
+		Ok(())
+	}
+
+	fn inputs(&self) -> Vec<PathBuf> {
+		if self.source.is_some() {
+			Vec::new()
+		} else {
+			vec![self.template_path.clone()]
+		}
+	}
+
+	fn fingerprint(&self, state: &mut dyn Hasher) {
+		if let Some(source) = &self.source {
+			hash_bytes(state, source.as_bytes());
+		}
+		hash_serialized(state, &self.globals);
 	}
 }
 
+/// a label pointing at the first occurrence of `snippet` in a template, if it occurs
+fn snippet_label(src: &str, snippet: &str, message: String) -> Option<::miette::LabeledSpan> {
+	src.find(snippet).map(|start| {
+		::miette::LabeledSpan::new_primary_with_span(Some(message), (start, snippet.len()))
+	})
+}
+
 /// compile liquid templates + a source language
 ///
-/// - `default_template` - the template to use when not overridden by a given source file
-/// - `liquid` - a shared cell of the liquid parser instance
+/// - `default_template` - the template to use when not overridden by a given source file, may be a path, a closure, or a map of named templates (see [`DefaultTemplate`])
+/// - `liquid` - a shared lock of the liquid parser instance
 /// - `globals` - the globals to use in templating
-///   - takes the source file path, props from frontmatter, and compiled content from `lang`
+///   - takes the source file path, the output file path (produced by the rule's `dst`), the public url of the output file (see [`Liquid::output_root`]), the entire frontmatter, and compiled content from `lang`
 ///   - returns the globals
 ///   
 ///   if you don't have a purpose for this, you should probably set it to [`default_globals`]
 /// - `lang` - the source language to parse
 ///   - takes the content of the source file
-///   - returns (frontmatter (unparsed), content)
+///   - returns (frontmatter format, frontmatter (unparsed), content)
+///   - wrap it with [`transform_body`](crate::lang::transform_body) to post-process the content before it reaches `globals`
 pub fn create_templated(
-	default_template: PathBuf,
-	liquid: Rc<RefCell<Liquid>>,
-	mut globals: impl for<'a> FnMut(PathBuf, Option<Object>, String) -> Object,
-	mut lang: impl for<'a> FnMut(&'a str, &'a Path) -> Result<(String, String), ErrorKind>,
-) -> impl FnMut(PathBuf, Vec<String>) -> Result<Box<dyn PlannedTransformation>, ErrorKind> {
-	move |src: PathBuf, _| {
-		let _span = trace_span!("templated liquid", ?default_template).entered();
+	default_template: impl DefaultTemplate,
+	liquid: Arc<Mutex<Liquid>>,
+	globals: impl for<'a> FnMut(PathBuf, &'a Path, Option<String>, Object, String) -> Object,
+	lang: impl for<'a> FnMut(
+		&'a str,
+		&'a Path,
+	) -> Result<(FrontmatterFormat, String, String), ErrorKind>,
+) -> impl FnMut(PathBuf, Vec<String>, &Path, &Context) -> Result<Box<dyn PlannedTransformation>, ErrorKind>
+{
+	create(default_template, liquid, globals, lang, false)
+}
+
+/// compile liquid templates + a source language, but only for source files whose frontmatter selects a `template`
+///
+/// source files that select a template behave exactly as in [`create_templated`],
+/// while those that don't have the content from `lang` written as-is, without any template
+///
+/// arguments are the same as [`create_templated`]
+pub fn create_auto(
+	default_template: impl DefaultTemplate,
+	liquid: Arc<Mutex<Liquid>>,
+	globals: impl for<'a> FnMut(PathBuf, &'a Path, Option<String>, Object, String) -> Object,
+	lang: impl for<'a> FnMut(
+		&'a str,
+		&'a Path,
+	) -> Result<(FrontmatterFormat, String, String), ErrorKind>,
+) -> impl FnMut(PathBuf, Vec<String>, &Path, &Context) -> Result<Box<dyn PlannedTransformation>, ErrorKind>
+{
+	create(default_template, liquid, globals, lang, true)
+}
+
+fn create(
+	mut default_template: impl DefaultTemplate,
+	liquid: Arc<Mutex<Liquid>>,
+	mut globals: impl for<'a> FnMut(PathBuf, &'a Path, Option<String>, Object, String) -> Object,
+	mut lang: impl for<'a> FnMut(
+		&'a str,
+		&'a Path,
+	) -> Result<(FrontmatterFormat, String, String), ErrorKind>,
+	auto: bool,
+) -> impl FnMut(PathBuf, Vec<String>, &Path, &Context) -> Result<Box<dyn PlannedTransformation>, ErrorKind>
+{
+	move |src: PathBuf, captures: Vec<String>, dst: &Path, _: &Context| {
+		let _span = trace_span!("templated liquid").entered();
 
 		let content = fs::read_to_string(&src)?;
 
-		let (frontmatter, body) = lang(&content, &src)?;
-
-		let frontmatter =
-			from_str::<Frontmatter>(&frontmatter).map_err(LiquidErrorKind::FrontmatterParsing)?;
-
-		let template = if let Some(template) = frontmatter.template {
-			with_added_extension_but_stable(
-				&if template.local {
-					if let Some(path) = template.path {
-						if path.is_absolute() {
-							return Err(LiquidErrorKind::FrontmatterAbsoluteLocalPath(path).into());
-						}
-
-						src.parent().unwrap().join(path)
-					} else {
-						src.with_extension("")
-					}
-				} else if let Some(path) = template.path {
-					path
-				} else {
-					default_template.clone()
-				},
-				"liquid",
-			)
-		} else {
-			default_template.clone()
-		};
+		let (format, frontmatter, body) = lang(&content, &src)?;
+
+		let frontmatter = parse_frontmatter(format, &frontmatter).map_err(|err| {
+			LiquidErrorKind::FrontmatterParsing {
+				label: err.label,
+				err: err.err,
+				path: src.clone(),
+				frontmatter: frontmatter.clone(),
+			}
+		})?;
+
+		if liquid
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.drafts
+			.skips(&frontmatter)
+		{
+			debug!(?src, "skipped (draft)");
+			return Ok(Box::new(()));
+		}
+
+		if auto && frontmatter.template.is_none() {
+			return Ok(Box::new(body));
+		}
+
+		let frontmatter_globals = ::liquid::model::to_object(&frontmatter.table)
+			.map_err(LiquidErrorKind::FrontmatterConversion)?;
+
+		let liquid = &mut *liquid.lock().unwrap_or_else(PoisonError::into_inner);
+
+		let template = resolve_template(
+			&src,
+			&captures,
+			frontmatter.template,
+			&mut default_template,
+			&liquid.template_extension,
+		)
+		.map_err(LiquidErrorKind::from)?;
+
+		let measured = liquid
+			.reading_time
+			.map(|reading_time| reading_time.measure(&body));
+
+		let mut globals = globals(src, dst, liquid.url(dst), frontmatter_globals, body);
+		Liquid::insert_reading_time(&mut globals, measured);
 
 		Ok(Box::new(LiquidPlan {
-			template: liquid.borrow_mut().parse(&template)?,
-			globals: globals(src, frontmatter.props, body),
+			template: liquid.parse(&template)?,
+			template_path: template,
+			source: None,
+			globals,
+			buffer_capacity: liquid.buffer_capacity,
 		}))
 	}
 }
 
 /// compile liquid templates standalone
 ///
-/// - `liquid` - a shared cell of the liquid parser instance
+/// - `liquid` - a shared lock of the liquid parser instance
 /// - `globals` - the globals to use in templating
-///   - takes the source file path
+///   - takes the source file path, the output file path (produced by the rule's `dst`), and the public url of the output file (see [`Liquid::output_root`])
 ///   - returns the globals
 ///   
 ///   if you don't have a purpose for this, you should probably return [`Default::default`]
 pub fn create_standalone(
-	liquid: Rc<RefCell<Liquid>>,
-	mut globals: impl for<'a> FnMut(PathBuf) -> Object,
-) -> impl FnMut(PathBuf, Vec<String>) -> Result<Box<dyn PlannedTransformation>, ErrorKind> {
-	move |src: PathBuf, _| {
+	liquid: Arc<Mutex<Liquid>>,
+	mut globals: impl for<'a> FnMut(PathBuf, &'a Path, Option<String>) -> Object,
+) -> impl FnMut(PathBuf, Vec<String>, &Path, &Context) -> Result<Box<dyn PlannedTransformation>, ErrorKind>
+{
+	move |src: PathBuf, _, dst: &Path, _: &Context| {
 		let _span = trace_span!("standalone liquid").entered();
 
+		let liquid = &mut *liquid.lock().unwrap_or_else(PoisonError::into_inner);
+
+		let globals = globals(src.clone(), dst, liquid.url(dst));
+
 		Ok(Box::new(LiquidPlan {
-			template: liquid.borrow_mut().parse(&src)?,
-			globals: globals(src),
+			template: liquid.parse(&src)?,
+			template_path: src,
+			source: None,
+			globals,
+			buffer_capacity: liquid.buffer_capacity,
 		}))
 	}
 }
@@ -216,14 +505,53 @@ pub enum LiquidErrorKind {
 	LiquidParsing(#[source] ::liquid::Error, PathBuf, #[source_code] String),
 
 	/// template rendering failed
-	#[error("template rendering failed for {}", .1.to_str().unwrap())]
-	#[diagnostic(code(dollgen::liquid::template_parse_failed))]
-	LiquidRendering(#[source] ::liquid::Error, PathBuf),
+	#[error("template rendering failed{} (using template {})", .dst.as_ref().map(|dst| format!(" for {}", dst.display())).unwrap_or_default(), .template.to_str().unwrap())]
+	#[diagnostic(code(dollgen::liquid::template_render_failed))]
+	LiquidRendering {
+		/// the error
+		#[source]
+		err: ::liquid::Error,
+		/// the file being rendered to, if rendering to a file
+		dst: Option<PathBuf>,
+		/// the template being rendered
+		template: PathBuf,
+		/// the source of the template
+		#[source_code]
+		source_code: String,
+		/// the tag or expression that failed, if it could be found in the source
+		#[label(collection)]
+		label: Vec<::miette::LabeledSpan>,
+	},
 
 	/// frontmatter parsing failed
-	#[error("frontmatter parsing failed")]
+	#[error("frontmatter parsing failed for {}", .path.display())]
 	#[diagnostic(code(dollgen::liquid::frontmatter_parse_failed))]
-	FrontmatterParsing(#[source] ::toml::de::Error),
+	FrontmatterParsing {
+		/// the parsing error
+		#[source]
+		err: Box<dyn ::std::error::Error + Send + Sync>,
+		/// the source file the frontmatter is from
+		path: PathBuf,
+		/// the frontmatter
+		#[source_code]
+		frontmatter: String,
+		/// where parsing failed, if known
+		#[label(collection)]
+		label: Vec<::miette::LabeledSpan>,
+	},
+
+	/// frontmatter could not be converted to liquid values
+	#[error("frontmatter conversion failed")]
+	#[diagnostic(code(dollgen::liquid::frontmatter_conversion_failed))]
+	FrontmatterConversion(#[source] ::liquid::Error),
+
+	/// data could not be converted to globals (see [`globals_from`])
+	#[error("globals conversion failed")]
+	#[diagnostic(
+		code(dollgen::liquid::globals_conversion_failed),
+		help("globals must serialize to a map (ex: a struct), not a single value or a list")
+	)]
+	GlobalsConversion(#[source] ::liquid::Error),
 
 	/// frontmatter requests a local template, but provides an absolute path
 	#[error("frontmatter requests a local template, but provides an absolute path")]
@@ -232,4 +560,81 @@ pub enum LiquidErrorKind {
 		help("either change to a relative path or remove the local attribute")
 	)]
 	FrontmatterAbsoluteLocalPath(PathBuf),
+
+	/// frontmatter selects a template by name, but no template has that name (or there is no default template)
+	#[error("no template named `{0}`")]
+	#[diagnostic(
+		code(dollgen::liquid::unknown_template_name),
+		help("add a template with this name to the rule's default templates")
+	)]
+	UnknownTemplateName(String),
+
+	/// frontmatter selects a template by path, but none of the search directories have it (see [`SearchDirs`])
+	#[error(
+		"template {} not found in any of {}",
+		.path.display(),
+		.searched.iter().map(|dir| dir.display().to_string()).collect::<Vec<_>>().join(", ")
+	)]
+	#[diagnostic(
+		code(dollgen::liquid::template_not_in_search_dirs),
+		help("check the path in the frontmatter, or set `template.local` if it is relative to the source file")
+	)]
+	TemplateNotInSearchDirs {
+		/// the path from the frontmatter, with the template extension
+		path: PathBuf,
+		/// the directories that were searched, in order
+		searched: Vec<PathBuf>,
+	},
+}
+
+impl From<ResolveError> for LiquidErrorKind {
+	fn from(err: ResolveError) -> Self {
+		match err {
+			ResolveError::AbsoluteLocalPath(path) => Self::FrontmatterAbsoluteLocalPath(path),
+			ResolveError::UnknownName(name) => Self::UnknownTemplateName(name),
+			ResolveError::NotFound { path, searched } => {
+				Self::TemplateNotInSearchDirs { path, searched }
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use {
+		super::*,
+		::liquid::ParserBuilder,
+		::std::{env, process},
+	};
+
+	#[test]
+	fn body_changes_identity() {
+		let root = env::temp_dir().join(format!("dollgen-liquid-identity-{}", process::id()));
+		fs::create_dir_all(&root).unwrap();
+		let template = root.join("page.liquid");
+		let src = root.join("page.txt");
+		fs::write(&template, "{{ body }}").unwrap();
+
+		let liquid = Liquid::new(ParserBuilder::new().build().unwrap());
+		let mut create = create_templated(
+			template,
+			liquid,
+			default_globals,
+			|content: &str, _: &Path| {
+				Ok((FrontmatterFormat::Toml, String::new(), content.to_string()))
+			},
+		);
+		let mut identity = |body: &str| {
+			fs::write(&src, body).unwrap();
+			let dst = root.join("page.html");
+			let data = create(src.clone(), Vec::new(), &dst, &Context::new()).unwrap();
+			crate::Plan { dst, data }.identity().unwrap()
+		};
+
+		let (first, same, edited) = (identity("hello"), identity("hello"), identity("goodbye"));
+		fs::remove_dir_all(&root).unwrap();
+
+		assert_eq!(first, same);
+		assert_ne!(first, edited);
+	}
 }
@@ -0,0 +1,32 @@
+//! sources of liquid partials (the templates used by `{% include %}`), for [`ParserBuilder::partials`](::liquid::ParserBuilder::partials)
+
+use {
+	::liquid::partials::PartialSource,
+	::std::{borrow::Cow, collections::BTreeSet},
+};
+
+/// layers several partial sources, where the first source that has a partial wins
+///
+/// ex: a project's own partials over built-in defaults, `CompositePartialSource(vec![Box::new(project), Box::new(defaults)])`
+#[derive(Debug, Default)]
+pub struct CompositePartialSource(pub Vec<Box<dyn PartialSource + Send + Sync>>);
+
+impl PartialSource for CompositePartialSource {
+	fn contains(&self, name: &str) -> bool {
+		self.0.iter().any(|source| source.contains(name))
+	}
+
+	fn names(&self) -> Vec<&str> {
+		let mut seen = BTreeSet::new();
+
+		self.0
+			.iter()
+			.flat_map(|source| source.names())
+			.filter(|name| seen.insert(*name))
+			.collect()
+	}
+
+	fn try_get<'a>(&'a self, name: &str) -> Option<Cow<'a, str>> {
+		self.0.iter().find_map(|source| source.try_get(name))
+	}
+}
@@ -0,0 +1,315 @@
+//! a tiny local server for authoring, which serves the output directory and reloads connected browsers after a rebuild
+//!
+//! served `.html` files have a snippet injected, which connects back to the server over a websocket and reloads the page when told to
+//!
+//! start it with [`LiveReload::serve`] (ex: `LiveReload::serve("127.0.0.1:8080", "deploy")`), and call [`LiveReload::reload`] whenever a rebuild succeeds
+//!
+//! this is meant for local development only, it is not hardened for serving the public
+//!
+//! requires `livereload` feature
+
+use {
+	crate::{util::normalize_lexically, ErrorKind},
+	::base64::{engine::general_purpose::STANDARD, Engine},
+	::std::{
+		fs,
+		io::{self, BufRead, BufReader, Write},
+		net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+		path::{Component, Path, PathBuf},
+		sync::{Arc, Mutex, PoisonError},
+		thread,
+	},
+	::tracing::{debug, error, trace},
+};
+
+/// the path the injected snippet connects to, as a literal so that it can be `concat!`ed into [`SNIPPET`]
+macro_rules! socket_path {
+	() => {
+		"/__dollgen/livereload"
+	};
+}
+
+/// the path the injected snippet connects to
+const SOCKET_PATH: &str = socket_path!();
+
+/// injected into served `.html` files
+const SNIPPET: &str = concat!(
+	"<script>new WebSocket(`ws://${location.host}",
+	socket_path!(),
+	"`).onmessage = () => location.reload();</script>"
+);
+
+/// a running livereload server
+#[derive(Debug)]
+pub struct LiveReload {
+	addr: SocketAddr,
+	clients: Arc<Mutex<Vec<TcpStream>>>,
+}
+
+impl LiveReload {
+	/// serve `output_root` on `addr` (ex: `127.0.0.1:8080`), on a background thread
+	pub fn serve(
+		addr: impl ToSocketAddrs,
+		output_root: impl Into<PathBuf>,
+	) -> Result<Self, ErrorKind> {
+		let listener = TcpListener::bind(addr)?;
+		let addr = listener.local_addr()?;
+		let output_root = Arc::new(output_root.into());
+		let clients = Arc::new(Mutex::new(Vec::new()));
+
+		debug!(%addr, "serving");
+
+		thread::spawn({
+			let clients = clients.clone();
+			move || {
+				for stream in listener.incoming() {
+					let Ok(stream) = stream else {
+						continue;
+					};
+
+					let output_root = output_root.clone();
+					let clients = clients.clone();
+					thread::spawn(move || {
+						if let Err(err) = handle(stream, &output_root, &clients) {
+							error!(%err, "failed to handle request");
+						}
+					});
+				}
+			}
+		});
+
+		Ok(Self { addr, clients })
+	}
+
+	/// the address being served on
+	#[must_use]
+	pub fn local_addr(&self) -> SocketAddr {
+		self.addr
+	}
+
+	/// tell every connected browser to reload, call this after a successful rebuild
+	pub fn reload(&self) {
+		let mut clients = self.clients.lock().unwrap_or_else(PoisonError::into_inner);
+
+		debug!(clients = clients.len(), "reloading");
+
+		// a single unmasked text frame, clients that fail to receive it have disconnected
+		clients.retain_mut(|client| client.write_all(b"\x81\x06reload").is_ok());
+	}
+}
+
+fn handle(
+	mut stream: TcpStream,
+	output_root: &Path,
+	clients: &Mutex<Vec<TcpStream>>,
+) -> Result<(), io::Error> {
+	let mut reader = BufReader::new(&stream);
+
+	let mut request_line = String::new();
+	reader.read_line(&mut request_line)?;
+	let target = request_line.split(' ').nth(1).unwrap_or("/").to_string();
+
+	let mut websocket_key = None;
+	loop {
+		let mut line = String::new();
+		if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+			break;
+		}
+
+		if let Some((name, value)) = line.split_once(':') {
+			if name.trim().eq_ignore_ascii_case("sec-websocket-key") {
+				websocket_key = Some(value.trim().to_string());
+			}
+		}
+	}
+
+	trace!(target, "request");
+
+	let path = target.split(['?', '#']).next().unwrap_or_default();
+
+	if path == SOCKET_PATH {
+		if let Some(key) = websocket_key {
+			let accept = STANDARD.encode(
+				::sha1_smol::Sha1::from(format!("{key}258EAFA5-E914-47DA-95CA-C5AB0DC85B11"))
+					.digest()
+					.bytes(),
+			);
+
+			write!(
+				stream,
+				"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {accept}\r\n\r\n"
+			)?;
+
+			clients
+				.lock()
+				.unwrap_or_else(PoisonError::into_inner)
+				.push(stream);
+
+			return Ok(());
+		}
+	}
+
+	match resolve(output_root, path) {
+		Some(file) => match fs::read(&file) {
+			Ok(mut content) => {
+				let is_html = file.extension().is_some_and(|ext| ext == "html");
+
+				if is_html {
+					inject(&mut content);
+				}
+
+				respond(&mut stream, "200 OK", content_type(&file), &content)
+			}
+			Err(_) => respond(&mut stream, "404 Not Found", "text/plain", b"not found"),
+		},
+		None => respond(&mut stream, "404 Not Found", "text/plain", b"not found"),
+	}
+}
+
+/// find the file a request path refers to, refusing paths that escape the output root
+fn resolve(output_root: &Path, path: &str) -> Option<PathBuf> {
+	let relative = normalize_lexically(Path::new(&percent_decode(path)?.trim_start_matches('/')));
+
+	if relative
+		.components()
+		.any(|component| !matches!(component, Component::Normal(_)))
+	{
+		return None;
+	}
+
+	let file = output_root.join(relative);
+
+	Some(if file.is_dir() {
+		file.join("index.html")
+	} else {
+		file
+	})
+}
+
+fn percent_decode(path: &str) -> Option<String> {
+	let bytes = path.as_bytes();
+	let mut decoded = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+
+	while i < bytes.len() {
+		if bytes[i] == b'%' {
+			decoded.push(u8::from_str_radix(path.get(i + 1..i + 3)?, 16).ok()?);
+			i += 3;
+		} else {
+			decoded.push(bytes[i]);
+			i += 1;
+		}
+	}
+
+	String::from_utf8(decoded).ok()
+}
+
+/// insert the snippet before `</body>`, or at the end if there is none
+fn inject(html: &mut Vec<u8>) {
+	let at = html
+		.windows(7)
+		.rposition(|window| window.eq_ignore_ascii_case(b"</body>"))
+		.unwrap_or(html.len());
+
+	html.splice(at..at, SNIPPET.bytes());
+}
+
+fn content_type(file: &Path) -> &'static str {
+	match file
+		.extension()
+		.and_then(|ext| ext.to_str())
+		.unwrap_or_default()
+	{
+		"html" => "text/html; charset=utf-8",
+		"css" => "text/css; charset=utf-8",
+		"js" | "mjs" => "text/javascript; charset=utf-8",
+		"json" => "application/json",
+		"wasm" => "application/wasm",
+		"svg" => "image/svg+xml",
+		"png" => "image/png",
+		"jpg" | "jpeg" => "image/jpeg",
+		"gif" => "image/gif",
+		"webp" => "image/webp",
+		"ico" => "image/x-icon",
+		"woff2" => "font/woff2",
+		"txt" => "text/plain; charset=utf-8",
+		"xml" => "application/xml",
+		_ => "application/octet-stream",
+	}
+}
+
+fn respond(
+	stream: &mut TcpStream,
+	status: &str,
+	content_type: &str,
+	body: &[u8],
+) -> Result<(), io::Error> {
+	write!(
+		stream,
+		"HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+		body.len()
+	)?;
+	stream.write_all(body)
+}
+
+#[cfg(test)]
+mod tests {
+	use {
+		super::*,
+		::std::{env, process},
+	};
+
+	#[test]
+	fn snippet_connects_to_the_socket() {
+		assert!(SNIPPET.contains(&format!("${{location.host}}{SOCKET_PATH}`")));
+	}
+
+	#[test]
+	fn percent_decodes() {
+		assert_eq!(percent_decode("/a%20b.html").as_deref(), Some("/a b.html"));
+		assert_eq!(percent_decode("/%2e%2e/x").as_deref(), Some("/../x"));
+		assert_eq!(percent_decode("/%zz"), None);
+		assert_eq!(percent_decode("/%2"), None);
+		assert_eq!(percent_decode("/%ff"), None);
+	}
+
+	#[test]
+	fn resolves_inside_the_output_root() {
+		let root = env::temp_dir().join(format!("dollgen-livereload-resolve-{}", process::id()));
+		fs::create_dir_all(root.join("blog")).unwrap();
+
+		let index = resolve(&root, "/blog");
+		let file = resolve(&root, "/blog/post%20one.html");
+		fs::remove_dir_all(&root).unwrap();
+
+		assert_eq!(index, Some(root.join("blog").join("index.html")));
+		assert_eq!(file, Some(root.join("blog").join("post one.html")));
+	}
+
+	#[test]
+	fn refuses_traversal() {
+		let root = Path::new("deploy");
+
+		assert_eq!(resolve(root, "/../secret"), None);
+		assert_eq!(resolve(root, "/%2e%2e/secret"), None);
+		assert_eq!(resolve(root, "/blog/%2E%2E/%2e%2e/secret"), None);
+		assert_eq!(
+			resolve(root, "/blog/../post.html"),
+			Some(root.join("post.html"))
+		);
+	}
+
+	#[test]
+	fn injects_before_body() {
+		let mut html = b"<html><body>hi</BODY></html>".to_vec();
+		inject(&mut html);
+		assert_eq!(
+			html,
+			format!("<html><body>hi{SNIPPET}</BODY></html>").into_bytes()
+		);
+
+		let mut fragment = b"<p>hi</p>".to_vec();
+		inject(&mut fragment);
+		assert_eq!(fragment, format!("<p>hi</p>{SNIPPET}").into_bytes());
+	}
+}
@@ -1,177 +1,607 @@
 //! compile jinja templates, based on input languages
 //!
-//! languages parse their source code and may provide a frontmatter string, which is parsed as TOML:
+//! languages parse their source code and may provide a frontmatter string, which is parsed in the format the language gives (see [`FrontmatterFormat`](crate::lang::FrontmatterFormat)):
 //!
 //! - `template` (optional)
+//!   - if `template` is a string, the template with that name is used (see [`DefaultTemplate`])
 //!   - if `template.local` is true:
 //!     - if `template.path` is defined, that template is used, and the path is assumed to be relative to the directory containing the source file
 //!     - if `template.path` is not defined, it uses the template with the same name as the source file (ex: `page.doll` will use `page.jinja` in the same directory)
 //!   - if `template.local` is false or not specified:
-//!     - if `template.path` is defined, that template is used, and the path is assumed to be relative to the root of the build
+//!     - if `template.path` is defined, that template is used, and the path is assumed to be relative to the root of the build, or to the first of the [`SearchDirs`] that has it
 //!     - if `template.path` is not defined, the default template is used
 //! - `props` (optional)
 //!   - values are fed into the jinja template
+//! - `draft` (optional)
+//!   - if `true`, the source file is skipped, unless drafts are included (see [`Minijinja::drafts`])
+//!
+//! template paths from the frontmatter have [`Minijinja::template_extension`] added to them (ex: `layouts/post` is `layouts/post.jinja`)
+//!
+//! the entire frontmatter (including any other keys) is given to the `globals` closure, so other top-level keys (ex: `title`, `date`) may be used too
+//!
+//! if [`Minijinja::output_root`] is set, the public url of the output file (see [`url`](crate::url)) is given to the `globals` closure, and [`default_globals`] provides it as the global `url`
+//!
+//! if [`Minijinja::reading_time`] is set, the word count and reading time of the content are provided as the globals `word_count` and `reading_time` (see [`ReadingTime`])
 //!
 //! requires `minijinja` feature
 
+pub use crate::templating::{DefaultTemplate, Drafts, ReadingTime, SearchDirs};
 use {
-	crate::{util::with_added_extension_but_stable, ErrorKind, PlannedTransformation},
-	::core::cell::RefCell,
-	::minijinja::{context, Environment, Value},
-	::serde::Deserialize,
+	crate::{
+		lang::FrontmatterFormat,
+		templating::{line_label, parse_frontmatter, resolve_template, ResolveError},
+		util::{hash_bytes, hash_serialized},
+		ClearCache,
+		Context,
+		ErrorKind,
+		PlannedTransformation,
+		Sink,
+	},
+	::core::hash::Hasher,
+	::minijinja::{
+		context,
+		functions::Function,
+		value::{merge_maps, FunctionArgs, FunctionResult},
+		Environment,
+		Value,
+	},
+	::serde::Serialize,
 	::std::{
-		fs::{self, OpenOptions},
+		borrow::Cow,
+		fs,
+		io::{self, BufWriter, Write},
 		path::{Path, PathBuf},
-		rc::Rc,
+		sync::{Arc, PoisonError, RwLock},
 	},
-	::toml::from_str,
-	::tracing::{instrument, trace_span, Level},
+	::tracing::{debug, instrument, trace_span, Level},
 };
 
 pub extern crate minijinja;
 
-#[derive(Debug, Deserialize)]
-struct Frontmatter {
-	pub template: Option<FrontmatterTemplate>,
-	pub props: Option<Value>,
+/// owns a minijinja environment that templates are loaded from
+///
+/// the environment caches the templates it loads, ensure to [`clear_cache`](Minijinja::clear_cache) in case templates change
+///
+/// this is shared behind an [`Arc`]`<`[`RwLock`]`>`, so that plans may render concurrently
+pub struct Minijinja {
+	/// the environment
+	pub env: Environment<'static>,
+	/// the root of the output directory, used to compute the url given to `globals` closures (see [`url`](crate::url))
+	///
+	/// if `None`, they are given no url
+	pub output_root: Option<PathBuf>,
+	/// maps the path of a resolved template to the name it is registered under in the environment
+	///
+	/// if `None`, the path itself is the name, which suits the filesystem loader of [`Minijinja::new`],
+	/// but templates added by name (ex: [`Environment::add_template`]) need a mapping (ex: `|path| path.file_stem().unwrap().to_str().unwrap().to_string()` to use `page` for `templates/page.jinja`)
+	pub template_name: Option<TemplateNameFn>,
+	/// how many bytes of rendered output are buffered before being written, see [`Liquid::buffer_capacity`](crate::liquid::Liquid::buffer_capacity)
+	pub buffer_capacity: usize,
+	/// the extension added to template paths from frontmatter (ex: `jinja`, `j2`, or `html.jinja`)
+	///
+	/// defaults to `jinja`
+	pub template_extension: String,
+	/// how source files marked as drafts in their frontmatter are handled (by default, `draft = true` skips them)
+	pub drafts: Drafts,
+	/// how the reading time globals are computed, see [`ReadingTime`]
+	///
+	/// if `None`, they aren't provided
+	pub reading_time: Option<ReadingTime>,
+}
+
+/// maps the path of a template to its name in the environment (see [`Minijinja::template_name`])
+pub type TemplateNameFn = Box<dyn Fn(&Path) -> String + Send + Sync>;
+
+impl Minijinja {
+	/// create with a fresh environment, which loads templates from the filesystem (relative to the current working directory)
+	#[must_use]
+	pub fn new() -> Arc<RwLock<Self>> {
+		let mut env = Environment::new();
+		env.set_loader(load_template);
+		Self::from_env(env)
+	}
+
+	/// create from an existing environment, leaving its loader as-is
+	#[must_use]
+	pub fn from_env(env: Environment<'static>) -> Arc<RwLock<Self>> {
+		Arc::new(RwLock::new(Self {
+			env,
+			output_root: None,
+			template_name: None,
+			buffer_capacity: 8 * 1024,
+			template_extension: "jinja".to_string(),
+			drafts: Drafts::default(),
+			reading_time: None,
+		}))
+	}
+
+	/// add a filter to the environment, see [`Environment::add_filter`]
+	pub fn add_filter<N, F, Rv, Args>(&mut self, name: N, f: F) -> &mut Self
+	where
+		N: Into<Cow<'static, str>>,
+		F: Function<Rv, Args> + for<'a> Function<Rv, <Args as FunctionArgs<'a>>::Output>,
+		Rv: FunctionResult,
+		Args: for<'a> FunctionArgs<'a>,
+	{
+		self.env.add_filter(name, f);
+		self
+	}
+
+	/// add a global function to the environment, see [`Environment::add_function`]
+	pub fn add_function<N, F, Rv, Args>(&mut self, name: N, f: F) -> &mut Self
+	where
+		N: Into<Cow<'static, str>>,
+		F: Function<Rv, Args> + for<'a> Function<Rv, <Args as FunctionArgs<'a>>::Output>,
+		Rv: FunctionResult,
+		Args: for<'a> FunctionArgs<'a>,
+	{
+		self.env.add_function(name, f);
+		self
+	}
+
+	/// add a global variable to the environment, see [`Environment::add_global`]
+	pub fn add_global<N, V>(&mut self, name: N, value: V) -> &mut Self
+	where
+		N: Into<Cow<'static, str>>,
+		V: Into<Value>,
+	{
+		self.env.add_global(name, value);
+		self
+	}
+
+	/// clear the cache
+	pub fn clear_cache(&mut self) {
+		self.env.clear_templates();
+	}
+
+	/// the name a template is loaded by, see [`Minijinja::template_name`]
+	pub fn name_of(&self, path: &Path) -> Result<String, ErrorKind> {
+		match &self.template_name {
+			Some(template_name) => Ok(template_name(path)),
+			None => path
+				.to_str()
+				.map(str::to_string)
+				.ok_or(ErrorKind::NonUTF8PathCharacters),
+		}
+	}
+
+	/// load and parse every template in `paths` up front, see [`Liquid::precompile`](crate::liquid::Liquid::precompile)
+	pub fn precompile(&self, paths: &[&Path]) -> Result<(), ErrorKind> {
+		let errors = paths
+			.iter()
+			.filter_map(|path| {
+				self.name_of(path)
+					.and_then(|name| self.validate(&name))
+					.err()
+			})
+			.collect::<Vec<_>>();
+
+		if errors.is_empty() {
+			Ok(())
+		} else {
+			Err(ErrorKind::Multiple(errors))
+		}
+	}
+
+	/// load and parse a template, so that a missing or malformed template is reported while planning rather than when rendering
+	pub fn validate(&self, name: &str) -> Result<(), ErrorKind> {
+		match self.env.get_template(name) {
+			Ok(_) => Ok(()),
+			Err(err) if err.kind() == ::minijinja::ErrorKind::TemplateNotFound => {
+				Err(MinijinjaErrorKind::TemplateNotFound(err, PathBuf::from(name)).into())
+			}
+			Err(err) => Err(MinijinjaErrorKind::MinijinjaParsing(
+				err,
+				PathBuf::from(name),
+				fs::read_to_string(name).unwrap_or_default(),
+			)
+			.into()),
+		}
+	}
+
+	/// the public url of `dst`, if there is an output root
+	fn url(&self, dst: &Path) -> Option<String> {
+		self.output_root
+			.as_deref()
+			.and_then(|root| crate::url(dst, root))
+	}
+
+	/// provide the globals measured by [`ReadingTime`], if any
+	fn with_reading_time(globals: Value, measured: Option<(usize, usize)>) -> Value {
+		match measured {
+			// earlier maps take precedence
+			Some((words, minutes)) => merge_maps([
+				globals,
+				context! { word_count => words, reading_time => minutes },
+			]),
+			None => globals,
+		}
+	}
 }
 
-#[derive(Debug, Deserialize)]
-struct FrontmatterTemplate {
-	pub path: Option<PathBuf>,
-	#[serde(default)]
-	pub local: bool,
+/// load a template from the filesystem, where only a missing file means the template doesn't exist
+fn load_template(name: &str) -> Result<Option<String>, ::minijinja::Error> {
+	match fs::read_to_string(name) {
+		Ok(source) => Ok(Some(source)),
+		Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+		Err(err) => Err(::minijinja::Error::new(
+			::minijinja::ErrorKind::InvalidOperation,
+			format!("could not read template `{name}`"),
+		)
+		.with_source(err)),
+	}
 }
 
-/// the default globals for [`create_templated`], which passes `props` as the global `props` and `body` as the global `body`
-pub fn default_globals(_: PathBuf, props: Option<Value>, body: String) -> Value {
+impl ClearCache for RwLock<Minijinja> {
+	fn clear_cache(&self) {
+		self.write()
+			.unwrap_or_else(PoisonError::into_inner)
+			.clear_cache();
+	}
+}
+
+/// the default globals for [`create_templated`], which passes `props` from the frontmatter as the global `props`, the entire frontmatter as the global `frontmatter`, `body` as the global `body`, and the url as the global `url` (`none` if there isn't one)
+#[allow(
+	clippy::needless_pass_by_value,
+	reason = "must match the signature of the `globals` closure"
+)]
+pub fn default_globals(
+	_: PathBuf,
+	_: &Path,
+	url: Option<String>,
+	frontmatter: Value,
+	body: String,
+) -> Value {
 	context! {
-		props => props.unwrap_or_default(),
+		props => frontmatter.get_attr("props").ok().filter(|props| !props.is_undefined()).unwrap_or_default(),
+		frontmatter => frontmatter,
 		body => body,
+		url => url,
 	}
 }
 
+/// convert serializable data into globals with [`Value::from_serialize`], see [`liquid::globals_from`](crate::liquid::globals_from)
+#[must_use]
+pub fn globals_from<T: Serialize>(value: &T) -> Value {
+	Value::from_serialize(value)
+}
+
 /// a plan to render a jinja template
 #[::tyfling::debug(.globals)]
 pub struct MinijinjaPlan {
 	/// environment to use
-	pub env: Rc<RefCell<Environment<'static>>>,
+	pub minijinja: Arc<RwLock<Minijinja>>,
 	/// template name
+	///
+	/// for templates parsed from a string, this is only the name used in errors (see [`MinijinjaPlan::from_source`])
 	pub template: String,
+	/// the source of the template, if it was parsed from a string rather than loaded from the environment
+	pub source: Option<Arc<str>>,
 	/// the globals
 	pub globals: Value,
 }
 
-impl PlannedTransformation for MinijinjaPlan {
-	#[instrument(skip(self), name = "render jinja template", level = Level::DEBUG)]
-	fn execute(self: Box<Self>, dst: PathBuf) -> Result<(), ErrorKind> {
-		self.env
-			.borrow()
-			.get_template(&self.template)
-			.map_err(|err| {
-				ErrorKind::MinijinjaIntegration(MinijinjaErrorKind::MinijinjaRendering(
-					err,
-					dst.clone(),
-				))
-			})?
-			.render_to_write(
-				&self.globals,
-				&mut OpenOptions::new()
-					.create(true)
-					.write(true)
-					.truncate(true)
-					.append(false)
-					.read(false)
-					.open(&dst)?,
-			)
+impl MinijinjaPlan {
+	/// plan to render a template file against globals directly, without a source file (ex: for generated index or tag pages, added with [`Plan::new`](crate::Plan::new))
+	///
+	/// unlike [`create_templated`], no url is computed, so add a `url` to `globals` if templates need it
+	pub fn new(
+		minijinja: Arc<RwLock<Minijinja>>,
+		template_path: &Path,
+		globals: Value,
+	) -> Result<Self, ErrorKind> {
+		let template = {
+			let minijinja = minijinja.read().unwrap_or_else(PoisonError::into_inner);
+			let template = minijinja.name_of(template_path)?;
+			minijinja.validate(&template)?;
+			template
+		};
+
+		Ok(Self {
+			minijinja,
+			template,
+			source: None,
+			globals,
+		})
+	}
+
+	/// plan to render a template from a string against globals directly, without a source file or template file
+	///
+	/// - `name` - the name used in errors (ex: `tag-index`)
+	/// - `source` - the template, which may extend or include templates from the environment
+	///
+	/// the template is kept by the plan rather than added to the environment, so plans with the same name don't replace each other, and other templates can't load it
+	pub fn from_source(
+		minijinja: Arc<RwLock<Minijinja>>,
+		name: &str,
+		source: impl Into<Arc<str>>,
+		globals: Value,
+	) -> Result<Self, ErrorKind> {
+		let source = source.into();
+
+		minijinja
+			.read()
+			.unwrap_or_else(PoisonError::into_inner)
+			.env
+			.template_from_named_str(name, &source)
 			.map_err(|err| {
-				ErrorKind::MinijinjaIntegration(MinijinjaErrorKind::MinijinjaRendering(err, dst))
+				MinijinjaErrorKind::MinijinjaParsing(err, PathBuf::from(name), source.to_string())
 			})?;
 
+		Ok(Self {
+			minijinja,
+			template: name.to_string(),
+			source: Some(source),
+			globals,
+		})
+	}
+
+	/// render the template to bytes, see [`LiquidPlan::render_to_bytes`](crate::liquid::LiquidPlan::render_to_bytes)
+	pub fn render_to_bytes(&self) -> Result<Vec<u8>, ErrorKind> {
+		let mut out = Vec::new();
+		self.render(&mut out, None)?;
+		Ok(out)
+	}
+
+	/// render the template to a string, see [`LiquidPlan::render_to_string`](crate::liquid::LiquidPlan::render_to_string)
+	pub fn render_to_string(&self) -> Result<String, ErrorKind> {
+		String::from_utf8(self.render_to_bytes()?).map_err(|_| ErrorKind::NonUTF8Characters)
+	}
+
+	fn render(&self, out: &mut impl Write, dst: Option<&Path>) -> Result<(), ErrorKind> {
+		let minijinja = self
+			.minijinja
+			.read()
+			.unwrap_or_else(PoisonError::into_inner);
+
+		let rendering_failed = |err: ::minijinja::Error| {
+			// the failing template may not be the one being rendered (ex: an `include`d one)
+			let source_code = match (err.name(), &self.source) {
+				(Some(name), Some(source)) if name == self.template => source.to_string(),
+				(name, _) => name
+					.and_then(|name| minijinja.env.get_template(name).ok())
+					.map(|template| template.source().to_string())
+					.unwrap_or_default(),
+			};
+			let label = err
+				.line()
+				.map(|line| {
+					line_label(
+						&source_code,
+						line,
+						err.detail().unwrap_or("here").to_string(),
+					)
+				})
+				.unwrap_or_default();
+
+			ErrorKind::MinijinjaIntegration(MinijinjaErrorKind::MinijinjaRendering {
+				err,
+				dst: dst.map(Path::to_path_buf),
+				source_code,
+				label,
+			})
+		};
+
+		match &self.source {
+			Some(source) => minijinja
+				.env
+				.template_from_named_str(&self.template, source),
+			None => minijinja.env.get_template(&self.template),
+		}
+		.map_err(rendering_failed)?
+		.render_to_write(&self.globals, out)
+		.map_err(rendering_failed)?;
+
+		Ok(())
+	}
+}
+
+impl PlannedTransformation for MinijinjaPlan {
+	fn execute(self: Box<Self>, dst: PathBuf) -> Result<(), ErrorKind> {
+		self.execute_to(Sink::File(dst))
+	}
+
+	#[instrument(skip(self), name = "render jinja template", level = Level::DEBUG)]
+	fn execute_to(self: Box<Self>, sink: Sink) -> Result<(), ErrorKind> {
+		let buffer_capacity = self
+			.minijinja
+			.read()
+			.unwrap_or_else(PoisonError::into_inner)
+			.buffer_capacity;
+
+		let mut out = BufWriter::with_capacity(buffer_capacity, sink.open()?);
+		self.render(&mut out, sink.path())?;
+		out.flush()?;
 		Ok(())
 	}
+
+	fn inputs(&self) -> Vec<PathBuf> {
+		let minijinja = self
+			.minijinja
+			.read()
+			.unwrap_or_else(PoisonError::into_inner);
+
+		// only known if the template name is its path, and not for templates parsed from a string
+		if self.source.is_none()
+			&& minijinja.template_name.is_none()
+			&& Path::new(&self.template).is_file()
+		{
+			vec![PathBuf::from(&self.template)]
+		} else {
+			Vec::new()
+		}
+	}
+
+	fn fingerprint(&self, state: &mut dyn Hasher) {
+		hash_bytes(state, self.template.as_bytes());
+
+		// templates that aren't inputs (ex: parsed from a string, or added to the environment by name) are hashed by their source
+		if let Some(source) = &self.source {
+			hash_bytes(state, source.as_bytes());
+		} else if self.inputs().is_empty() {
+			let minijinja = self
+				.minijinja
+				.read()
+				.unwrap_or_else(PoisonError::into_inner);
+			if let Ok(template) = minijinja.env.get_template(&self.template) {
+				hash_bytes(state, template.source().as_bytes());
+			}
+		}
+
+		hash_serialized(state, &self.globals);
+	}
 }
 
 /// compile jinja templates + a source language
 ///
-/// - `default_template` - the template to use when not overridden by a given source file
-/// - `env` - a shared cell of the minijinja environment
+/// - `default_template` - the template to use when not overridden by a given source file, may be a path, a closure, or a map of named templates (see [`DefaultTemplate`])
+/// - `minijinja` - a shared lock of the minijinja environment
 /// - `globals` - the globals to use in templating
-///   - takes the source file path, props from frontmatter, and compiled content from `lang`
+///   - takes the source file path, the output file path (produced by the rule's `dst`), the public url of the output file (see [`Minijinja::output_root`]), the entire frontmatter, and compiled content from `lang`
 ///   - returns the globals
 ///   
 ///   if you don't have a purpose for this, you should probably set it to [`default_globals`]
 /// - `lang` - the source language to parse
 ///   - takes the content of the source file
-///   - returns (frontmatter (unparsed), content)
+///   - returns (frontmatter format, frontmatter (unparsed), content)
+///   - wrap it with [`transform_body`](crate::lang::transform_body) to post-process the content before it reaches `globals`
 pub fn create_templated(
-	default_template: PathBuf,
-	env: Rc<RefCell<Environment<'static>>>,
-	mut globals: impl for<'a> FnMut(PathBuf, Option<Value>, String) -> Value,
-	mut lang: impl for<'a> FnMut(&'a str, &'a Path) -> Result<(String, String), ErrorKind>,
-) -> impl FnMut(PathBuf, Vec<String>) -> Result<Box<dyn PlannedTransformation>, ErrorKind> {
-	move |src: PathBuf, _| {
-		let _span = trace_span!("templated minijinja", ?default_template).entered();
+	default_template: impl DefaultTemplate,
+	minijinja: Arc<RwLock<Minijinja>>,
+	globals: impl for<'a> FnMut(PathBuf, &'a Path, Option<String>, Value, String) -> Value,
+	lang: impl for<'a> FnMut(
+		&'a str,
+		&'a Path,
+	) -> Result<(FrontmatterFormat, String, String), ErrorKind>,
+) -> impl FnMut(PathBuf, Vec<String>, &Path, &Context) -> Result<Box<dyn PlannedTransformation>, ErrorKind>
+{
+	create(default_template, minijinja, globals, lang, false)
+}
+
+/// compile jinja templates + a source language, but only for source files whose frontmatter selects a `template`
+///
+/// source files that select a template behave exactly as in [`create_templated`],
+/// while those that don't have the content from `lang` written as-is, without any template
+///
+/// arguments are the same as [`create_templated`]
+pub fn create_auto(
+	default_template: impl DefaultTemplate,
+	minijinja: Arc<RwLock<Minijinja>>,
+	globals: impl for<'a> FnMut(PathBuf, &'a Path, Option<String>, Value, String) -> Value,
+	lang: impl for<'a> FnMut(
+		&'a str,
+		&'a Path,
+	) -> Result<(FrontmatterFormat, String, String), ErrorKind>,
+) -> impl FnMut(PathBuf, Vec<String>, &Path, &Context) -> Result<Box<dyn PlannedTransformation>, ErrorKind>
+{
+	create(default_template, minijinja, globals, lang, true)
+}
+
+fn create(
+	mut default_template: impl DefaultTemplate,
+	minijinja: Arc<RwLock<Minijinja>>,
+	mut globals: impl for<'a> FnMut(PathBuf, &'a Path, Option<String>, Value, String) -> Value,
+	mut lang: impl for<'a> FnMut(
+		&'a str,
+		&'a Path,
+	) -> Result<(FrontmatterFormat, String, String), ErrorKind>,
+	auto: bool,
+) -> impl FnMut(PathBuf, Vec<String>, &Path, &Context) -> Result<Box<dyn PlannedTransformation>, ErrorKind>
+{
+	move |src: PathBuf, captures: Vec<String>, dst: &Path, _: &Context| {
+		let _span = trace_span!("templated minijinja").entered();
 
 		let content = fs::read_to_string(&src)?;
 
-		let (frontmatter, body) = lang(&content, &src)?;
-
-		let frontmatter = from_str::<Frontmatter>(&frontmatter)
-			.map_err(MinijinjaErrorKind::FrontmatterParsing)?;
-
-		let template = if let Some(template) = frontmatter.template {
-			with_added_extension_but_stable(
-				&if template.local {
-					if let Some(path) = template.path {
-						if path.is_absolute() {
-							return Err(
-								MinijinjaErrorKind::FrontmatterAbsoluteLocalPath(path).into()
-							);
-						}
-
-						src.parent().unwrap().join(path)
-					} else {
-						src.with_extension("")
-					}
-				} else if let Some(path) = template.path {
-					path
-				} else {
-					default_template.clone()
-				},
-				"jinja",
+		let (format, frontmatter, body) = lang(&content, &src)?;
+
+		let frontmatter = parse_frontmatter(format, &frontmatter).map_err(|err| {
+			MinijinjaErrorKind::FrontmatterParsing {
+				label: err.label,
+				err: err.err,
+				path: src.clone(),
+				frontmatter: frontmatter.clone(),
+			}
+		})?;
+
+		if minijinja
+			.read()
+			.unwrap_or_else(PoisonError::into_inner)
+			.drafts
+			.skips(&frontmatter)
+		{
+			debug!(?src, "skipped (draft)");
+			return Ok(Box::new(()));
+		}
+
+		let frontmatter_globals = Value::from_serialize(&frontmatter.table);
+
+		if auto && frontmatter.template.is_none() {
+			return Ok(Box::new(body));
+		}
+
+		// the lock is released before `globals` runs, so that it may modify the environment
+		let (template, url, measured) = {
+			let minijinja = minijinja.read().unwrap_or_else(PoisonError::into_inner);
+			let template = resolve_template(
+				&src,
+				&captures,
+				frontmatter.template,
+				&mut default_template,
+				&minijinja.template_extension,
 			)
-		} else {
-			default_template.clone()
+			.map_err(MinijinjaErrorKind::from)?;
+			let template = minijinja.name_of(&template)?;
+			minijinja.validate(&template)?;
+			let measured = minijinja
+				.reading_time
+				.map(|reading_time| reading_time.measure(&body));
+			(template, minijinja.url(dst), measured)
 		};
+		let globals = Minijinja::with_reading_time(
+			globals(src, dst, url, frontmatter_globals, body),
+			measured,
+		);
 
 		Ok(Box::new(MinijinjaPlan {
-			env: env.clone(),
-			template: template.to_str().unwrap().to_string(),
-			globals: globals(src, frontmatter.props, body),
+			minijinja: minijinja.clone(),
+			template,
+			source: None,
+			globals,
 		}))
 	}
 }
 
 /// compile jinja templates standalone
 ///
-/// - `env` - a shared cell of the minijinja environment
+/// - `minijinja` - a shared lock of the minijinja environment
 /// - `globals` - the globals to use in templating
-///   - takes the source file path
+///   - takes the source file path, the output file path (produced by the rule's `dst`), and the public url of the output file (see [`Minijinja::output_root`])
 ///   - returns the globals
 ///   
 ///   if you don't have a purpose for this, you should probably return [`Default::default`]
 pub fn create_standalone(
-	env: Rc<RefCell<Environment<'static>>>,
-	mut globals: impl for<'a> FnMut(PathBuf) -> Value,
-) -> impl FnMut(PathBuf, Vec<String>) -> Result<Box<dyn PlannedTransformation>, ErrorKind> {
-	move |src: PathBuf, _| {
+	minijinja: Arc<RwLock<Minijinja>>,
+	mut globals: impl for<'a> FnMut(PathBuf, &'a Path, Option<String>) -> Value,
+) -> impl FnMut(PathBuf, Vec<String>, &Path, &Context) -> Result<Box<dyn PlannedTransformation>, ErrorKind>
+{
+	move |src: PathBuf, _, dst: &Path, _: &Context| {
 		let _span = trace_span!("standalone minijinja").entered();
 
+		// the lock is released before `globals` runs, so that it may modify the environment
+		let (template, url) = {
+			let minijinja = minijinja.read().unwrap_or_else(PoisonError::into_inner);
+			let template = minijinja.name_of(&src)?;
+			minijinja.validate(&template)?;
+			(template, minijinja.url(dst))
+		};
+		let globals = globals(src, dst, url);
+
 		Ok(Box::new(MinijinjaPlan {
-			env: env.clone(),
-			template: src.to_str().unwrap().to_string(),
-			globals: globals(src),
+			minijinja: minijinja.clone(),
+			template,
+			source: None,
+			globals,
 		}))
 	}
 }
@@ -184,15 +614,47 @@ pub enum MinijinjaErrorKind {
 	#[diagnostic(code(dollgen::minijinja::template_parse_failed))]
 	MinijinjaParsing(#[source] ::minijinja::Error, PathBuf, #[source_code] String),
 
+	/// template does not exist
+	#[error("template {} not found", .1.to_str().unwrap())]
+	#[diagnostic(
+		code(dollgen::minijinja::template_not_found),
+		help("check the template path in the frontmatter, or the default template of the rule")
+	)]
+	TemplateNotFound(#[source] ::minijinja::Error, PathBuf),
+
 	/// template rendering failed
-	#[error("template rendering failed for {}", .1.to_str().unwrap())]
+	#[error("template rendering failed{}", .dst.as_ref().map(|dst| format!(" for {}", dst.display())).unwrap_or_default())]
 	#[diagnostic(code(dollgen::minijinja::template_parse_failed))]
-	MinijinjaRendering(#[source] ::minijinja::Error, PathBuf),
+	MinijinjaRendering {
+		/// the error
+		#[source]
+		err: ::minijinja::Error,
+		/// the file being rendered to, if rendering to a file
+		dst: Option<PathBuf>,
+		/// the source of the template that failed, empty if unknown
+		#[source_code]
+		source_code: String,
+		/// the line that failed, if known
+		#[label(collection)]
+		label: Vec<::miette::LabeledSpan>,
+	},
 
 	/// frontmatter parsing failed
-	#[error("frontmatter parsing failed")]
+	#[error("frontmatter parsing failed for {}", .path.display())]
 	#[diagnostic(code(dollgen::minijinja::frontmatter_parse_failed))]
-	FrontmatterParsing(#[source] ::toml::de::Error),
+	FrontmatterParsing {
+		/// the parsing error
+		#[source]
+		err: Box<dyn ::std::error::Error + Send + Sync>,
+		/// the source file the frontmatter is from
+		path: PathBuf,
+		/// the frontmatter
+		#[source_code]
+		frontmatter: String,
+		/// where parsing failed, if known
+		#[label(collection)]
+		label: Vec<::miette::LabeledSpan>,
+	},
 
 	/// frontmatter requests a local template, but provides an absolute path
 	#[error("frontmatter requests a local template, but provides an absolute path")]
@@ -201,4 +663,147 @@ pub enum MinijinjaErrorKind {
 		help("either change to a relative path or remove the local attribute")
 	)]
 	FrontmatterAbsoluteLocalPath(PathBuf),
+
+	/// frontmatter selects a template by name, but no template has that name (or there is no default template)
+	#[error("no template named `{0}`")]
+	#[diagnostic(
+		code(dollgen::minijinja::unknown_template_name),
+		help("add a template with this name to the rule's default templates")
+	)]
+	UnknownTemplateName(String),
+
+	/// frontmatter selects a template by path, but none of the search directories have it (see [`SearchDirs`])
+	#[error(
+		"template {} not found in any of {}",
+		.path.display(),
+		.searched.iter().map(|dir| dir.display().to_string()).collect::<Vec<_>>().join(", ")
+	)]
+	#[diagnostic(
+		code(dollgen::minijinja::template_not_in_search_dirs),
+		help("check the path in the frontmatter, or set `template.local` if it is relative to the source file")
+	)]
+	TemplateNotInSearchDirs {
+		/// the path from the frontmatter, with the template extension
+		path: PathBuf,
+		/// the directories that were searched, in order
+		searched: Vec<PathBuf>,
+	},
+}
+
+impl From<ResolveError> for MinijinjaErrorKind {
+	fn from(err: ResolveError) -> Self {
+		match err {
+			ResolveError::AbsoluteLocalPath(path) => Self::FrontmatterAbsoluteLocalPath(path),
+			ResolveError::UnknownName(name) => Self::UnknownTemplateName(name),
+			ResolveError::NotFound { path, searched } => {
+				Self::TemplateNotInSearchDirs { path, searched }
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use {
+		super::*,
+		::std::{env, process},
+	};
+
+	#[test]
+	fn unreadable_templates_are_not_missing() {
+		let root = env::temp_dir().join(format!("dollgen-minijinja-loader-{}", process::id()));
+		fs::create_dir_all(&root).unwrap();
+		let minijinja = Minijinja::new();
+		let minijinja = minijinja.read().unwrap();
+
+		let missing = minijinja.validate(root.join("missing.jinja").to_str().unwrap());
+		let unreadable = minijinja.validate(root.to_str().unwrap());
+		fs::remove_dir_all(&root).unwrap();
+
+		assert!(matches!(
+			missing,
+			Err(ErrorKind::MinijinjaIntegration(
+				MinijinjaErrorKind::TemplateNotFound(..)
+			))
+		));
+		match unreadable {
+			Err(ErrorKind::MinijinjaIntegration(MinijinjaErrorKind::MinijinjaParsing(err, ..))) => {
+				assert_eq!(err.kind(), ::minijinja::ErrorKind::InvalidOperation);
+			}
+			result => panic!("expected a read error, got {result:?}"),
+		}
+	}
+
+	#[test]
+	fn globals_may_modify_the_environment() {
+		let root = env::temp_dir().join(format!("dollgen-minijinja-globals-{}", process::id()));
+		fs::create_dir_all(&root).unwrap();
+		let src = root.join("page.jinja");
+		fs::write(&src, "{{ greeting }}").unwrap();
+
+		let minijinja = Minijinja::new();
+		let mut create = create_standalone(minijinja.clone(), {
+			let minijinja = minijinja.clone();
+			move |_, _, _| {
+				minijinja.write().unwrap().add_global("greeting", "hi");
+				Value::default()
+			}
+		});
+		let planned = create(src, Vec::new(), &root.join("page.html"), &Context::new());
+		fs::remove_dir_all(&root).unwrap();
+
+		assert!(planned.is_ok());
+		assert!(minijinja
+			.read()
+			.unwrap()
+			.env
+			.globals()
+			.any(|(name, _)| name == "greeting"));
+	}
+
+	#[test]
+	fn body_changes_identity() {
+		let root = env::temp_dir().join(format!("dollgen-minijinja-identity-{}", process::id()));
+		fs::create_dir_all(&root).unwrap();
+		let template = root.join("page.jinja");
+		let src = root.join("page.txt");
+		fs::write(&template, "{{ body }}").unwrap();
+
+		let minijinja = Minijinja::new();
+		let mut create = create_templated(
+			template,
+			minijinja,
+			default_globals,
+			|content: &str, _: &Path| {
+				Ok((FrontmatterFormat::Toml, String::new(), content.to_string()))
+			},
+		);
+		let mut identity = |body: &str| {
+			fs::write(&src, body).unwrap();
+			let dst = root.join("page.html");
+			let data = create(src.clone(), Vec::new(), &dst, &Context::new()).unwrap();
+			crate::Plan { dst, data }.identity().unwrap()
+		};
+
+		let (first, same, edited) = (identity("hello"), identity("hello"), identity("goodbye"));
+		fs::remove_dir_all(&root).unwrap();
+
+		assert_eq!(first, same);
+		assert_ne!(first, edited);
+	}
+
+	#[test]
+	fn inline_templates_stay_out_of_the_environment() {
+		let minijinja = Minijinja::new();
+		let plan = |source: &str| {
+			MinijinjaPlan::from_source(minijinja.clone(), "index", source, Value::default())
+				.unwrap()
+		};
+
+		let (first, second) = (plan("first"), plan("second"));
+
+		assert_eq!(first.render_to_string().unwrap(), "first");
+		assert_eq!(second.render_to_string().unwrap(), "second");
+		assert!(minijinja.read().unwrap().env.get_template("index").is_err());
+	}
 }
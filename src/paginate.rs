@@ -0,0 +1,120 @@
+//! split a list of items (ex: posts, sorted newest first) into pages, planning one output per page (ex: `blog/index.html`, `blog/page/2/index.html`, ...)
+//!
+//! each page is planned by a closure, which is given the [`Page`] (its items, and links to the pages around it), usually to render a template against it (ex: with `liquid::LiquidPlan::new` and `liquid::globals_from`)
+//!
+//! the plans are not tied to any source file, so add them to the plans of the rules (ex: after collecting the items while planning them)
+
+use {
+	crate::{format, url, ErrorKind, Plan, PlannedTransformation},
+	::std::path::{Path, PathBuf},
+};
+
+/// how items are split into pages, and where each page is written
+#[derive(Clone, Copy, Debug)]
+pub struct Pagination<'a> {
+	/// how many items are on each page, or every item if `0`
+	pub per_page: usize,
+	/// where the first page is written (ex: `deploy/blog/index.html`)
+	pub first: &'a str,
+	/// a [format string](crate::format) for where the rest of the pages are written, where `{0}` is the page number, starting at 2 (ex: `deploy/blog/page/{0}/index.html`)
+	pub rest: &'a str,
+	/// the root of the output directory, used to compute the urls of pages (see [`url`](crate::url))
+	///
+	/// if `None`, pages have no urls
+	pub output_root: Option<&'a Path>,
+}
+
+impl Pagination<'_> {
+	/// where page `number` (starting at 1) is written
+	pub fn dst(&self, number: usize) -> Result<PathBuf, ErrorKind> {
+		if number == 1 {
+			Ok(PathBuf::from(self.first))
+		} else {
+			Ok(PathBuf::from(format(self.rest, &[number.to_string()])?))
+		}
+	}
+
+	/// the public url of page `number` (starting at 1), if there is an output root
+	fn url(&self, number: usize) -> Result<Option<String>, ErrorKind> {
+		Ok(match self.output_root {
+			Some(output_root) => url(&self.dst(number)?, output_root),
+			None => None,
+		})
+	}
+}
+
+/// a single page of items, given to the closure that plans it
+///
+/// serializable (without `dst`) when the `liquid` or `minijinja` feature is enabled, so it may be given to a template as globals
+#[derive(Debug)]
+#[cfg_attr(
+	any(feature = "liquid", feature = "minijinja"),
+	derive(::serde::Serialize)
+)]
+pub struct Page<'a, T> {
+	/// the number of this page, starting at 1
+	pub number: usize,
+	/// how many pages there are
+	pub total: usize,
+	/// the items on this page
+	pub items: &'a [T],
+	/// the url of this page (see [`Pagination::output_root`])
+	pub url: Option<String>,
+	/// the url of the previous page, if there is one
+	pub prev: Option<String>,
+	/// the url of the next page, if there is one
+	pub next: Option<String>,
+	/// where this page is written
+	#[cfg_attr(any(feature = "liquid", feature = "minijinja"), serde(skip))]
+	pub dst: PathBuf,
+}
+
+/// plan a page for every `pagination.per_page` items
+///
+/// - `items` - every item, in the order they should be listed
+/// - `pagination` - how items are split into pages, and where each page is written
+/// - `plan` - plans a page
+///
+/// there is always at least one page, even if there are no items, so that list pages exist before anything is listed
+pub fn paginate<T>(
+	items: &[T],
+	pagination: &Pagination<'_>,
+	mut plan: impl FnMut(Page<'_, T>) -> Result<Box<dyn PlannedTransformation>, ErrorKind>,
+) -> Result<Vec<Plan>, ErrorKind> {
+	let per_page = if pagination.per_page == 0 {
+		items.len().max(1)
+	} else {
+		pagination.per_page
+	};
+	let total = items.len().div_ceil(per_page).max(1);
+
+	(1..=total)
+		.map(|number| {
+			let start = (number - 1) * per_page;
+			let dst = pagination.dst(number)?;
+
+			let page = Page {
+				number,
+				total,
+				items: &items[start..items.len().min(start + per_page)],
+				url: pagination.url(number)?,
+				prev: if number > 1 {
+					pagination.url(number - 1)?
+				} else {
+					None
+				},
+				next: if number < total {
+					pagination.url(number + 1)?
+				} else {
+					None
+				},
+				dst: dst.clone(),
+			};
+
+			Ok(Plan {
+				data: plan(page)?,
+				dst,
+			})
+		})
+		.collect()
+}
@@ -0,0 +1,292 @@
+//! redirect old urls to pages, from the `aliases` in their frontmatter (ex: `aliases = ["/old-post/"]`), which is useful when migrating from another generator
+//!
+//! aliases are collected while planning, by wrapping the `globals` closure of templated rules (see `Redirects::liquid_globals` and `Redirects::minijinja_globals`),
+//! then a plan made by [`Redirects::plan`], added after those rules are planned, writes them in the selected [`RedirectFormat`]
+//!
+//! pages that `create_auto` passes through without a template never reach `globals`, so their aliases aren't collected, add those with [`Redirects::insert`]
+
+use {
+	crate::{
+		filesystem::{Fs, StdFs},
+		url,
+		util::{hash_bytes, normalize_lexically},
+		ErrorKind,
+		Plan,
+		PlannedTransformation,
+	},
+	::core::hash::Hasher,
+	::std::{
+		collections::BTreeMap,
+		io::Write,
+		path::{Path, PathBuf},
+		sync::{Arc, Mutex, PoisonError},
+	},
+	::tracing::{instrument, warn, Level},
+};
+
+/// how redirects are written
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RedirectFormat {
+	/// a single [netlify `_redirects`](https://docs.netlify.com/routing/redirects/) file, written to `_redirects` in the output root
+	Netlify,
+	/// an html page at each alias that immediately redirects with `<meta http-equiv="refresh">`, for hosts without redirect support
+	MetaRefresh,
+}
+
+/// the aliases of every page, shared between the `globals` closures that collect them and the plan that writes them
+#[derive(Clone, Debug)]
+pub struct Redirects {
+	output_root: PathBuf,
+	/// from alias url to (page url, page path)
+	aliases: Arc<Mutex<BTreeMap<String, (String, PathBuf)>>>,
+}
+
+impl Redirects {
+	/// collect redirects to pages in `output_root`, which is also where redirects are written
+	#[must_use]
+	pub fn new(output_root: impl Into<PathBuf>) -> Self {
+		Self {
+			output_root: output_root.into(),
+			aliases: Arc::default(),
+		}
+	}
+
+	/// redirect each of `aliases` (ex: `/old-post/`) to the page at `dst`
+	///
+	/// if `dst` is outside of the output root it has no url, so it can't be redirected to
+	pub fn insert<'a>(&self, dst: &Path, aliases: impl IntoIterator<Item = &'a str>) {
+		let Some(target) = url(dst, &self.output_root) else {
+			warn!(?dst, "not redirecting to a page outside of the output root");
+			return;
+		};
+
+		let mut redirects = self.aliases.lock().unwrap_or_else(PoisonError::into_inner);
+		for alias in aliases {
+			if let Some((previous, _)) =
+				redirects.insert(alias.to_string(), (target.clone(), dst.to_path_buf()))
+			{
+				warn!(
+					alias,
+					previous, target, "alias redirected to several pages, using the last"
+				);
+			}
+		}
+	}
+
+	/// every alias and the url it redirects to
+	#[must_use]
+	pub fn aliases(&self) -> BTreeMap<String, String> {
+		self.aliases
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.iter()
+			.map(|(alias, (target, _))| (alias.clone(), target.clone()))
+			.collect()
+	}
+
+	/// a plan that writes every redirect, which must come after the plans of the rules that collect aliases
+	#[must_use]
+	pub fn plan(&self, format: RedirectFormat) -> Plan {
+		let dst = match format {
+			RedirectFormat::Netlify => self.output_root.join("_redirects"),
+			RedirectFormat::MetaRefresh => self.output_root.clone(),
+		};
+
+		Plan::new(
+			dst,
+			RedirectsPlan {
+				redirects: self.clone(),
+				format,
+			},
+		)
+	}
+
+	/// wrap a liquid `globals` closure, collecting the `aliases` from the frontmatter of every page
+	///
+	/// only pages rendered with a template are seen (see the [module-level documentation](crate::redirects))
+	///
+	/// requires `liquid` feature
+	#[cfg(feature = "liquid")]
+	pub fn liquid_globals(
+		&self,
+		mut globals: impl for<'a> FnMut(
+			PathBuf,
+			&'a Path,
+			Option<String>,
+			::liquid::Object,
+			String,
+		) -> ::liquid::Object,
+	) -> impl for<'a> FnMut(
+		PathBuf,
+		&'a Path,
+		Option<String>,
+		::liquid::Object,
+		String,
+	) -> ::liquid::Object {
+		use ::liquid::model::{Value, ValueView};
+
+		let redirects = self.clone();
+
+		move |src, dst, url, frontmatter, body| {
+			if let Some(Value::Array(aliases)) = frontmatter.get("aliases") {
+				redirects.insert(
+					dst,
+					aliases
+						.iter()
+						.filter_map(Value::as_scalar)
+						.map(|alias| alias.to_kstr().into_string())
+						.collect::<Vec<_>>()
+						.iter()
+						.map(String::as_str),
+				);
+			}
+
+			globals(src, dst, url, frontmatter, body)
+		}
+	}
+
+	/// wrap a minijinja `globals` closure, collecting the `aliases` from the frontmatter of every page
+	///
+	/// only pages rendered with a template are seen (see the [module-level documentation](crate::redirects))
+	///
+	/// requires `minijinja` feature
+	#[cfg(feature = "minijinja")]
+	pub fn minijinja_globals(
+		&self,
+		mut globals: impl for<'a> FnMut(
+			PathBuf,
+			&'a Path,
+			Option<String>,
+			::minijinja::Value,
+			String,
+		) -> ::minijinja::Value,
+	) -> impl for<'a> FnMut(
+		PathBuf,
+		&'a Path,
+		Option<String>,
+		::minijinja::Value,
+		String,
+	) -> ::minijinja::Value {
+		let redirects = self.clone();
+
+		move |src, dst, url, frontmatter, body| {
+			if let Ok(aliases) = frontmatter
+				.get_attr("aliases")
+				.and_then(|aliases| aliases.try_iter())
+			{
+				let aliases = aliases
+					.filter_map(|alias| alias.as_str().map(ToString::to_string))
+					.collect::<Vec<_>>();
+				redirects.insert(dst, aliases.iter().map(String::as_str));
+			}
+
+			globals(src, dst, url, frontmatter, body)
+		}
+	}
+}
+
+/// a plan to write redirects, see [`Redirects::plan`]
+#[derive(Debug)]
+pub struct RedirectsPlan {
+	/// the redirects to write
+	pub redirects: Redirects,
+	/// how to write them
+	pub format: RedirectFormat,
+}
+
+impl RedirectsPlan {
+	/// where the page for `alias` is written, for [`RedirectFormat::MetaRefresh`]
+	fn alias_path(&self, alias: &str, page: &Path) -> Result<PathBuf, ErrorKind> {
+		let output_root = &self.redirects.output_root;
+		let relative = alias.trim_start_matches('/');
+
+		// the root alias (`/`) has no extension, so it gets an index too
+		let path = if !alias.ends_with('/') && Path::new(relative).extension().is_some() {
+			output_root.join(relative)
+		} else {
+			output_root.join(relative).join("index.html")
+		};
+
+		if normalize_lexically(&path).starts_with(normalize_lexically(output_root)) {
+			Ok(path)
+		} else {
+			Err(ErrorKind::PathEscape {
+				src: page.to_path_buf(),
+				dst: path,
+				output_root: output_root.clone(),
+			})
+		}
+	}
+}
+
+impl PlannedTransformation for RedirectsPlan {
+	fn execute(self: Box<Self>, dst: PathBuf) -> Result<(), ErrorKind> {
+		self.execute_in(dst, &StdFs)
+	}
+
+	#[instrument(name = "write redirects", level = Level::DEBUG, skip(fs))]
+	fn execute_in(self: Box<Self>, dst: PathBuf, fs: &dyn Fs) -> Result<(), ErrorKind> {
+		let aliases = self
+			.redirects
+			.aliases
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.clone();
+
+		match self.format {
+			RedirectFormat::Netlify => {
+				let mut out = Vec::new();
+				for (alias, (target, _)) in &aliases {
+					writeln!(out, "{alias} {target} 301")?;
+				}
+				fs.write(&dst, &out)?;
+			}
+			RedirectFormat::MetaRefresh => {
+				for (alias, (target, page)) in &aliases {
+					let path = self.alias_path(alias, page)?;
+					let target = target.replace('&', "&amp;").replace('"', "&quot;");
+
+					fs.create_dir_all(path.parent().unwrap())?;
+					fs.write(
+						&path,
+						format!(
+							"<!doctype html><meta charset=\"utf-8\"><title>redirecting</title><link rel=\"canonical\" href=\"{target}\"><meta http-equiv=\"refresh\" content=\"0; url={target}\">\n"
+						)
+						.as_bytes(),
+					)?;
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	fn outputs(&self, dst: &Path) -> Vec<PathBuf> {
+		match self.format {
+			RedirectFormat::Netlify => vec![dst.to_path_buf()],
+			RedirectFormat::MetaRefresh => self
+				.redirects
+				.aliases
+				.lock()
+				.unwrap_or_else(PoisonError::into_inner)
+				.iter()
+				.filter_map(|(alias, (_, page))| self.alias_path(alias, page).ok())
+				.collect(),
+		}
+	}
+
+	fn fingerprint(&self, state: &mut dyn Hasher) {
+		state.write_u8(self.format as u8);
+
+		let aliases = self
+			.redirects
+			.aliases
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner);
+		state.write_usize(aliases.len());
+		for (alias, (target, _)) in &*aliases {
+			hash_bytes(state, alias.as_bytes());
+			hash_bytes(state, target.as_bytes());
+		}
+	}
+}
@@ -0,0 +1,95 @@
+//! match source files with regexes rather than globs, for paths too complex to capture positionally
+//!
+//! a regex is matched against the path of every file and directory under its root, relative to that root (with `/` separators), and must match the entire path
+//!
+//! every capture group is a capture, in order, so `{0}` is the first group
+//! named groups may also be referenced by name in `dst` (ex: `(?<year>\d{4})/(?<slug>[^/]+)\.doll` with `dist/{year}/{slug}.html`)
+//!
+//! regex includes are given to [`Rule::include_regex`](crate::Rule::include_regex)
+//!
+//! requires `regex` feature
+
+use {
+	crate::{util::walk, ErrorKind},
+	::regex::Regex,
+	::std::path::{Path, PathBuf},
+};
+
+pub extern crate regex;
+
+/// a regex that includes source files under a root directory
+#[derive(Clone, Debug)]
+pub struct RegexInclude {
+	/// the directory to search, matched paths are relative to this
+	pub root: PathBuf,
+	/// the regex, anchored to match the entire relative path
+	pub regex: Regex,
+}
+
+impl RegexInclude {
+	/// create from a root directory and a regex, which is anchored to match the entire relative path
+	pub fn new(root: impl Into<PathBuf>, regex: &str) -> Result<Self, ::regex::Error> {
+		Ok(Self {
+			root: root.into(),
+			regex: Regex::new(&format!("^(?:{regex})$"))?,
+		})
+	}
+
+	/// the names of each capture group, in order (unnamed groups are `None`)
+	pub fn names(&self) -> impl Iterator<Item = Option<&str>> {
+		self.regex.capture_names().skip(1) // skip 0, which is just the entire match
+	}
+
+	/// how many capture groups the regex has
+	#[must_use]
+	pub fn capture_count(&self) -> usize {
+		self.regex.captures_len() - 1
+	}
+
+	/// match a single path, which must be inside of `root`
+	///
+	/// returns the captures, groups that did not participate in the match are empty
+	pub fn captures(&self, path: &Path) -> Result<Option<Vec<String>>, ErrorKind> {
+		let Ok(relative) = path.strip_prefix(&self.root) else {
+			return Ok(None);
+		};
+
+		let mut relative_str = String::new();
+		for component in relative.components() {
+			if !relative_str.is_empty() {
+				relative_str.push('/');
+			}
+			relative_str.push_str(
+				component
+					.as_os_str()
+					.to_str()
+					.ok_or(ErrorKind::NonUTF8PathCharacters)?,
+			);
+		}
+
+		Ok(self.regex.captures(&relative_str).map(|captures| {
+			captures
+				.iter()
+				.skip(1) // skip 0, which is just the entire match
+				.map(|capture| {
+					capture.map_or_else(String::new, |capture| capture.as_str().to_string())
+				})
+				.collect()
+		}))
+	}
+
+	/// walk `root` and match every file and directory under it, in sorted order
+	pub fn matches(&self) -> Result<Vec<(PathBuf, Vec<String>)>, ErrorKind> {
+		let mut paths = Vec::new();
+		walk(&self.root, &mut paths)?;
+
+		let mut matches = Vec::new();
+		for path in paths {
+			if let Some(captures) = self.captures(&path)? {
+				matches.push((path, captures));
+			}
+		}
+
+		Ok(matches)
+	}
+}
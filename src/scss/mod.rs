@@ -3,22 +3,63 @@
 //! requires `scss` feature
 
 use {
-	crate::{ErrorKind, PlannedTransformation},
-	::grass::{from_path, Options},
+	crate::{Context, ErrorKind, PlannedTransformation},
+	::codemap::SpanLoc,
+	::grass::{from_path, Logger, Options},
 	::miette::LabeledSpan,
-	::std::{path::PathBuf, sync::Arc},
-	::tracing::debug_span,
+	::std::{
+		path::{Path, PathBuf},
+		sync::Arc,
+	},
+	::tracing::{debug, debug_span, warn},
 };
 
 pub extern crate grass;
 
+/// forwards `@warn` and `@debug` messages from stylesheets to [`tracing`], rather than printing them to stderr
+///
+/// ex: `Options::default().logger(&TracingLogger)`, or just [`options`]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct TracingLogger;
+
+impl Logger for TracingLogger {
+	fn debug(&self, location: SpanLoc, message: &str) {
+		debug!(
+			file = location.file.name(),
+			line = location.begin.line + 1,
+			"@debug: {message}"
+		);
+	}
+
+	fn warn(&self, location: SpanLoc, message: &str) {
+		warn!(
+			file = location.file.name(),
+			line = location.begin.line + 1,
+			column = location.begin.column + 1,
+			"@warn: {message}"
+		);
+	}
+}
+
+/// the default options, but with `@warn` and `@debug` messages forwarded to [`tracing`] (see [`TracingLogger`])
+#[must_use]
+pub fn options() -> Options<'static> {
+	Options::default().logger(&TracingLogger)
+}
+
 /// compiles scss/sass
 ///
-/// - `options` - the options to compile with
+/// - `options` - the options to compile with, see [`options`] to report `@warn` and `@debug` messages through [`tracing`]
 pub fn create<'a>(
 	options: &'a Options<'a>,
-) -> impl FnMut(PathBuf, Vec<String>) -> Result<Box<dyn PlannedTransformation>, ErrorKind> + 'a {
-	move |src, _| {
+) -> impl FnMut(
+	PathBuf,
+	Vec<String>,
+	&Path,
+	&Context,
+) -> Result<Box<dyn PlannedTransformation>, ErrorKind>
+       + 'a {
+	move |src, _, _: &Path, _: &Context| {
 		let _span = debug_span!("compile scss", ?options).entered();
 
 		Ok(Box::new(from_path(src, &options).map_err(|err| {
@@ -0,0 +1,469 @@
+//! frontmatter handling shared between the templating integrations
+
+use {
+	crate::{lang::FrontmatterFormat, util::with_added_extension_but_stable},
+	::miette::LabeledSpan,
+	::serde::Deserialize,
+	::std::{
+		collections::HashMap,
+		hash::BuildHasher,
+		path::{Path, PathBuf},
+	},
+	::toml::Table,
+};
+
+/// the templates used by source files that don't select one by path in their frontmatter
+///
+/// this is implemented for:
+/// - a [`PathBuf`], which is always used
+/// - closures taking the source file path and the captures from the rule's `include`,
+///   so that the default may vary by path (ex: `|_, captures| format!("templates/{}.liquid", captures[0]).into()` for a different template per section)
+/// - a [`HashMap`] of named templates, where the `default` key is the default, and frontmatter may select any other by name (ex: `template = "amp"`)
+/// - [`SearchDirs`], which wraps any of these with directories to search for templates selected by path
+pub trait DefaultTemplate {
+	/// the default template for a source file, if there is one
+	fn default_template(&mut self, src: &Path, captures: &[String]) -> Option<PathBuf>;
+
+	/// the template named `name`, if there is one
+	fn named_template(&mut self, name: &str) -> Option<PathBuf> {
+		let _ = name;
+		None
+	}
+
+	/// the directories to search, in order, for a template selected by a non-local path (ex: `template.path = "layouts/post"`)
+	///
+	/// if empty, the path is relative to the root of the build
+	fn search_dirs(&self) -> &[PathBuf] {
+		&[]
+	}
+}
+
+impl DefaultTemplate for PathBuf {
+	fn default_template(&mut self, _: &Path, _: &[String]) -> Option<PathBuf> {
+		Some(self.clone())
+	}
+}
+
+impl<F: FnMut(&Path, &[String]) -> PathBuf> DefaultTemplate for F {
+	fn default_template(&mut self, src: &Path, captures: &[String]) -> Option<PathBuf> {
+		Some(self(src, captures))
+	}
+}
+
+impl<S: BuildHasher> DefaultTemplate for HashMap<String, PathBuf, S> {
+	fn default_template(&mut self, _: &Path, _: &[String]) -> Option<PathBuf> {
+		self.get("default").cloned()
+	}
+
+	fn named_template(&mut self, name: &str) -> Option<PathBuf> {
+		self.get(name).cloned()
+	}
+}
+
+/// default templates, with directories to search for templates selected by a non-local path (see [`DefaultTemplate::search_dirs`])
+///
+/// ex: `SearchDirs { dirs: vec!["theme/layouts".into(), "layouts".into()], templates: PathBuf::from("layouts/default.liquid") }`
+#[derive(Clone, Debug)]
+pub struct SearchDirs<T> {
+	/// the directories to search, in order, so earlier directories override later ones (ex: a site's layouts before its theme's)
+	pub dirs: Vec<PathBuf>,
+	/// the default and named templates, which are not searched for
+	pub templates: T,
+}
+
+impl<T: DefaultTemplate> DefaultTemplate for SearchDirs<T> {
+	fn default_template(&mut self, src: &Path, captures: &[String]) -> Option<PathBuf> {
+		self.templates.default_template(src, captures)
+	}
+
+	fn named_template(&mut self, name: &str) -> Option<PathBuf> {
+		self.templates.named_template(name)
+	}
+
+	fn search_dirs(&self) -> &[PathBuf] {
+		&self.dirs
+	}
+}
+
+/// parsed frontmatter
+pub struct Frontmatter {
+	/// the `template` key, which selects the template
+	pub template: Option<FrontmatterTemplate>,
+	/// every key of the frontmatter, including `template`
+	pub table: Table,
+}
+
+/// how source files marked as drafts in their frontmatter (ex: `draft = true`) are handled by the templated integrations
+///
+/// drafts are planned as a [`noop`](crate::noop), so they aren't written at all
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Drafts {
+	/// the frontmatter key that marks a source file as a draft when it is `true`
+	///
+	/// if `None`, nothing is a draft
+	pub key: Option<String>,
+	/// render drafts like any other source file (ex: for local previews)
+	pub include: bool,
+}
+
+impl Default for Drafts {
+	fn default() -> Self {
+		Self {
+			key: Some("draft".to_string()),
+			include: false,
+		}
+	}
+}
+
+impl Drafts {
+	/// whether a source file with this frontmatter should be skipped
+	#[must_use]
+	pub fn skips(&self, frontmatter: &Frontmatter) -> bool {
+		!self.include
+			&& self
+				.key
+				.as_ref()
+				.and_then(|key| frontmatter.table.get(key))
+				.and_then(::toml::Value::as_bool)
+				.unwrap_or(false)
+	}
+}
+
+/// provides the word count and estimated reading time of the content from the language as globals in the templated integrations
+///
+/// - `word_count` - how many words are in the content, not counting html tags
+/// - `reading_time` - how many minutes the content takes to read, rounded up (ex: for "5 min read"), which is only `0` if there are no words
+///
+/// neither is provided if the `globals` closure already provides it
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ReadingTime {
+	/// how many words are read per minute
+	///
+	/// defaults to `200`
+	pub words_per_minute: usize,
+}
+
+impl Default for ReadingTime {
+	fn default() -> Self {
+		Self {
+			words_per_minute: 200,
+		}
+	}
+}
+
+impl ReadingTime {
+	/// the word count and reading time in minutes of some content
+	#[must_use]
+	pub fn measure(&self, content: &str) -> (usize, usize) {
+		let words = word_count(content);
+		(words, words.div_ceil(self.words_per_minute.max(1)))
+	}
+}
+
+/// count the words in some content, skipping html tags (ex: `<p>hello <em>world</em></p>` is 2 words) and entities (ex: `&amp;`)
+#[must_use]
+pub fn word_count(content: &str) -> usize {
+	let mut text = String::with_capacity(content.len());
+	let mut in_tag = false;
+
+	for ch in content.chars() {
+		match ch {
+			'<' => in_tag = true,
+			'>' if in_tag => {
+				in_tag = false;
+				// tags separate words (ex: `a<br>b`)
+				text.push(' ');
+			}
+			_ if !in_tag => text.push(ch),
+			_ => {}
+		}
+	}
+
+	text.split_whitespace()
+		.filter(|word| !(word.starts_with('&') && word.ends_with(';')))
+		.filter(|word| word.chars().any(char::is_alphanumeric))
+		.count()
+}
+
+/// why frontmatter could not be parsed
+pub struct FrontmatterError {
+	/// the error from the parser
+	pub err: Box<dyn ::std::error::Error + Send + Sync>,
+	/// where parsing failed, if the error knows where
+	pub label: Vec<LabeledSpan>,
+}
+
+impl From<::toml::de::Error> for FrontmatterError {
+	fn from(err: ::toml::de::Error) -> Self {
+		Self {
+			label: err
+				.span()
+				.map(|span| {
+					LabeledSpan::new_primary_with_span(Some(err.message().to_string()), span)
+				})
+				.into_iter()
+				.collect(),
+			err: Box::new(err),
+		}
+	}
+}
+
+/// parse frontmatter in the format the language gave
+pub fn parse_frontmatter(
+	format: FrontmatterFormat,
+	src: &str,
+) -> Result<Frontmatter, FrontmatterError> {
+	// an empty document is not an empty table in every format
+	let table = if src.trim().is_empty() {
+		Table::new()
+	} else {
+		match format {
+			FrontmatterFormat::Toml => ::toml::from_str::<Table>(src)?,
+			FrontmatterFormat::Yaml => {
+				::serde_yaml::from_str::<Table>(src).map_err(|err| FrontmatterError {
+					label: err
+						.location()
+						.map(|location| {
+							LabeledSpan::new_primary_with_span(
+								Some(err.to_string()),
+								location.index(),
+							)
+						})
+						.into_iter()
+						.collect(),
+					err: Box::new(err),
+				})?
+			}
+			FrontmatterFormat::Json => {
+				::serde_json::from_str::<Table>(src).map_err(|err| FrontmatterError {
+					label: line_label(src, err.line(), err.to_string()),
+					err: Box::new(err),
+				})?
+			}
+		}
+	};
+
+	Ok(Frontmatter {
+		template: table
+			.get("template")
+			.cloned()
+			.map(::toml::Value::try_into)
+			.transpose()?,
+		table,
+	})
+}
+
+/// a label pointing at a line of a template (1-indexed), if it exists
+pub fn line_label(src: &str, line: usize, message: String) -> Vec<LabeledSpan> {
+	let mut start = 0;
+
+	for (index, text) in src.split_inclusive('\n').enumerate() {
+		if index + 1 == line {
+			return vec![LabeledSpan::new_primary_with_span(
+				Some(message),
+				(start, text.trim_end().len()),
+			)];
+		}
+
+		start += text.len();
+	}
+
+	Vec::new()
+}
+
+/// the `template` key of frontmatter, either the name of a template (ex: `template = "amp"`) or a table
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum FrontmatterTemplate {
+	Named(String),
+	Table {
+		path: Option<PathBuf>,
+		#[serde(default)]
+		local: bool,
+	},
+}
+
+/// why a source file's template could not be resolved
+pub enum ResolveError {
+	/// a local template was given an absolute path
+	AbsoluteLocalPath(PathBuf),
+	/// no template has the name (`default` if no template was selected, and there is no default)
+	UnknownName(String),
+	/// a template path wasn't found in any of the search directories
+	NotFound {
+		/// the path from the frontmatter, with the template extension
+		path: PathBuf,
+		/// the directories that were searched
+		searched: Vec<PathBuf>,
+	},
+}
+
+/// resolve which template a source file uses, based on the `template` from its frontmatter
+pub fn resolve_template(
+	src: &Path,
+	captures: &[String],
+	template: Option<FrontmatterTemplate>,
+	default_template: &mut impl DefaultTemplate,
+	extension: &str,
+) -> Result<PathBuf, ResolveError> {
+	Ok(match template {
+		Some(FrontmatterTemplate::Named(name)) => default_template
+			.named_template(&name)
+			.ok_or(ResolveError::UnknownName(name))?,
+		Some(FrontmatterTemplate::Table {
+			local: true,
+			path: Some(path),
+		}) => {
+			if path.is_absolute() {
+				return Err(ResolveError::AbsoluteLocalPath(path));
+			}
+
+			with_added_extension_but_stable(&src.parent().unwrap().join(path), extension)
+		}
+		Some(FrontmatterTemplate::Table {
+			local: true,
+			path: None,
+		}) => with_added_extension_but_stable(&src.with_extension(""), extension),
+		Some(FrontmatterTemplate::Table {
+			local: false,
+			path: Some(path),
+		}) => {
+			let path = with_added_extension_but_stable(&path, extension);
+			let searched = default_template.search_dirs();
+
+			if searched.is_empty() {
+				path
+			} else {
+				searched
+					.iter()
+					.map(|dir| dir.join(&path))
+					.find(|candidate| candidate.is_file())
+					.ok_or_else(|| ResolveError::NotFound {
+						path,
+						searched: searched.to_vec(),
+					})?
+			}
+		}
+		Some(FrontmatterTemplate::Table {
+			local: false,
+			path: None,
+		})
+		| None => default_template
+			.default_template(src, captures)
+			.ok_or_else(|| ResolveError::UnknownName("default".to_string()))?,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn word_count_plain_text() {
+		assert_eq!(word_count(""), 0);
+		assert_eq!(word_count("hello world"), 2);
+		assert_eq!(word_count("  spread\n\tout  words "), 3);
+	}
+
+	#[test]
+	fn word_count_skips_markup() {
+		assert_eq!(word_count("<p>hello <em>world</em></p>"), 2);
+		// tags separate words
+		assert_eq!(word_count("a<br>b"), 2);
+		assert_eq!(word_count("<a href=\"x y z\">link</a>"), 1);
+		assert_eq!(word_count("salt &amp; pepper"), 2);
+		// punctuation alone isn't a word
+		assert_eq!(word_count("wait - what"), 2);
+	}
+
+	#[test]
+	fn reading_time_rounds_up() {
+		let reading_time = ReadingTime {
+			words_per_minute: 2,
+		};
+
+		assert_eq!(reading_time.measure(""), (0, 0));
+		assert_eq!(reading_time.measure("one two three"), (3, 2));
+		assert_eq!(
+			ReadingTime {
+				words_per_minute: 0
+			}
+			.measure("one"),
+			(1, 1)
+		);
+	}
+
+	fn parsed(format: FrontmatterFormat, src: &str) -> Frontmatter {
+		parse_frontmatter(format, src)
+			.unwrap_or_else(|err| panic!("failed to parse frontmatter: {}", err.err))
+	}
+
+	#[test]
+	fn frontmatter_formats_agree() {
+		for (format, src) in [
+			(
+				FrontmatterFormat::Toml,
+				"title = \"hi\"\ntemplate = \"post\"",
+			),
+			(FrontmatterFormat::Yaml, "title: hi\ntemplate: post"),
+			(
+				FrontmatterFormat::Json,
+				r#"{ "title": "hi", "template": "post" }"#,
+			),
+		] {
+			let frontmatter = parsed(format, src);
+
+			assert_eq!(
+				frontmatter
+					.table
+					.get("title")
+					.and_then(|title| title.as_str()),
+				Some("hi")
+			);
+			assert!(
+				matches!(frontmatter.template, Some(FrontmatterTemplate::Named(name)) if name == "post")
+			);
+		}
+	}
+
+	#[test]
+	fn empty_frontmatter_is_an_empty_table() {
+		for format in [
+			FrontmatterFormat::Toml,
+			FrontmatterFormat::Yaml,
+			FrontmatterFormat::Json,
+		] {
+			let frontmatter = parsed(format, " \n");
+
+			assert!(frontmatter.table.is_empty());
+			assert!(frontmatter.template.is_none());
+		}
+	}
+
+	#[test]
+	fn frontmatter_template_table() {
+		let frontmatter = parsed(
+			FrontmatterFormat::Toml,
+			"template = { path = \"post\", local = true }",
+		);
+
+		assert!(matches!(
+			frontmatter.template,
+			Some(FrontmatterTemplate::Table { path: Some(path), local: true }) if path == Path::new("post")
+		));
+	}
+
+	#[test]
+	fn frontmatter_errors_are_labeled() {
+		for (format, src) in [
+			(FrontmatterFormat::Toml, "title = "),
+			(FrontmatterFormat::Yaml, "title: [hi"),
+			(FrontmatterFormat::Json, "{\n\"title\": }"),
+		] {
+			match parse_frontmatter(format, src) {
+				Ok(_) => panic!("expected {format:?} frontmatter to fail"),
+				Err(err) => assert!(!err.label.is_empty(), "{format:?} error is unlabeled"),
+			}
+		}
+	}
+}
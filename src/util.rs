@@ -1,14 +1,242 @@
-use ::std::{
-	ffi::OsStr,
-	path::{Path, PathBuf},
+use {
+	crate::ErrorKind,
+	::std::{
+		fs,
+		hash::Hasher,
+		path::{Component, Path, PathBuf, MAIN_SEPARATOR, MAIN_SEPARATOR_STR},
+	},
 };
 
-#[allow(unused, reason = "used with some features")]
-pub fn with_added_extension_but_stable(path: &Path, extension: impl AsRef<OsStr>) -> PathBuf {
-	let mut new = path.extension().unwrap_or_default().to_os_string();
-	if path.extension().is_some() {
+/// add an extension after any existing extension, like the unstable `Path::with_added_extension`
+///
+/// - `page` -> `page.liquid`
+/// - `page.tar` -> `page.tar.liquid`
+/// - `page.` -> `page.liquid` (the trailing dot is reused, rather than doubled)
+/// - `.page` -> `.page.liquid` (a dotfile's name is never treated as an extension)
+/// - a leading dot on `extension` is ignored, so `.liquid` is the same as `liquid`
+/// - paths without a file name (ex: `..`, `/`) are returned unchanged
+pub fn with_added_extension_but_stable(path: &Path, extension: &str) -> PathBuf {
+	let Some(file_name) = path.file_name() else {
+		return path.to_path_buf();
+	};
+
+	let extension = extension.strip_prefix('.').unwrap_or(extension);
+
+	let mut new = file_name.to_os_string();
+	if !new.as_encoded_bytes().ends_with(b".") {
 		new.push(".");
 	}
 	new.push(extension);
-	path.with_extension(new)
+
+	path.with_file_name(new)
+}
+
+/// turn a path built from a format string, which always uses `/`, into one that only uses the platform's separator
+///
+/// - on unix, `src/page.html` is unchanged (and `\` is left alone, as it is a valid file name character)
+/// - on windows, `src/page.html` becomes `src\page.html`, as does a mix like `src/docs\page.html`
+pub fn native_separators(path: &str) -> PathBuf {
+	if MAIN_SEPARATOR == '/' {
+		PathBuf::from(path)
+	} else {
+		PathBuf::from(path.replace('/', MAIN_SEPARATOR_STR))
+	}
+}
+
+/// resolve `.` and `..` components without touching the filesystem (so the path doesn't need to exist)
+///
+/// `..` components that would go above the start of a relative path are kept
+pub fn normalize_lexically(path: &Path) -> PathBuf {
+	let mut normalized = PathBuf::new();
+
+	for component in path.components() {
+		match component {
+			Component::CurDir => {}
+			Component::ParentDir => match normalized.components().next_back() {
+				Some(Component::Normal(_)) => {
+					normalized.pop();
+				}
+				Some(Component::RootDir | Component::Prefix(_)) => {}
+				Some(Component::ParentDir | Component::CurDir) | None => {
+					normalized.push(component);
+				}
+			},
+			component => normalized.push(component),
+		}
+	}
+
+	normalized
+}
+
+/// a 64-bit fnv-1a hasher, whose hashes are the same across runs and versions of rust (unlike [`DefaultHasher`](std::hash::DefaultHasher))
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+	fn default() -> Self {
+		Self(0xcbf2_9ce4_8422_2325)
+	}
+}
+
+impl Hasher for StableHasher {
+	fn finish(&self) -> u64 {
+		self.0
+	}
+
+	fn write(&mut self, bytes: &[u8]) {
+		for byte in bytes {
+			self.0 ^= u64::from(*byte);
+			self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+		}
+	}
+}
+
+/// feed `bytes` into `state`, prefixed by their length, so that consecutive values can't run together (ex: `ab` then `c`, and `a` then `bc`)
+pub fn hash_bytes(state: &mut dyn Hasher, bytes: &[u8]) {
+	state.write_usize(bytes.len());
+	state.write(bytes);
+}
+
+/// feed serializable data into `state`, with the keys of maps sorted so that their order (ex: in a `HashMap`) doesn't change the hash
+///
+/// data that can't be serialized feeds the error instead
+#[cfg(any(feature = "data", feature = "liquid", feature = "minijinja"))]
+pub fn hash_serialized(state: &mut dyn Hasher, value: &impl ::serde::Serialize) {
+	use ::serde_json::Value;
+
+	fn hash_value(state: &mut dyn Hasher, value: &Value) {
+		match value {
+			Value::Null => state.write_u8(0),
+			Value::Bool(value) => {
+				state.write_u8(1);
+				state.write_u8((*value).into());
+			}
+			Value::Number(number) => {
+				state.write_u8(2);
+				hash_bytes(state, number.to_string().as_bytes());
+			}
+			Value::String(string) => {
+				state.write_u8(3);
+				hash_bytes(state, string.as_bytes());
+			}
+			Value::Array(values) => {
+				state.write_u8(4);
+				state.write_usize(values.len());
+				for value in values {
+					hash_value(state, value);
+				}
+			}
+			Value::Object(map) => {
+				state.write_u8(5);
+				state.write_usize(map.len());
+				let mut entries = map.iter().collect::<Vec<_>>();
+				entries.sort_by_key(|(key, _)| *key);
+				for (key, value) in entries {
+					hash_bytes(state, key.as_bytes());
+					hash_value(state, value);
+				}
+			}
+		}
+	}
+
+	match ::serde_json::to_value(value) {
+		Ok(value) => hash_value(state, &value),
+		Err(err) => {
+			state.write_u8(6);
+			hash_bytes(state, err.to_string().as_bytes());
+		}
+	}
+}
+
+/// recursively collect every path under `dir`, sorted so that builds are deterministic
+///
+/// symlinked directories are collected but not descended into, so that links can't loop or escape `dir`
+#[allow(unused, reason = "used with some features")]
+pub fn walk(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<(), ErrorKind> {
+	let mut entries = fs::read_dir(dir)?
+		.map(|entry| entry.and_then(|entry| Ok((entry.path(), entry.file_type()?))))
+		.collect::<Result<Vec<_>, _>>()?;
+	entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+	for (path, file_type) in entries {
+		paths.push(path.clone());
+
+		// `file_type` doesn't follow symlinks
+		if file_type.is_dir() {
+			walk(&path, paths)?;
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use {
+		super::*,
+		::std::{env, process},
+	};
+
+	#[test]
+	fn added_extension_without_extension() {
+		assert_eq!(
+			with_added_extension_but_stable(Path::new("dir/page"), "liquid"),
+			Path::new("dir/page.liquid")
+		);
+	}
+
+	#[test]
+	fn added_extension_after_existing_extension() {
+		assert_eq!(
+			with_added_extension_but_stable(Path::new("page.tar"), ".liquid"),
+			Path::new("page.tar.liquid")
+		);
+	}
+
+	#[test]
+	fn added_extension_to_dotfile() {
+		assert_eq!(
+			with_added_extension_but_stable(Path::new(".page"), "liquid"),
+			Path::new(".page.liquid")
+		);
+	}
+
+	#[test]
+	fn native_separators_replace_slashes() {
+		let expected = ["src", "docs", "page.html"].iter().collect::<PathBuf>();
+		assert_eq!(
+			native_separators("src/docs/page.html").as_os_str(),
+			expected.as_os_str()
+		);
+	}
+
+	#[test]
+	fn added_extension_after_trailing_dot() {
+		assert_eq!(
+			with_added_extension_but_stable(Path::new("page."), "liquid"),
+			Path::new("page.liquid")
+		);
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn walk_skips_symlinked_directories() {
+		let root = env::temp_dir().join(format!("dollgen-walk-{}", process::id()));
+		fs::create_dir_all(root.join("dir")).unwrap();
+		fs::write(root.join("dir/file"), "").unwrap();
+		std::os::unix::fs::symlink(&root, root.join("dir/loop")).unwrap();
+
+		let mut paths = Vec::new();
+		let walked = walk(&root, &mut paths);
+		fs::remove_dir_all(&root).unwrap();
+
+		assert!(walked.is_ok());
+		assert_eq!(
+			paths,
+			[
+				root.join("dir"),
+				root.join("dir/file"),
+				root.join("dir/loop")
+			]
+		);
+	}
 }
@@ -3,10 +3,25 @@
 //! requires `wasm` feature
 
 use {
-	crate::{format, ErrorKind, PlannedTransformation},
+	crate::{
+		capture_count,
+		filesystem::{Fs, StdFs},
+		format,
+		out_of_range_key,
+		util::hash_bytes,
+		Context,
+		ErrorKind,
+		Forced,
+		Jobs,
+		Pattern,
+		Planned,
+		PlannedTransformation,
+	},
 	::convert_case::Casing,
+	::core::hash::Hasher,
 	::serde::Deserialize,
 	::std::{
+		env,
 		fs,
 		path::{Path, PathBuf},
 		process::Command,
@@ -18,7 +33,8 @@ use {
 
 #[derive(Deserialize)]
 struct Manifest {
-	pub package: ManifestPackage,
+	pub package: Option<ManifestPackage>,
+	pub workspace: Option<::toml::Table>,
 }
 
 #[derive(Deserialize)]
@@ -26,21 +42,181 @@ struct ManifestPackage {
 	pub name: String,
 }
 
+/// the cargo profile to compile with
+///
+/// `bool`s convert into [`Profile::Release`] (`true`) and [`Profile::Dev`] (`false`), and strings convert by name
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Profile {
+	/// the `dev` profile (`cargo build`)
+	Dev,
+	/// the `release` profile (`cargo build --release`)
+	Release,
+	/// a custom profile defined in the crate's manifest (`cargo build --profile <name>`)
+	Custom(String),
+}
+
+impl Profile {
+	/// read the profile name from an environment variable (ex: `DOLLGEN_WASM_PROFILE=release`), using `default` if it isn't set
+	///
+	/// this lets different environments (ex: CI and local builds) build differently without changing code
+	#[must_use]
+	pub fn from_env(var: &str, default: Self) -> Self {
+		env::var(var).map_or(default, Self::from)
+	}
+
+	/// the name of the profile, as given to `cargo build --profile`
+	#[must_use]
+	pub fn name(&self) -> &str {
+		match self {
+			Self::Dev => "dev",
+			Self::Release => "release",
+			Self::Custom(name) => name,
+		}
+	}
+
+	/// the directory inside of the target directory that cargo outputs artifacts of this profile to
+	#[must_use]
+	pub fn dir(&self) -> &str {
+		match self.name() {
+			"dev" | "test" => "debug",
+			"bench" => "release",
+			name => name,
+		}
+	}
+
+	/// whether artifacts are built for debugging, in which case debug info is kept by wasm-bindgen
+	#[must_use]
+	pub fn is_debug(&self) -> bool {
+		self.dir() == "debug"
+	}
+}
+
+impl From<bool> for Profile {
+	fn from(release: bool) -> Self {
+		if release {
+			Self::Release
+		} else {
+			Self::Dev
+		}
+	}
+}
+
+impl From<&str> for Profile {
+	fn from(name: &str) -> Self {
+		match name {
+			"dev" | "debug" => Self::Dev,
+			"release" => Self::Release,
+			name => Self::Custom(name.to_string()),
+		}
+	}
+}
+
+impl From<String> for Profile {
+	fn from(name: String) -> Self {
+		Self::from(name.as_str())
+	}
+}
+
+/// the target triple that is compiled for by default
+pub const DEFAULT_TARGET: &str = "wasm32-unknown-unknown";
+
+/// how to compile rust libraries to wasm
+///
+/// anything that converts into a [`Profile`] (ex: `true` for release mode) converts into options with that profile, and defaults for the rest
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CompileOptions {
+	/// the cargo profile to compile with
+	pub profile: Profile,
+	/// the target triple to compile for (`cargo build --target`), which must be installed (ex: `rustup target add wasm32v1-none`)
+	///
+	/// bindings are always generated with wasm-bindgen, so only targets without an os are supported (ex: [`DEFAULT_TARGET`], or `wasm32v1-none`), not emscripten or wasi targets
+	pub target: String,
+	/// whether to empty the bindgen output directory before regenerating it, so that bindings for removed exports aren't left behind
+	pub clean_bindgen: bool,
+	/// where to put bindgen output (ex: a temporary directory), which is nested by the crate's directory, like the target directory
+	///
+	/// if `None`, it is put in `bindgen` in the target directory
+	pub bindgen_dir: Option<PathBuf>,
+	/// how many jobs cargo may run at once (`cargo build --jobs`)
+	///
+	/// each compile holds one slot of the build's [`Jobs`] while cargo runs, so rules with `plan_parallel` may run up to [`BuildOptions::jobs`](crate::BuildOptions::jobs) cargo processes at once, each running this many jobs,
+	/// so lower this when compiling several crates in parallel to avoid oversubscribing the CPUs
+	///
+	/// if `None`, the build's limit ([`Jobs::limit`]), or if planned without a build's context, cargo decides (which is the number of CPUs, unless configured otherwise)
+	pub jobs: Option<usize>,
+	/// whether wasm-bindgen includes debug-only assertions and error messages in the bindings (`wasm-bindgen --debug`)
+	///
+	/// if `None`, only for profiles built for debugging (see [`Profile::is_debug`])
+	pub debug: Option<bool>,
+	/// whether wasm-bindgen keeps debug info and names in the module (`wasm-bindgen --keep-debug`), ex: to profile a release build, or shrink a dev build
+	///
+	/// if `None`, only for profiles built for debugging (see [`Profile::is_debug`])
+	pub keep_debug: Option<bool>,
+	/// write compressed copies of the `.wasm` module next to it (ex: `app_bg.wasm.br`), which saves a lot of bandwidth as wasm compresses very well
+	///
+	/// the js bindings fetch the `.wasm` itself, so the host must serve the compressed copies in its place (ex: with `Content-Encoding: br`)
+	///
+	/// requires `compress` feature
+	#[cfg(feature = "compress")]
+	pub compress: Vec<crate::compress::Encoding>,
+}
+
+impl<T: Into<Profile>> From<T> for CompileOptions {
+	fn from(profile: T) -> Self {
+		Self {
+			profile: profile.into(),
+			target: DEFAULT_TARGET.to_string(),
+			clean_bindgen: false,
+			bindgen_dir: None,
+			jobs: None,
+			debug: None,
+			keep_debug: None,
+			#[cfg(feature = "compress")]
+			compress: Vec::new(),
+		}
+	}
+}
+
 #[instrument(level = Level::TRACE)]
-fn compile(manifest: PathBuf, release: bool) -> Result<(PathBuf, String), ErrorKind> {
+fn compile(
+	manifest: PathBuf,
+	options: &CompileOptions,
+	context: &Context,
+) -> Result<(PathBuf, String), ErrorKind> {
+	let profile = &options.profile;
+
+	if !is_bindgen_compatible(&options.target) {
+		return Err(WASMErrorKind::IncompatibleTarget(options.target.clone()).into());
+	}
+
 	let start = SystemTime::now();
 
 	let src_dir = manifest.parent().unwrap();
 
-	let crate_name = ::toml::from_str::<Manifest>(
+	let crate_name = match ::toml::from_str::<Manifest>(
 		&fs::read_to_string(&manifest).map_err(WASMErrorKind::FailedManifestRead)?,
 	)
 	.map_err(WASMErrorKind::BadManifest)?
-	.package
-	.name
+	{
+		Manifest {
+			package: Some(package),
+			..
+		} => package.name,
+		Manifest { workspace, .. } => {
+			return Err(WASMErrorKind::NotAPackageManifest {
+				manifest,
+				workspace: workspace.is_some(),
+			}
+			.into())
+		}
+	}
 	.to_case(::convert_case::Case::Snake);
 
 	let target_dir = Path::new("target/dollgen").join(src_dir);
+	let bindgen_dir = match &options.bindgen_dir {
+		Some(dir) => dir.join(src_dir),
+		None => target_dir.join("bindgen"),
+	};
 
 	// build
 	{
@@ -59,12 +235,16 @@ fn compile(manifest: PathBuf, release: bool) -> Result<(PathBuf, String), ErrorK
 					.ok_or(ErrorKind::NonUTF8PathCharacters)?,
 			)
 			.arg("--target")
-			.arg("wasm32-unknown-unknown");
+			.arg(&options.target)
+			.arg("--profile")
+			.arg(profile.name());
 
-		if release {
-			command.arg("--release");
+		let jobs = context.get::<Jobs>();
+		if let Some(jobs) = options.jobs.or_else(|| jobs.map(Jobs::limit)) {
+			command.arg("--jobs").arg(jobs.to_string());
 		}
 
+		let _slot = jobs.map(Jobs::acquire);
 		let out = command
 			.output()
 			.map_err(WASMErrorKind::BuildProcessFailed)?;
@@ -82,15 +262,29 @@ fn compile(manifest: PathBuf, release: bool) -> Result<(PathBuf, String), ErrorK
 	// bindgen
 	{
 		let input = target_dir
-			.join("wasm32-unknown-unknown")
-			.join(if release { "release" } else { "debug" })
+			.join(&options.target)
+			.join(profile.dir())
 			.join(&crate_name)
 			.with_extension("wasm");
-		let bindgen_target = target_dir.join("bindgen");
-
-		let _trace_span = trace_span!("wasm-bindgen", ?input, ?bindgen_target).entered();
+		let _trace_span = trace_span!("wasm-bindgen", ?input, ?bindgen_dir).entered();
+
+		let debug = options.debug.unwrap_or_else(|| profile.is_debug());
+		let keep_debug = options.keep_debug.unwrap_or_else(|| profile.is_debug());
+		let stamp = bindgen_stamp(debug, keep_debug);
+
+		// a forced build regenerates the bindings even if the module didn't change (see `BuildOptions::force`)
+		let forced = context.get::<Forced>().is_some();
+
+		if forced
+			|| fs::metadata(&input)?.modified()? >= start
+			|| !bindgen_dir.exists()
+			|| !stamp_matches(&bindgen_dir, &stamp)
+		{
+			if options.clean_bindgen && bindgen_dir.exists() {
+				trace!("cleaning bindgen output");
+				fs::remove_dir_all(&bindgen_dir)?;
+			}
 
-		if fs::metadata(&input)?.modified()? >= start {
 			let mut bindgen = Bindgen::new();
 
 			bindgen
@@ -98,23 +292,59 @@ fn compile(manifest: PathBuf, release: bool) -> Result<(PathBuf, String), ErrorK
 				.input_path(input.to_str().ok_or(ErrorKind::NonUTF8PathCharacters)?)
 				.web(true)
 				.map_err(WASMErrorKind::BindgenFailed)?
-				.debug(!release)
-				.keep_debug(!release)
+				.debug(debug)
+				.keep_debug(keep_debug)
 				.typescript(true);
 
 			bindgen
 				.generate(
-					bindgen_target
+					bindgen_dir
 						.to_str()
 						.ok_or(ErrorKind::NonUTF8PathCharacters)?,
 				)
 				.map_err(|err| WASMErrorKind::BindgenFailed(err.into()))?;
+
+			fs::write(bindgen_dir.join(STAMP_FILE), stamp)?;
 		} else {
 			error!("skipped (compiled wasm didn't change)");
 		}
 	}
 
-	Ok((target_dir.join("bindgen"), crate_name))
+	Ok((bindgen_dir, crate_name))
+}
+
+/// the file in the bindgen output directory that records the options the bindings were generated with
+const STAMP_FILE: &str = ".dollgen-bindgen";
+
+/// the options bindings are generated with, which aren't reflected in the compiled module, so changing them must regenerate the bindings
+fn bindgen_stamp(debug: bool, keep_debug: bool) -> String {
+	format!("debug = {debug}\nkeep_debug = {keep_debug}\n")
+}
+
+/// whether the bindings in `bindgen_dir` were generated with the options in `stamp`
+fn stamp_matches(bindgen_dir: &Path, stamp: &str) -> bool {
+	fs::read_to_string(bindgen_dir.join(STAMP_FILE)).is_ok_and(|previous| previous == stamp)
+}
+
+/// whether wasm-bindgen can generate bindings for modules compiled for `target`, which is only the case for wasm targets without an os
+fn is_bindgen_compatible(target: &str) -> bool {
+	target.starts_with("wasm")
+		&& (target.ends_with("-unknown-unknown") || target.ends_with("-none"))
+}
+
+/// make sure a format-string only refers to captures that `include` produces, before any file is planned
+fn validate_format(name: &'static str, fmt: &str, include: &[Pattern]) -> Result<(), ErrorKind> {
+	let available = include.iter().map(capture_count).max().unwrap_or_default();
+
+	match out_of_range_key(fmt, available, &[])? {
+		Some(index) => Err(WASMErrorKind::CaptureOutOfRange {
+			name,
+			index,
+			available,
+		}
+		.into()),
+		None => Ok(()),
+	}
 }
 
 /// a plan to finish compiling a wasm module
@@ -126,6 +356,11 @@ pub struct WASMPlan {
 	pub crate_name: String,
 	/// the kind of plan
 	pub kind: WASMPlanKind,
+	/// which encodings to write compressed copies of the `.wasm` module in (see [`CompileOptions::compress`])
+	///
+	/// requires `compress` feature
+	#[cfg(feature = "compress")]
+	pub compress: Vec<crate::compress::Encoding>,
 }
 
 /// the kind of plan
@@ -147,90 +382,187 @@ pub enum WASMPlanKind {
 	},
 }
 
+impl WASMPlan {
+	/// the path of a file emitted by bindgen, making sure that it exists
+	fn artifact(&self, suffix: &str) -> Result<PathBuf, ErrorKind> {
+		let path = self
+			.bindgen_dir
+			.join(format!("{}{suffix}", self.crate_name));
+
+		if path.is_file() {
+			Ok(path)
+		} else {
+			Err(WASMErrorKind::MissingBindgenArtifact(path).into())
+		}
+	}
+}
+
+/// write a bindgen artifact, which is always on the real filesystem, to an output on `fs`
+fn emit(fs: &dyn Fs, from: &Path, to: &Path) -> Result<(), ErrorKind> {
+	fs.write(to, &::std::fs::read(from)?)?;
+	Ok(())
+}
+
 impl PlannedTransformation for WASMPlan {
-	#[instrument(name = "wasm", level = Level::DEBUG)]
 	fn execute(self: Box<Self>, dst_file: PathBuf) -> Result<(), ErrorKind> {
+		self.execute_in(dst_file, &StdFs)
+	}
+
+	#[instrument(name = "wasm", level = Level::DEBUG, skip(fs))]
+	fn execute_in(self: Box<Self>, dst_file: PathBuf, fs: &dyn Fs) -> Result<(), ErrorKind> {
 		match &self.kind {
 			WASMPlanKind::Wasm { js } | WASMPlanKind::Both { js, .. } => {
-				let from = self
-					.bindgen_dir
-					.join(format!("{}_bg.wasm", self.crate_name));
+				let from = self.artifact("_bg.wasm")?;
 				let to = &dst_file;
 				trace!(?from, ?to, ".wasm");
-				fs::copy(from, to)?;
+				emit(fs, &from, to)?;
+				#[cfg(feature = "compress")]
+				crate::compress::compress_file_in(fs, to, &self.compress)?;
 
-				let from = self.bindgen_dir.join(format!("{}.js", self.crate_name));
+				let from = self.artifact(".js")?;
 				let to = js;
 				trace!(?from, ?to, ".js");
-				fs::create_dir_all(to.parent().unwrap())?;
-				fs::copy(from, to)?;
+				fs.create_dir_all(to.parent().unwrap())?;
+				emit(fs, &from, to)?;
 			}
 			_ => {}
 		}
 
 		match &self.kind {
 			WASMPlanKind::TypescriptDeclarations => {
-				let from = self.bindgen_dir.join(format!("{}.d.ts", self.crate_name));
+				let from = self.artifact(".d.ts")?;
 				let to = &dst_file;
 				trace!(?from, ?to, ".d.ts");
-				fs::copy(from, to)?;
+				emit(fs, &from, to)?;
 			}
 			WASMPlanKind::Both { d_ts, .. } => {
-				let from = self.bindgen_dir.join(format!("{}.d.ts", self.crate_name));
+				let from = self.artifact(".d.ts")?;
 				let to = d_ts;
 				trace!(?from, ?to, ".d.ts");
-				fs::create_dir_all(to.parent().unwrap())?;
-				fs::copy(from, to)?;
+				fs.create_dir_all(to.parent().unwrap())?;
+				emit(fs, &from, to)?;
 			}
 			_ => {}
 		}
 
 		Ok(())
 	}
+
+	fn outputs(&self, dst_file: &Path) -> Vec<PathBuf> {
+		#[cfg_attr(
+			not(feature = "compress"),
+			expect(unused_mut, reason = "only compressed copies are added")
+		)]
+		let mut outputs = match &self.kind {
+			WASMPlanKind::Wasm { js } => vec![dst_file.to_path_buf(), js.clone()],
+			WASMPlanKind::TypescriptDeclarations => vec![dst_file.to_path_buf()],
+			WASMPlanKind::Both { js, d_ts } => {
+				vec![dst_file.to_path_buf(), js.clone(), d_ts.clone()]
+			}
+		};
+
+		#[cfg(feature = "compress")]
+		if !matches!(self.kind, WASMPlanKind::TypescriptDeclarations) {
+			outputs.extend(self.compress.iter().map(|encoding| encoding.path(dst_file)));
+		}
+
+		outputs
+	}
+
+	fn inputs(&self) -> Vec<PathBuf> {
+		let suffixes: &[&str] = match self.kind {
+			WASMPlanKind::Wasm { .. } => &["_bg.wasm", ".js"],
+			WASMPlanKind::TypescriptDeclarations => &[".d.ts"],
+			WASMPlanKind::Both { .. } => &["_bg.wasm", ".js", ".d.ts"],
+		};
+
+		suffixes
+			.iter()
+			.map(|suffix| {
+				self.bindgen_dir
+					.join(format!("{}{suffix}", self.crate_name))
+			})
+			.collect()
+	}
+
+	fn fingerprint(&self, state: &mut dyn Hasher) {
+		match &self.kind {
+			WASMPlanKind::Wasm { js } => hash_bytes(state, js.as_os_str().as_encoded_bytes()),
+			WASMPlanKind::TypescriptDeclarations => {}
+			WASMPlanKind::Both { js, d_ts } => {
+				hash_bytes(state, js.as_os_str().as_encoded_bytes());
+				hash_bytes(state, d_ts.as_os_str().as_encoded_bytes());
+			}
+		}
+
+		#[cfg(feature = "compress")]
+		for encoding in &self.compress {
+			state.write_u8(*encoding as u8);
+		}
+	}
 }
 
 /// compile rust libraries to wasm and include bindings
 ///
-/// - `release` - whether to compile in release mode
+/// - `options` - how to compile, see [`CompileOptions`] (ex: `true` for release mode)
+/// - `include` - the rule's `include` patterns, which `js` may only refer to the captures of
 /// - `js` - the [`format string`](crate::format) to use to determine where to put the js binding file,
 ///   ultimately you should be importing this in your javascript code
 ///
+/// fails if `js` refers to a capture that `include` doesn't produce, before any file is planned
+///
 /// [see module-level documentation for help](crate::wasm)
 pub fn create_wasm_with_bindings(
-	release: bool,
+	options: impl Into<CompileOptions>,
+	include: &[Pattern],
 	js: &'static str,
-) -> impl FnMut(PathBuf, Vec<String>) -> Result<Box<dyn PlannedTransformation>, ErrorKind> {
-	move |src_file, cap| {
-		let _trace_span = debug_span!("wasm", ?release, ?js).entered();
-
-		let (bindgen_dir, crate_name) = compile(src_file.with_file_name("Cargo.toml"), release)?;
-
-		Ok(Box::new(WASMPlan {
-			bindgen_dir,
-			crate_name,
-			kind: WASMPlanKind::Wasm {
-				js: PathBuf::from(format(&js, &cap)?),
-			},
-		}))
-	}
+) -> Result<impl FnMut(PathBuf, Vec<String>, &Path, &Context) -> Planned, ErrorKind> {
+	validate_format("js", js, include)?;
+
+	let options = options.into();
+
+	Ok(
+		move |src_file: PathBuf, cap: Vec<String>, _: &Path, context: &Context| {
+			let _trace_span = debug_span!("wasm", ?options, ?js).entered();
+
+			let (bindgen_dir, crate_name) =
+				compile(src_file.with_file_name("Cargo.toml"), &options, context)?;
+
+			Ok(Box::new(WASMPlan {
+				bindgen_dir,
+				crate_name,
+				#[cfg(feature = "compress")]
+				compress: options.compress.clone(),
+				kind: WASMPlanKind::Wasm {
+					js: PathBuf::from(format(js, &cap)?),
+				},
+			}) as Box<dyn PlannedTransformation>)
+		},
+	)
 }
 
 /// compile rust libraries to wasm and output the typescript `.d.ts` declaration file for the js module
 ///
-/// - `release` - whether to compile in release mode
+/// - `options` - how to compile, see [`CompileOptions`] (ex: `true` for release mode)
 ///
 /// [see module-level documentation for help](crate::wasm)
 pub fn create_typescript_declarations(
-	release: bool,
-) -> impl FnMut(PathBuf, Vec<String>) -> Result<Box<dyn PlannedTransformation>, ErrorKind> {
-	move |src_file, _| {
-		let _trace_span = debug_span!("typescript declarations", ?release).entered();
+	options: impl Into<CompileOptions>,
+) -> impl FnMut(PathBuf, Vec<String>, &Path, &Context) -> Result<Box<dyn PlannedTransformation>, ErrorKind>
+{
+	let options = options.into();
+
+	move |src_file, _, _: &Path, context: &Context| {
+		let _trace_span = debug_span!("typescript declarations", ?options).entered();
 
-		let (bindgen_dir, crate_name) = compile(src_file.with_file_name("Cargo.toml"), release)?;
+		let (bindgen_dir, crate_name) =
+			compile(src_file.with_file_name("Cargo.toml"), &options, context)?;
 
 		Ok(Box::new(WASMPlan {
 			bindgen_dir,
 			crate_name,
+			#[cfg(feature = "compress")]
+			compress: options.compress.clone(),
 			kind: WASMPlanKind::TypescriptDeclarations,
 		}))
 	}
@@ -238,28 +570,45 @@ pub fn create_typescript_declarations(
 
 /// compile rust libraries to wasm and output the typescript `.d.ts` declaration file for the js module
 ///
-/// - `release` - whether to compile in release mode
+/// - `options` - how to compile, see [`CompileOptions`] (ex: `true` for release mode)
+/// - `include` - the rule's `include` patterns, which `js` and `d_ts` may only refer to the captures of
+/// - `js` - the [`format string`](crate::format) to use to determine where to put the js binding file
+/// - `d_ts` - the [`format string`](crate::format) to use to determine where to put the `.d.ts` declaration file
+///
+/// fails if `js` or `d_ts` refers to a capture that `include` doesn't produce, before any file is planned
 ///
 /// [see module-level documentation for help](crate::wasm)
 pub fn create_both(
-	release: bool,
+	options: impl Into<CompileOptions>,
+	include: &[Pattern],
 	js: &'static str,
 	d_ts: &'static str,
-) -> impl FnMut(PathBuf, Vec<String>) -> Result<Box<dyn PlannedTransformation>, ErrorKind> {
-	move |src_file, cap| {
-		let _trace_span = debug_span!("wasm + typescript declarations", ?release, ?js).entered();
-
-		let (bindgen_dir, crate_name) = compile(src_file.with_file_name("Cargo.toml"), release)?;
-
-		Ok(Box::new(WASMPlan {
-			bindgen_dir,
-			crate_name,
-			kind: WASMPlanKind::Both {
-				js: PathBuf::from(format(&js, &cap)?),
-				d_ts: PathBuf::from(format(&d_ts, &cap)?),
-			},
-		}))
-	}
+) -> Result<impl FnMut(PathBuf, Vec<String>, &Path, &Context) -> Planned, ErrorKind> {
+	validate_format("js", js, include)?;
+	validate_format("d_ts", d_ts, include)?;
+
+	let options = options.into();
+
+	Ok(
+		move |src_file: PathBuf, cap: Vec<String>, _: &Path, context: &Context| {
+			let _trace_span =
+				debug_span!("wasm + typescript declarations", ?options, ?js).entered();
+
+			let (bindgen_dir, crate_name) =
+				compile(src_file.with_file_name("Cargo.toml"), &options, context)?;
+
+			Ok(Box::new(WASMPlan {
+				bindgen_dir,
+				crate_name,
+				#[cfg(feature = "compress")]
+				compress: options.compress.clone(),
+				kind: WASMPlanKind::Both {
+					js: PathBuf::from(format(js, &cap)?),
+					d_ts: PathBuf::from(format(d_ts, &cap)?),
+				},
+			}) as Box<dyn PlannedTransformation>)
+		},
+	)
 }
 
 /// an error while compiling wasm
@@ -278,6 +627,31 @@ pub enum WASMErrorKind {
 	#[diagnostic(code(dollgen::wasm::manifest::parse))]
 	BadManifest(#[source] ::toml::de::Error),
 
+	/// Cargo.toml manifest has no `[package]`, such as a virtual workspace manifest
+	#[error(
+		"{} is {}, not a package manifest",
+		.manifest.display(),
+		if *.workspace { "a virtual workspace manifest" } else { "missing `[package]`" }
+	)]
+	#[diagnostic(
+		code(dollgen::wasm::manifest::not_a_package),
+		help("point the rule at the build file of the member crate to compile, not the workspace root")
+	)]
+	NotAPackageManifest {
+		/// the manifest that was read
+		manifest: PathBuf,
+		/// whether the manifest defines a `[workspace]`
+		workspace: bool,
+	},
+
+	/// the target triple can't be used with wasm-bindgen
+	#[error("can't generate bindings for target `{0}`")]
+	#[diagnostic(
+		code(dollgen::wasm::incompatible_target),
+		help("wasm-bindgen only supports wasm targets without an os (ex: `wasm32-unknown-unknown` or `wasm32v1-none`), emscripten and wasi targets are not supported")
+	)]
+	IncompatibleTarget(String),
+
 	/// failed to run `cargo build``
 	#[error("failed to run `cargo build`")]
 	#[diagnostic(
@@ -298,8 +672,104 @@ pub enum WASMErrorKind {
 		span: (usize, usize),
 	},
 
+	/// a format-string references a capture that the `include` pattern did not produce
+	#[error("`{name}` references capture {{{index}}}, but only {available} were captured")]
+	#[diagnostic(
+		code(dollgen::wasm::capture_out_of_range),
+		help("captures are numbered from 0, in the order their `(` appears in the pattern")
+	)]
+	CaptureOutOfRange {
+		/// which format-string is invalid
+		name: &'static str,
+		/// the capture that was referenced
+		index: usize,
+		/// how many captures were produced
+		available: usize,
+	},
+
+	/// bindgen didn't emit a file that was expected of it
+	#[error("bindgen did not emit {}", .0.display())]
+	#[diagnostic(
+		code(dollgen::wasm::bindgen::missing_artifact),
+		help("make sure the crate exports something with `#[wasm_bindgen]`")
+	)]
+	MissingBindgenArtifact(PathBuf),
+
 	/// bindgen failed
 	#[error("bindgen failed")]
 	#[diagnostic(code(dollgen::wasm::bindgen::fail))]
 	BindgenFailed(#[source] ::anyhow::Error),
 }
+
+#[cfg(test)]
+mod tests {
+	use {
+		super::*,
+		::std::{env, process},
+	};
+
+	/// a crate with an empty `lib.rs` exports nothing, so bindgen leaves only the module behind
+	#[test]
+	fn missing_bindgen_artifact_is_named() {
+		let root = env::temp_dir().join(format!("dollgen-missing-artifact-{}", process::id()));
+		let bindgen_dir = root.join("bindgen");
+		fs::create_dir_all(&bindgen_dir).unwrap();
+		fs::write(bindgen_dir.join("empty_bg.wasm"), b"\0asm").unwrap();
+
+		let plan = Box::new(WASMPlan {
+			bindgen_dir: bindgen_dir.clone(),
+			crate_name: "empty".to_string(),
+			kind: WASMPlanKind::Both {
+				js: root.join("out/empty.js"),
+				d_ts: root.join("out/empty.d.ts"),
+			},
+			#[cfg(feature = "compress")]
+			compress: Vec::new(),
+		});
+		let result = plan.execute(root.join("empty.wasm"));
+		fs::remove_dir_all(&root).unwrap();
+
+		match result {
+			Err(ErrorKind::WASMIntegration(WASMErrorKind::MissingBindgenArtifact(path))) => {
+				assert_eq!(path, bindgen_dir.join("empty.js"));
+			}
+			result => panic!("expected a missing artifact, got {result:?}"),
+		}
+	}
+
+	#[test]
+	fn out_of_range_captures_fail_on_create() {
+		let include = [Pattern::new("src/(**)/.build-wasm").unwrap()];
+
+		assert!(create_both(true, &include, "deploy/{0}.js", "gen_types/{0}.d.ts").is_ok());
+		match create_both(true, &include, "deploy/{0}.js", "gen_types/{1}.d.ts") {
+			Err(ErrorKind::WASMIntegration(WASMErrorKind::CaptureOutOfRange {
+				name,
+				index,
+				available,
+			})) => {
+				assert_eq!((name, index, available), ("d_ts", 1, 1));
+			}
+			Err(err) => panic!("expected an out of range capture, got {err:?}"),
+			Ok(_) => panic!("expected an out of range capture"),
+		}
+	}
+
+	#[test]
+	fn changed_bindgen_options_regenerate() {
+		let bindgen_dir = env::temp_dir().join(format!("dollgen-bindgen-stamp-{}", process::id()));
+		fs::create_dir_all(&bindgen_dir).unwrap();
+
+		let missing = stamp_matches(&bindgen_dir, &bindgen_stamp(false, false));
+		fs::write(bindgen_dir.join(STAMP_FILE), bindgen_stamp(false, false)).unwrap();
+		let same = stamp_matches(&bindgen_dir, &bindgen_stamp(false, false));
+		let debug = stamp_matches(&bindgen_dir, &bindgen_stamp(true, false));
+		let keep_debug = stamp_matches(&bindgen_dir, &bindgen_stamp(false, true));
+		fs::remove_dir_all(&bindgen_dir).unwrap();
+
+		assert!(!missing);
+		assert!(same);
+		assert!(!debug);
+		assert!(!keep_debug);
+	}
+}
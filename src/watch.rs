@@ -0,0 +1,240 @@
+//! rebuild whenever source files change
+//!
+//! changes that happen close together (ex: a `git checkout` touching hundreds of files) are batched, so they cause a single rebuild instead of one per file
+//!
+//! changes to the output directory, editor temporary files (ex: vim's `.swp` files), and files that were deleted are ignored
+//!
+//! requires `watch` feature
+
+use {
+	crate::{execute, plan_files, plan_with, BuildOptions, ErrorKind, Rule},
+	::notify::{Event, EventKind, RecursiveMode, Watcher},
+	::std::{
+		collections::BTreeSet,
+		env,
+		path::{Path, PathBuf},
+		sync::mpsc::{self, Receiver},
+		time::Duration,
+	},
+	::tracing::{debug, error},
+};
+
+/// batches the paths from filesystem events, so that changes that happen within `window` of each other are handled together
+#[derive(Debug)]
+pub struct Debouncer {
+	events: Receiver<::notify::Result<Event>>,
+	window: Duration,
+	ignore: Vec<PathBuf>,
+}
+
+impl Debouncer {
+	/// batch the events from `events`
+	///
+	/// - `window` - how long to wait for another change after one happens, before ending the batch (ex: 100ms)
+	#[must_use]
+	pub const fn new(events: Receiver<::notify::Result<Event>>, window: Duration) -> Self {
+		Self {
+			events,
+			window,
+			ignore: Vec::new(),
+		}
+	}
+
+	/// also ignore changes to anything under `dir` (ex: the output directory, so that a build doesn't trigger another)
+	///
+	/// `dir` must be absolute, like the paths in events
+	#[must_use]
+	pub fn ignoring(mut self, dir: impl Into<PathBuf>) -> Self {
+		self.ignore.push(dir.into());
+		self
+	}
+
+	/// wait for the next batch of changed paths
+	///
+	/// the batch ends once no change happens for `window`, returns `None` once the events stop (the watcher was dropped)
+	///
+	/// paths that no longer exist when the batch ends (ex: deleted, or renamed away) are left out, since there is nothing to build from them, and a batch of only those is skipped
+	pub fn next_batch(&self) -> Option<BTreeSet<PathBuf>> {
+		let mut batch = BTreeSet::new();
+
+		while batch.is_empty() {
+			// block until the first change, then collect until it goes quiet
+			while batch.is_empty() {
+				self.collect(&mut batch, self.events.recv().ok()?);
+			}
+
+			while let Ok(event) = self.events.recv_timeout(self.window) {
+				self.collect(&mut batch, event);
+			}
+
+			batch.retain(|path| path.exists());
+		}
+
+		debug!(changed = batch.len(), "batched changes");
+
+		Some(batch)
+	}
+
+	/// add the paths an event changed to `batch`, ignoring events that don't change anything and paths that aren't sources
+	fn collect(&self, batch: &mut BTreeSet<PathBuf>, event: ::notify::Result<Event>) {
+		match event {
+			Ok(event) => {
+				if !matches!(event.kind, EventKind::Access(_)) {
+					batch.extend(event.paths.into_iter().filter(|path| {
+						!is_editor_temporary(path)
+							&& !self.ignore.iter().any(|dir| path.starts_with(dir))
+					}));
+				}
+			}
+			Err(err) => error!(%err, "watch error"),
+		}
+	}
+}
+
+/// whether `path` is a file editors write while saving or editing, rather than a source
+///
+/// ex: `.page.doll.swp` and `4913` (vim), `page.doll~` (vim, emacs), `.#page.doll` and `#page.doll#` (emacs)
+fn is_editor_temporary(path: &Path) -> bool {
+	let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+		return false;
+	};
+
+	name == "4913"
+		|| name.ends_with('~')
+		|| name.starts_with(".#")
+		|| (name.len() > 1 && name.starts_with('#') && name.ends_with('#'))
+		|| (name.starts_with('.')
+			&& Path::new(name)
+				.extension()
+				.is_some_and(|ext| ext == "swp" || ext == "swo" || ext == "swx"))
+}
+
+/// build, then rebuild whenever anything in `roots` changes, forever
+///
+/// each batch of changes (see [`Debouncer`]) is planned once with [`plan_files`], so only the changed files are rebuilt, unless a changed file is not included by any rule (ex: a template), in which case everything is rebuilt
+///
+/// - `roots` - directories to watch recursively (ex: `src`), changes under [`BuildOptions::output_root`] are ignored if it is set, so that a build doesn't trigger another
+/// - `window` - how long to wait for another change after one happens, before rebuilding (ex: 100ms)
+/// - `after` - called with the result of every build, errors do not stop watching (ex: to print them, or [`LiveReload::reload`](crate::livereload::LiveReload::reload) on success)
+///
+/// only returns if watching fails
+pub fn watch(
+	rules: &mut [Rule<'_>],
+	options: &BuildOptions<'_>,
+	roots: &[&Path],
+	window: Duration,
+	mut after: impl FnMut(Result<(), ErrorKind>),
+) -> Result<(), ErrorKind> {
+	let (sender, receiver) = mpsc::channel();
+	// kept alive until watching ends, dropping it stops the events
+	let mut watcher = ::notify::recommended_watcher(sender)?;
+	for root in roots {
+		watcher.watch(root, RecursiveMode::Recursive)?;
+	}
+
+	let cwd = env::current_dir()?;
+
+	after(plan_with(rules, options).and_then(execute));
+
+	let mut debouncer = Debouncer::new(receiver, window);
+	if let Some(output_root) = options.output_root {
+		debouncer = debouncer.ignoring(cwd.join(output_root));
+	}
+	while let Some(batch) = debouncer.next_batch() {
+		// events have absolute paths, but `include` patterns are relative
+		let files = batch
+			.into_iter()
+			.map(|path| {
+				path.strip_prefix(&cwd)
+					.map(Path::to_path_buf)
+					.unwrap_or(path)
+			})
+			.collect::<Vec<_>>();
+
+		let plans = if files
+			.iter()
+			.all(|file| rules.iter().any(|rule| rule.includes(file)))
+		{
+			plan_files(rules, &files, options)
+		} else {
+			debug!("a changed file is not included by any rule, rebuilding everything");
+			plan_with(rules, options)
+		};
+
+		after(plans.and_then(execute));
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use {
+		super::*,
+		::notify::event::{CreateKind, ModifyKind, RemoveKind},
+		::std::{fs, process},
+	};
+
+	fn batch_of(root: &Path, events: impl IntoIterator<Item = Event>) -> Option<BTreeSet<PathBuf>> {
+		let (sender, receiver) = mpsc::channel();
+		for event in events {
+			sender.send(Ok(event)).unwrap();
+		}
+		drop(sender);
+
+		Debouncer::new(receiver, Duration::ZERO)
+			.ignoring(root.join("deploy"))
+			.next_batch()
+	}
+
+	#[test]
+	fn ignores_output_and_editor_files() {
+		let root = env::temp_dir().join(format!("dollgen-watch-ignore-{}", process::id()));
+		fs::create_dir_all(root.join("deploy")).unwrap();
+		for file in [
+			"page.doll",
+			".page.doll.swp",
+			"page.doll~",
+			"deploy/page.html",
+		] {
+			fs::write(root.join(file), "").unwrap();
+		}
+
+		let batch = batch_of(
+			&root,
+			[
+				"page.doll",
+				".page.doll.swp",
+				"page.doll~",
+				"deploy/page.html",
+			]
+			.map(|file| Event::new(EventKind::Modify(ModifyKind::Any)).add_path(root.join(file))),
+		);
+		fs::remove_dir_all(&root).unwrap();
+
+		assert_eq!(batch, Some(BTreeSet::from([root.join("page.doll")])));
+	}
+
+	#[test]
+	fn deleted_files_are_left_out() {
+		let root = env::temp_dir().join(format!("dollgen-watch-deleted-{}", process::id()));
+		fs::create_dir_all(&root).unwrap();
+		fs::write(root.join("kept.doll"), "").unwrap();
+
+		let batch = batch_of(
+			&root,
+			[
+				Event::new(EventKind::Create(CreateKind::File)).add_path(root.join("kept.doll")),
+				Event::new(EventKind::Remove(RemoveKind::File)).add_path(root.join("gone.doll")),
+			],
+		);
+		let only_deleted = batch_of(
+			&root,
+			[Event::new(EventKind::Remove(RemoveKind::File)).add_path(root.join("gone.doll"))],
+		);
+		fs::remove_dir_all(&root).unwrap();
+
+		assert_eq!(batch, Some(BTreeSet::from([root.join("kept.doll")])));
+		assert_eq!(only_deleted, None);
+	}
+}